@@ -1,3 +1,4 @@
 export const JWT_ALGORITHM = "RS256" as const;
+export const EDDSA_ALGORITHM = "EdDSA" as const;
 export const SECONDS_IN_MILLISECOND = 1000;
 export const NS_PER_SECOND = 1e9;
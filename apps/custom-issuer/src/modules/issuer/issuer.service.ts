@@ -1,8 +1,9 @@
 import { Injectable, UnauthorizedException } from "@nestjs/common";
 import { ConfigService } from "@nestjs/config";
 import * as jwt from "jsonwebtoken";
+import { sign } from "crypto";
 import { KeyService } from "./key.service";
-import { JWT_ALGORITHM, NS_PER_SECOND, SECONDS_IN_MILLISECOND } from "./issuer.constants";
+import { EDDSA_ALGORITHM, JWT_ALGORITHM, NS_PER_SECOND, SECONDS_IN_MILLISECOND } from "./issuer.constants";
 import { ErrorMessage } from "./issuer.errors";
 import { TokenClaims } from "./issuer.types";
 import { Config } from "../../config";
@@ -154,11 +155,28 @@ export class IssuerService {
         const signingKey = this.keyService.getSigningKey();
         const payload = this.buildTokenPayload(claims);
 
+        if (this.keyService.getSigningAlgorithm() === EDDSA_ALGORITHM) {
+            return this.createEdDsaToken(payload, signingKey);
+        }
+
         return jwt.sign(payload, signingKey, {
             algorithm: JWT_ALGORITHM,
         });
     }
 
+    private createEdDsaToken(payload: jwt.JwtPayload, signingKey: string): string {
+        // jsonwebtoken does not support EdDSA, so the token is signed with the Ed25519 key directly
+        const header = this.encodeSegment({ alg: EDDSA_ALGORITHM, typ: "JWT" });
+        const body = this.encodeSegment({ ...payload, iat: this.getCurrentTimestamp() });
+        const signature = sign(null, Buffer.from(`${header}.${body}`), signingKey).toString("base64url");
+
+        return `${header}.${body}.${signature}`;
+    }
+
+    private encodeSegment(segment: object): string {
+        return Buffer.from(JSON.stringify(segment)).toString("base64url");
+    }
+
     private buildTokenPayload(claims: TokenClaims): jwt.JwtPayload {
         const payload: jwt.JwtPayload = {
             sub: claims.sub,
@@ -5,3 +5,5 @@ export type TokenClaims = {
     iss?: string;
     fatxn: number[];
 };
+
+export type SigningAlgorithm = "RS256" | "EdDSA";
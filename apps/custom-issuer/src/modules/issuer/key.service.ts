@@ -1,11 +1,15 @@
 import { Injectable, OnModuleInit, Logger } from "@nestjs/common";
 import { ConfigService } from "@nestjs/config";
+import { createPrivateKey } from "crypto";
 import { Config } from "../../config";
+import { EDDSA_ALGORITHM, JWT_ALGORITHM } from "./issuer.constants";
+import { SigningAlgorithm } from "./issuer.types";
 
 @Injectable()
 export class KeyService implements OnModuleInit {
     private readonly logger = new Logger(KeyService.name);
     private signingKey: string | null = null;
+    private signingAlgorithm: SigningAlgorithm = JWT_ALGORITHM;
     private validationPublicKeys: string[] = [];
     private readonly keyBase64: string;
     private readonly validationPublicKeyUrl: string;
@@ -32,9 +36,12 @@ export class KeyService implements OnModuleInit {
     private async loadKeys(): Promise<void> {
         try {
             this.signingKey = this.decodeBase64Key(this.keyBase64);
+            this.signingAlgorithm = this.getKeyAlgorithm(this.signingKey);
             this.validationPublicKeys = await this.loadPublicKeysFromUrl(this.validationPublicKeyUrl);
             this.lastUpdateTime = Date.now();
-            this.logger.log(`Keys loaded successfully. Found ${this.validationPublicKeys.length} validation public key(s)`);
+            this.logger.log(
+                `Keys loaded successfully. Signing with ${this.signingAlgorithm}, found ${this.validationPublicKeys.length} validation public key(s)`,
+            );
         } catch (error) {
             this.logger.error("Failed to load keys", error);
             throw error;
@@ -65,6 +72,19 @@ export class KeyService implements OnModuleInit {
         }
     }
 
+    private getKeyAlgorithm(signingKey: string): SigningAlgorithm {
+        // RSA keys sign RS256 tokens, Ed25519 keys sign the EdDSA tokens that are much cheaper to verify on-chain
+        const keyType = createPrivateKey(signingKey).asymmetricKeyType;
+        switch (keyType) {
+            case "rsa":
+                return JWT_ALGORITHM;
+            case "ed25519":
+                return EDDSA_ALGORITHM;
+            default:
+                throw new Error(`Unsupported signing key type: ${keyType}. Expected an RSA or Ed25519 private key`);
+        }
+    }
+
     private async loadPublicKeysFromUrl(url: string): Promise<string[]> {
         try {
             const response = await fetch(url);
@@ -167,6 +187,10 @@ export class KeyService implements OnModuleInit {
         return this.signingKey;
     }
 
+    getSigningAlgorithm(): SigningAlgorithm {
+        return this.signingAlgorithm;
+    }
+
     getValidationPublicKeys(): string[] {
         if (this.validationPublicKeys.length === 0) {
             throw new Error("Validation public keys not loaded. Check application startup logs.");
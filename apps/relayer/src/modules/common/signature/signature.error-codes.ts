@@ -1,5 +1,6 @@
 export enum SignatureErrorCodes {
     UNSUPPORTED_ALGORITHM = "UNSUPPORTED_ALGORITHM",
+    SIGN_FAILED = "SIGN_FAILED",
 }
 
 export enum ED25519ErrorCodes {
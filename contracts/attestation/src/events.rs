@@ -0,0 +1,20 @@
+use near_sdk::{near, AccountId};
+
+/// NEP-297 events emitted by AttestationContract
+///
+/// Hashes are the hex encoded SHA-256 of the attested public keys, RSA or Ed25519.
+#[near(event_json(standard = "fast-auth"))]
+pub enum AttestationEvent<'a> {
+    /// An attester submitted a set of public keys
+    #[event_version("1.0.0")]
+    AttestationSubmitted { attester: &'a AccountId, hash: &'a str, public_keys: u32 },
+    /// Enough attesters submitted the same set of public keys
+    #[event_version("1.0.0")]
+    QuorumReached { hash: &'a str, attestations: u32, quorum: u32 },
+    /// The attested public keys replaced the previous ones
+    #[event_version("1.0.0")]
+    PublicKeysRotated { hash: &'a str, public_keys: u32 },
+    /// The attested Ed25519 public keys replaced the previous ones
+    #[event_version("1.0.0")]
+    OkpPublicKeysRotated { hash: &'a str, public_keys: u32 },
+}
@@ -9,12 +9,21 @@ use near_sdk::{
     AccountId, BorshStorageKey, PanicOnDefault, borsh::{BorshDeserialize, BorshSerialize}, env, near, require, serde::{Deserialize, Serialize}, store::{IterableMap, Vector}
 };
 use schemars::JsonSchema;
+use crate::events::AttestationEvent;
+
+pub mod events;
+
+const STATE_KEY: &[u8] = b"STATE";
+/// Length of an Ed25519 public key
+const OKP_PUBLIC_KEY_LENGTH: usize = 32;
 
 #[derive(BorshStorageKey)]
 #[near(serializers = [borsh])]
 pub enum Prefix {
     Attestations,
     PublicKeys,
+    OkpAttestations,
+    OkpPublicKeys,
 }
 
 #[near(serializers = [json, borsh])]
@@ -60,6 +69,22 @@ pub struct Attestation {
     public_keys: Vec<PublicKey>,
 }
 
+/// An Ed25519 public key verifying the EdDSA tokens of the issuer
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, JsonSchema)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct OkpPublicKey {
+    x: Vec<u8>,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, JsonSchema)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct OkpAttestation {
+    hash: Vec<u8>,
+    public_keys: Vec<OkpPublicKey>,
+}
+
 #[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
 #[borsh(crate = "near_sdk::borsh")]
 #[serde(crate = "near_sdk::serde")]
@@ -88,6 +113,16 @@ pub struct AttestationContract {
     attestations: IterableMap<AccountId, Attestation>,
     quorum: u32,
     public_keys: Vector<PublicKey>,
+    okp_attestations: IterableMap<AccountId, OkpAttestation>,
+    okp_public_keys: Vector<OkpPublicKey>,
+}
+
+/// AttestationContract state layout prior to Ed25519 key attestation, used for state migration
+#[near(serializers = [borsh])]
+pub struct AttestationContractV1 {
+    attestations: IterableMap<AccountId, Attestation>,
+    quorum: u32,
+    public_keys: Vector<PublicKey>,
 }
 
 // Implement the contract structure
@@ -114,6 +149,8 @@ impl AttestationContract {
             attestations: IterableMap::new(Prefix::Attestations),
             quorum,
             public_keys: Vector::new(Prefix::PublicKeys),
+            okp_attestations: IterableMap::new(Prefix::OkpAttestations),
+            okp_public_keys: Vector::new(Prefix::OkpPublicKeys),
         };
 
         let mut acl = contract.acl_get_or_init();
@@ -142,6 +179,26 @@ impl AttestationContract {
         contract
     }
 
+    /// Migrates the contract state
+    /// # Returns
+    /// * The migrated contract state
+    #[private]
+    #[init(ignore_state)]
+    pub fn migrate() -> Self {
+        let state = env::storage_read(STATE_KEY).expect("Error: No previous state");
+        if let Ok(prev_state) = Self::try_from_slice(&state) {
+            return prev_state;
+        }
+        let prev_state = AttestationContractV1::try_from_slice(&state).expect("Error: Cannot deserialize previous state");
+        Self {
+            attestations: prev_state.attestations,
+            quorum: prev_state.quorum,
+            public_keys: prev_state.public_keys,
+            okp_attestations: IterableMap::new(Prefix::OkpAttestations),
+            okp_public_keys: Vector::new(Prefix::OkpPublicKeys),
+        }
+    }
+
     // Only accounts with Attester role can call this
     #[pause]
     #[access_control_any(roles(Role::Attester, Role::DAO))]
@@ -155,12 +212,18 @@ impl AttestationContract {
         
         // Compute SHA256 hash of the public keys
         let hash = self.compute_public_keys_hash(&public_keys);
+        let hex_hash: String = hash.iter().map(|b| format!("{:02x}", b)).collect();
         
         // Store attestation for this attester
         let attestation = Attestation {
             hash: hash.clone(),
             public_keys: public_keys.clone(),
         };
+        AttestationEvent::AttestationSubmitted {
+            attester: &caller,
+            hash: &hex_hash,
+            public_keys: public_keys.len() as u32,
+        }.emit();
         self.attestations.insert(caller, attestation);
         
         // Count how many attestations match this hash
@@ -171,6 +234,12 @@ impl AttestationContract {
         
         // If quorum is reached, update public keys and reset attestations
         if matching_count >= self.quorum {
+            AttestationEvent::QuorumReached {
+                hash: &hex_hash,
+                attestations: matching_count,
+                quorum: self.quorum,
+            }.emit();
+
             // Clear existing public keys
             self.public_keys.clear();
             
@@ -181,6 +250,11 @@ impl AttestationContract {
             
             // Reset attestations
             self.attestations.clear();
+
+            AttestationEvent::PublicKeysRotated {
+                hash: &hex_hash,
+                public_keys: self.public_keys.len(),
+            }.emit();
         }
     }
 
@@ -188,6 +262,57 @@ impl AttestationContract {
         self.public_keys.iter().cloned().collect()
     }
 
+    // Ed25519 counterpart of attest_public_keys, with its own attestations and quorum count
+    #[pause]
+    #[access_control_any(roles(Role::Attester, Role::DAO))]
+    pub fn attest_okp_public_keys(&mut self, public_keys: Vec<OkpPublicKey>) {
+        require!(!public_keys.is_empty(), "Public keys cannot be empty");
+        for pk in &public_keys {
+            require!(pk.x.len() == OKP_PUBLIC_KEY_LENGTH, "Ed25519 public keys must be 32 bytes long");
+        }
+
+        let caller = env::predecessor_account_id();
+        let hash = env::sha256(public_keys.iter().flat_map(|pk| pk.x.iter().copied()).collect::<Vec<u8>>());
+        let hex_hash: String = hash.iter().map(|b| format!("{:02x}", b)).collect();
+
+        AttestationEvent::AttestationSubmitted {
+            attester: &caller,
+            hash: &hex_hash,
+            public_keys: public_keys.len() as u32,
+        }.emit();
+        self.okp_attestations.insert(caller, OkpAttestation { hash: hash.clone(), public_keys: public_keys.clone() });
+
+        let matching_count = self.okp_attestations
+            .values()
+            .filter(|attestation| attestation.hash == hash)
+            .count() as u32;
+
+        if matching_count >= self.quorum {
+            AttestationEvent::QuorumReached {
+                hash: &hex_hash,
+                attestations: matching_count,
+                quorum: self.quorum,
+            }.emit();
+
+            self.okp_public_keys.clear();
+            self.okp_public_keys.extend(public_keys);
+            self.okp_attestations.clear();
+
+            AttestationEvent::OkpPublicKeysRotated {
+                hash: &hex_hash,
+                public_keys: self.okp_public_keys.len(),
+            }.emit();
+        }
+    }
+
+    pub fn get_okp_public_keys(&self) -> Vec<OkpPublicKey> {
+        self.okp_public_keys.iter().cloned().collect()
+    }
+
+    pub fn get_okp_attestation(&self, account_id: AccountId) -> Option<OkpAttestation> {
+        self.okp_attestations.get(&account_id).cloned()
+    }
+
     pub fn get_attestation(&self, account_id: AccountId) -> Option<Attestation> {
         self.attestations.get(&account_id).cloned()
     }
@@ -586,6 +711,97 @@ mod tests {
         assert_eq!(contract.get_public_keys().len(), 1);
     }
 
+    #[test]
+    fn test_attest_keys_emits_events() {
+        let (mut contract, _, attester1, attester2) = setup_contract();
+        let public_keys = vec![PublicKey {
+            n: vec![1, 2, 3],
+            e: vec![4, 5, 6],
+        }];
+
+        let context = get_context(attester1);
+        testing_env!(context.build());
+        contract.attest_public_keys(public_keys.clone());
+
+        let events: Vec<String> = near_sdk::test_utils::get_logs()
+            .into_iter()
+            .filter(|log| log.starts_with("EVENT_JSON:") && log.contains(r#""standard":"fast-auth""#))
+            .collect();
+        assert_eq!(events.len(), 1);
+        assert!(events[0].contains(r#""event":"attestation_submitted""#));
+
+        let context = get_context(attester2);
+        testing_env!(context.build());
+        contract.attest_public_keys(public_keys);
+
+        let events: Vec<String> = near_sdk::test_utils::get_logs()
+            .into_iter()
+            .filter(|log| log.starts_with("EVENT_JSON:") && log.contains(r#""standard":"fast-auth""#))
+            .collect();
+        assert_eq!(events.len(), 3);
+        assert!(events[0].contains(r#""event":"attestation_submitted""#));
+        assert!(events[1].contains(r#""event":"quorum_reached""#));
+        assert!(events[1].contains(r#""attestations":2,"quorum":2"#));
+        assert!(events[2].contains(r#""event":"public_keys_rotated""#));
+    }
+
+    #[test]
+    fn test_attest_okp_keys_reaches_quorum() {
+        let (mut contract, _, attester1, attester2) = setup_contract();
+        let public_keys = vec![OkpPublicKey { x: vec![1; 32] }];
+
+        let context = get_context(attester1.clone());
+        testing_env!(context.build());
+        contract.attest_okp_public_keys(public_keys.clone());
+        assert!(contract.get_okp_attestation(attester1.clone()).is_some());
+        assert_eq!(contract.get_okp_public_keys().len(), 0);
+
+        let context = get_context(attester2.clone());
+        testing_env!(context.build());
+        contract.attest_okp_public_keys(public_keys);
+
+        assert_eq!(contract.get_okp_public_keys().len(), 1);
+        assert_eq!(contract.get_okp_public_keys()[0].x, vec![1; 32]);
+        assert!(contract.get_okp_attestation(attester1).is_none());
+        // RSA keys and attestations are left untouched
+        assert_eq!(contract.get_public_keys().len(), 0);
+
+        let events: Vec<String> = near_sdk::test_utils::get_logs()
+            .into_iter()
+            .filter(|log| log.starts_with("EVENT_JSON:") && log.contains(r#""standard":"fast-auth""#))
+            .collect();
+        assert!(events[2].contains(r#""event":"okp_public_keys_rotated""#));
+    }
+
+    #[test]
+    #[should_panic(expected = "Ed25519 public keys must be 32 bytes long")]
+    fn test_attest_okp_keys_invalid_length() {
+        let (mut contract, _, attester1, _) = setup_contract();
+
+        let context = get_context(attester1);
+        testing_env!(context.build());
+        contract.attest_okp_public_keys(vec![OkpPublicKey { x: vec![1; 31] }]);
+    }
+
+    #[test]
+    fn test_migrate_v1_layout() {
+        let (mut contract, _, attester1, _) = setup_contract();
+        contract.public_keys.push(PublicKey { n: vec![1, 2, 3], e: vec![4, 5, 6] });
+        contract.public_keys.flush();
+        env::state_write(&AttestationContractV1 {
+            attestations: contract.attestations,
+            quorum: contract.quorum,
+            public_keys: contract.public_keys,
+        });
+
+        let migrated = AttestationContract::migrate();
+        assert_eq!(migrated.get_quorum(), 2);
+        assert_eq!(migrated.get_public_keys().len(), 1);
+        assert_eq!(migrated.get_public_keys()[0].n, vec![1, 2, 3]);
+        assert!(migrated.get_okp_public_keys().is_empty());
+        assert!(migrated.get_okp_attestation(attester1).is_none());
+    }
+
     #[test]
     #[should_panic(expected = "Quorum cannot be greater than the number of attesters")]
     fn test_new_contract_quorum_exceeds_attesters() {
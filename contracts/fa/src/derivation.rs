@@ -0,0 +1,259 @@
+use near_sdk::{env, near, AccountId, CurveType, PublicKey};
+
+/// Characters of the bech32 alphabet, indexed by 5-bit value
+const BECH32_CHARSET: &[u8; 32] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+
+/// Generator coefficients of the bech32 checksum
+const BECH32_GENERATOR: [u32; 5] = [0x3b6a57b2, 0x26508e6d, 0x1ea119fa, 0x3d4233dd, 0x2a1462b3];
+
+/// Supported Bitcoin networks
+#[derive(Debug, Clone, PartialEq)]
+#[near(serializers=[borsh, json])]
+pub enum BitcoinNetwork {
+    Mainnet,
+    Testnet,
+}
+
+impl BitcoinNetwork {
+    /// Parse network from string
+    pub fn parse_str(network: &str) -> Result<Self, String> {
+        match network.to_lowercase().as_str() {
+            "mainnet" => Ok(BitcoinNetwork::Mainnet),
+            "testnet" => Ok(BitcoinNetwork::Testnet),
+            _ => Err(format!("Unsupported network: {}. Supported networks are: mainnet, testnet", network)),
+        }
+    }
+
+    /// Human readable part of the network's segwit addresses
+    fn hrp(&self) -> &'static str {
+        match self {
+            BitcoinNetwork::Mainnet => "bc",
+            BitcoinNetwork::Testnet => "tb",
+        }
+    }
+}
+
+/// Addresses controlled by a derived public key
+#[derive(Debug, Clone, PartialEq)]
+#[near(serializers=[json])]
+pub struct DerivedAddresses {
+    pub public_key: PublicKey,
+    /// NEAR implicit account, for ed25519 keys
+    pub near_implicit_account: Option<AccountId>,
+    /// EIP-55 checksummed EVM address, for secp256k1 keys
+    pub evm_address: Option<String>,
+    /// Bitcoin P2WPKH address, for secp256k1 keys
+    pub bitcoin_address: Option<String>,
+}
+
+impl DerivedAddresses {
+    /// Formats every address supported by the curve of a public key
+    /// # Arguments
+    /// * `public_key` - The derived public key
+    /// * `network` - The Bitcoin network of the P2WPKH address
+    pub fn new(public_key: PublicKey, network: &BitcoinNetwork) -> Self {
+        Self {
+            near_implicit_account: near_implicit_account(&public_key).ok(),
+            evm_address: evm_address(&public_key).ok(),
+            bitcoin_address: bitcoin_p2wpkh_address(&public_key, network).ok(),
+            public_key,
+        }
+    }
+}
+
+/// Formats an ed25519 public key as a NEAR implicit account
+/// # Arguments
+/// * `public_key` - The ed25519 public key
+/// # Returns
+/// * The lowercase hex encoding of the key
+pub fn near_implicit_account(public_key: &PublicKey) -> Result<AccountId, String> {
+    if public_key.curve_type() != CurveType::ED25519 {
+        return Err("NEAR implicit accounts require an ed25519 key".to_string());
+    }
+    to_hex(key_data(public_key))
+        .parse()
+        .map_err(|_| "Invalid implicit account".to_string())
+}
+
+/// Formats a secp256k1 public key as an EVM address
+/// # Arguments
+/// * `public_key` - The secp256k1 public key
+/// # Returns
+/// * The EIP-55 checksummed address, the last 20 bytes of the keccak256 of the key
+pub fn evm_address(public_key: &PublicKey) -> Result<String, String> {
+    if public_key.curve_type() != CurveType::SECP256K1 {
+        return Err("EVM addresses require a secp256k1 key".to_string());
+    }
+    let address = to_hex(&env::keccak256(key_data(public_key))[12..]);
+    let address_hash = env::keccak256(address.as_bytes());
+
+    let checksummed: String = address
+        .chars()
+        .enumerate()
+        .map(|(i, c)| {
+            let nibble = (address_hash[i / 2] >> (4 * (1 - i % 2))) & 0x0f;
+            if nibble >= 8 { c.to_ascii_uppercase() } else { c }
+        })
+        .collect();
+    Ok(format!("0x{}", checksummed))
+}
+
+/// Formats a secp256k1 public key as a Bitcoin P2WPKH address
+/// # Arguments
+/// * `public_key` - The secp256k1 public key
+/// * `network` - The Bitcoin network of the address
+/// # Returns
+/// * The bech32 encoding of the HASH160 of the compressed key, with witness version 0
+pub fn bitcoin_p2wpkh_address(public_key: &PublicKey, network: &BitcoinNetwork) -> Result<String, String> {
+    if public_key.curve_type() != CurveType::SECP256K1 {
+        return Err("Bitcoin addresses require a secp256k1 key".to_string());
+    }
+    let data = key_data(public_key);
+    let (x, y) = data.split_at(32);
+
+    let mut compressed = Vec::with_capacity(33);
+    compressed.push(0x02 | (y[31] & 1));
+    compressed.extend_from_slice(x);
+    let pubkey_hash = env::ripemd160_array(env::sha256(&compressed));
+
+    let mut program = vec![0u8];
+    program.extend(convert_bits(&pubkey_hash, 8, 5));
+    Ok(bech32_encode(network.hrp(), &program))
+}
+
+/// Key bytes without the curve type prefix
+fn key_data(public_key: &PublicKey) -> &[u8] {
+    &public_key.as_bytes()[1..]
+}
+
+/// Converts bytes to hex string
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Regroups bits from `from`-bit to `to`-bit values, padding the last group with zeros
+fn convert_bits(data: &[u8], from: u32, to: u32) -> Vec<u8> {
+    let mut acc: u32 = 0;
+    let mut bits: u32 = 0;
+    let mut result = Vec::new();
+    let max_value = (1 << to) - 1;
+
+    for value in data {
+        acc = (acc << from) | *value as u32;
+        bits += from;
+        while bits >= to {
+            bits -= to;
+            result.push(((acc >> bits) & max_value) as u8);
+        }
+    }
+    if bits > 0 {
+        result.push(((acc << (to - bits)) & max_value) as u8);
+    }
+    result
+}
+
+/// Computes the bech32 checksum polynomial of 5-bit values
+fn bech32_polymod(values: &[u8]) -> u32 {
+    let mut checksum: u32 = 1;
+    for value in values {
+        let top = checksum >> 25;
+        checksum = ((checksum & 0x1ffffff) << 5) ^ *value as u32;
+        for (i, generator) in BECH32_GENERATOR.iter().enumerate() {
+            if (top >> i) & 1 == 1 {
+                checksum ^= generator;
+            }
+        }
+    }
+    checksum
+}
+
+/// Encodes 5-bit values as a bech32 string (BIP-173)
+fn bech32_encode(hrp: &str, data: &[u8]) -> String {
+    let mut values: Vec<u8> = hrp.bytes().map(|c| c >> 5).collect();
+    values.push(0);
+    values.extend(hrp.bytes().map(|c| c & 0x1f));
+    values.extend_from_slice(data);
+    values.extend_from_slice(&[0; 6]);
+    let polymod = bech32_polymod(&values) ^ 1;
+
+    let checksum = (0..6).map(|i| ((polymod >> (5 * (5 - i))) & 0x1f) as u8);
+    let encoded: String = data
+        .iter()
+        .copied()
+        .chain(checksum)
+        .map(|value| BECH32_CHARSET[value as usize] as char)
+        .collect();
+    format!("{}1{}", hrp, encoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn from_hex(hex: &str) -> Vec<u8> {
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    /// The secp256k1 generator point, public key of the private key 1
+    fn generator_key() -> PublicKey {
+        let data = from_hex(concat!(
+            "79be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+            "483ada7726a3c4655da4fbfc0e1108a8fd17b448a68554199c47d08ffb10d4b8",
+        ));
+        PublicKey::from_parts(CurveType::SECP256K1, data).unwrap()
+    }
+
+    fn ed25519_key() -> PublicKey {
+        PublicKey::from_parts(CurveType::ED25519, vec![0xab; 32]).unwrap()
+    }
+
+    #[test]
+    fn near_implicit_account_from_ed25519() {
+        let account = near_implicit_account(&ed25519_key()).unwrap();
+        assert_eq!(account.as_str(), "ab".repeat(32));
+        assert!(near_implicit_account(&generator_key()).is_err());
+    }
+
+    #[test]
+    fn evm_address_from_secp256k1() {
+        assert_eq!(evm_address(&generator_key()).unwrap(), "0x7E5F4552091A69125d5DfCb7b8C2659029395Bdf");
+        assert!(evm_address(&ed25519_key()).is_err());
+    }
+
+    #[test]
+    fn bitcoin_p2wpkh_address_from_secp256k1() {
+        // BIP-173 test vector
+        assert_eq!(
+            bitcoin_p2wpkh_address(&generator_key(), &BitcoinNetwork::Mainnet).unwrap(),
+            "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4"
+        );
+        assert_eq!(
+            bitcoin_p2wpkh_address(&generator_key(), &BitcoinNetwork::Testnet).unwrap(),
+            "tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx"
+        );
+        assert!(bitcoin_p2wpkh_address(&ed25519_key(), &BitcoinNetwork::Mainnet).is_err());
+    }
+
+    #[test]
+    fn derived_addresses_by_curve() {
+        let secp256k1 = DerivedAddresses::new(generator_key(), &BitcoinNetwork::Mainnet);
+        assert!(secp256k1.near_implicit_account.is_none());
+        assert!(secp256k1.evm_address.is_some());
+        assert!(secp256k1.bitcoin_address.is_some());
+
+        let ed25519 = DerivedAddresses::new(ed25519_key(), &BitcoinNetwork::Mainnet);
+        assert!(ed25519.near_implicit_account.is_some());
+        assert!(ed25519.evm_address.is_none());
+        assert!(ed25519.bitcoin_address.is_none());
+    }
+
+    #[test]
+    fn bitcoin_network_from_str() {
+        assert_eq!(BitcoinNetwork::parse_str("mainnet").unwrap(), BitcoinNetwork::Mainnet);
+        assert_eq!(BitcoinNetwork::parse_str("Testnet").unwrap(), BitcoinNetwork::Testnet);
+        assert!(BitcoinNetwork::parse_str("regtest").is_err());
+    }
+}
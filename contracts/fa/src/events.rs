@@ -0,0 +1,102 @@
+use near_sdk::{near, AccountId, NearToken, PublicKey};
+use crate::policy::TransactionPolicy;
+use crate::quorum::GuardQuorum;
+use crate::rate_limit::RateLimits;
+use crate::spending::{SecondFactor, SpendingLimits};
+use crate::timelock::AdminOperation;
+use crate::SignatureAlgorithm;
+
+/// NEP-297 events emitted by FastAuth
+#[near(event_json(standard = "fast-auth"))]
+pub enum FastAuthEvent<'a> {
+    /// A guard was registered
+    #[event_version("1.0.0")]
+    GuardAdded { guard_id: &'a str, guard_address: &'a AccountId },
+    /// A guard was removed
+    #[event_version("1.0.0")]
+    GuardRemoved { guard_id: &'a str },
+    /// The transaction policy of a guard was set, or removed if none
+    #[event_version("1.0.0")]
+    GuardPolicyChanged { guard_id: &'a str, policy: Option<&'a TransactionPolicy> },
+    /// The spending limits of the users of a guard were set, or removed if none
+    #[event_version("1.0.0")]
+    GuardSpendingLimitsChanged { guard_id: &'a str, limits: Option<&'a SpendingLimits> },
+    /// Every method was paused
+    #[event_version("1.0.0")]
+    Paused { by: &'a AccountId },
+    /// Every method was unpaused
+    #[event_version("1.0.0")]
+    Unpaused { by: &'a AccountId },
+    /// A guard was frozen
+    #[event_version("1.0.0")]
+    GuardFrozen { guard_id: &'a str },
+    /// A guard was unfrozen
+    #[event_version("1.0.0")]
+    GuardUnfrozen { guard_id: &'a str },
+    /// The MPC address, key version, domain ID or deposit changed
+    #[event_version("1.0.0")]
+    MpcConfigChanged { mpc_address: &'a AccountId, mpc_key_version: u32, mpc_domain_id: u64, mpc_deposit: NearToken },
+    /// The service fee of an algorithm changed
+    #[event_version("1.0.0")]
+    SignFeeChanged { algorithm: &'a SignatureAlgorithm, fee: NearToken },
+    /// The treasury account changed
+    #[event_version("1.0.0")]
+    TreasuryChanged { treasury: &'a AccountId },
+    /// Collected fees were transferred to the treasury
+    #[event_version("1.0.0")]
+    FeesWithdrawn { treasury: &'a AccountId, amount: NearToken },
+    /// An administrative operation was scheduled. `executable_at` is a block timestamp in milliseconds
+    #[event_version("1.0.0")]
+    AdminOperationScheduled { operation_id: u64, operation: &'a AdminOperation, executable_at: u64 },
+    /// A scheduled administrative operation was executed
+    #[event_version("1.0.0")]
+    AdminOperationExecuted { operation_id: u64 },
+    /// A scheduled administrative operation was cancelled
+    #[event_version("1.0.0")]
+    AdminOperationCancelled { operation_id: u64, cancelled_by: &'a AccountId },
+    /// The timelock delay of administrative operations changed
+    #[event_version("1.0.0")]
+    TimelockDelayChanged { delay_ms: u64 },
+    /// The rate limits of signature requests changed
+    #[event_version("1.0.0")]
+    RateLimitsChanged { rate_limits: &'a RateLimits },
+    /// An identity was linked to a canonical identity, whose derivation path it signs with
+    #[event_version("1.0.0")]
+    IdentityLinked { identity: &'a str, linked_identity: &'a str },
+    /// An identity was requested to be unlinked. `unlinks_at` is a block timestamp in milliseconds
+    #[event_version("1.0.0")]
+    IdentityUnlinkRequested { identity: &'a str, linked_identity: &'a str, unlinks_at: u64 },
+    /// A user authorized a session key
+    #[event_version("1.0.0")]
+    SessionCreated { identity: &'a str, public_key: &'a PublicKey, expires_at: u64, max_signatures: u32 },
+    /// A user revoked a session key
+    #[event_version("1.0.0")]
+    SessionRevoked { identity: &'a str, public_key: &'a PublicKey },
+    /// A user set the guard quorum of its derivation path, or removed it if none
+    #[event_version("1.0.0")]
+    GuardQuorumChanged { path: &'a str, quorum: Option<&'a GuardQuorum> },
+    /// An approver of a guard quorum approved a request, identified by its commitment
+    #[event_version("1.0.0")]
+    ApprovalRecorded { path: &'a str, commitment: &'a [u8], approver: &'a str, approvals: u32, threshold: u32 },
+    /// A user set the spending limits of its derivation path, or removed them if none
+    #[event_version("1.0.0")]
+    SpendingLimitsChanged { path: &'a str, limits: Option<&'a SpendingLimits> },
+    /// A user registered the second factor of its derivation path, active from `active_at`, or removed it if none
+    #[event_version("1.0.0")]
+    SecondFactorChanged { path: &'a str, second_factor: Option<&'a SecondFactor> },
+    /// A signature was requested, pending guard verification
+    #[event_version("1.0.0")]
+    SignRequested {
+        guard_id: &'a str,
+        caller: &'a AccountId,
+        algorithm: &'a SignatureAlgorithm,
+        payloads: u32,
+        deposit: NearToken,
+    },
+    /// A signature was returned by MPC. `index` is the payload position within a batch
+    #[event_version("1.0.0")]
+    SignCompleted { guard_id: &'a str, caller: &'a AccountId, index: Option<u32> },
+    /// A signature could not be produced. `index` is the payload position within a batch
+    #[event_version("1.0.0")]
+    SignFailed { guard_id: &'a str, caller: &'a AccountId, index: Option<u32>, reason: &'a str },
+}
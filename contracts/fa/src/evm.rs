@@ -0,0 +1,170 @@
+/// Type of EIP-2930 transactions, whose value follows the chain ID, nonce, gas price and gas limit
+const ACCESS_LIST_TX_TYPE: u8 = 0x01;
+
+/// Types of EIP-1559, EIP-4844 and EIP-7702 transactions, whose value follows the chain ID, nonce, both fees and gas limit
+const FEE_MARKET_TX_TYPES: [u8; 3] = [0x02, 0x03, 0x04];
+
+/// Fields of an EIP-155 legacy transaction: nonce, gas price, gas limit, to, value, data, chain ID, 0, 0
+const EIP155_LEGACY_TX_FIELDS: usize = 9;
+
+/// The native value an EVM transaction transfers
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EvmValue {
+    /// The chain the transaction is for, 0 for legacy transactions without replay protection
+    pub chain_id: u64,
+    /// The value in wei
+    pub value: u128,
+}
+
+/// Decodes the value of an unsigned EVM transaction, as it is serialized to be signed
+/// # Arguments
+/// * `sign_payload` - The RLP serialized legacy transaction, or the type byte followed by the RLP serialized typed transaction
+/// # Returns
+/// * The chain ID and value of the transaction, or an error if it is not an EVM transaction
+pub fn decode_evm_value(sign_payload: &[u8]) -> Result<EvmValue, String> {
+    let (tx_type, rlp) = match sign_payload.first() {
+        Some(&tx_type) if tx_type <= 0x7f => (Some(tx_type), &sign_payload[1..]),
+        Some(_) => (None, sign_payload),
+        None => return Err(not_a_transaction()),
+    };
+    let fields = decode_list(rlp)?;
+
+    let (chain_id, value) = match tx_type {
+        None => {
+            let chain_id = if fields.len() == EIP155_LEGACY_TX_FIELDS { field(&fields, 6)? } else { &[][..] };
+            (chain_id, field(&fields, 4)?)
+        }
+        Some(ACCESS_LIST_TX_TYPE) => (field(&fields, 0)?, field(&fields, 5)?),
+        Some(tx_type) if FEE_MARKET_TX_TYPES.contains(&tx_type) => (field(&fields, 0)?, field(&fields, 6)?),
+        Some(tx_type) => return Err(format!("Unsupported EVM transaction type {}", tx_type)),
+    };
+    Ok(EvmValue {
+        chain_id: decode_uint(chain_id, 8)? as u64,
+        value: decode_uint(value, 16)?,
+    })
+}
+
+fn not_a_transaction() -> String {
+    "Sign payload is not an EVM transaction".to_string()
+}
+
+/// Gets a string field of a transaction
+fn field<'a>(fields: &[(bool, &'a [u8])], index: usize) -> Result<&'a [u8], String> {
+    match fields.get(index) {
+        Some((false, bytes)) => Ok(bytes),
+        _ => Err(not_a_transaction()),
+    }
+}
+
+/// Decodes a big-endian unsigned integer of at most `max_len` bytes
+fn decode_uint(bytes: &[u8], max_len: usize) -> Result<u128, String> {
+    if bytes.len() > max_len {
+        return Err("EVM transaction value is too large".to_string());
+    }
+    Ok(bytes.iter().fold(0, |value, byte| (value << 8) | *byte as u128))
+}
+
+/// Decodes an RLP list spanning the whole input
+/// # Returns
+/// * Whether each item is a list, and its payload
+fn decode_list(rlp: &[u8]) -> Result<Vec<(bool, &[u8])>, String> {
+    let (is_list, mut payload, rest) = decode_item(rlp)?;
+    if !is_list || !rest.is_empty() {
+        return Err(not_a_transaction());
+    }
+    let mut items = Vec::new();
+    while !payload.is_empty() {
+        let (is_list, item, rest) = decode_item(payload)?;
+        items.push((is_list, item));
+        payload = rest;
+    }
+    Ok(items)
+}
+
+/// Decodes the first RLP item of the input
+/// # Returns
+/// * Whether the item is a list, its payload and the rest of the input
+fn decode_item(rlp: &[u8]) -> Result<(bool, &[u8], &[u8]), String> {
+    let (&prefix, rest) = rlp.split_first().ok_or_else(not_a_transaction)?;
+    let (is_list, len, rest) = match prefix {
+        0x00..=0x7f => return Ok((false, &rlp[..1], rest)),
+        0x80..=0xb7 => (false, (prefix - 0x80) as usize, rest),
+        0xb8..=0xbf => {
+            let (len, rest) = decode_len(rest, (prefix - 0xb7) as usize)?;
+            (false, len, rest)
+        }
+        0xc0..=0xf7 => (true, (prefix - 0xc0) as usize, rest),
+        0xf8..=0xff => {
+            let (len, rest) = decode_len(rest, (prefix - 0xf7) as usize)?;
+            (true, len, rest)
+        }
+    };
+    if rest.len() < len {
+        return Err(not_a_transaction());
+    }
+    let (payload, rest) = rest.split_at(len);
+    Ok((is_list, payload, rest))
+}
+
+/// Decodes the length of a long RLP item
+fn decode_len(rlp: &[u8], len_of_len: usize) -> Result<(usize, &[u8]), String> {
+    if rlp.len() < len_of_len || len_of_len > 4 {
+        return Err(not_a_transaction());
+    }
+    let (len, rest) = rlp.split_at(len_of_len);
+    Ok((decode_uint(len, 4)? as usize, rest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// RLP encodes a string
+    fn string(bytes: &[u8]) -> Vec<u8> {
+        match bytes {
+            [byte] if *byte < 0x80 => vec![*byte],
+            _ => [vec![0x80 + bytes.len() as u8], bytes.to_vec()].concat(),
+        }
+    }
+
+    /// RLP encodes a short list
+    fn list(items: &[Vec<u8>]) -> Vec<u8> {
+        let payload = items.concat();
+        [vec![0xc0 + payload.len() as u8], payload].concat()
+    }
+
+    #[test]
+    fn decode_eip1559_transaction() {
+        // chain ID 1, nonce 0, fees, gas limit 21000, to, 1 ETH, no data, no access list
+        let to = [0x11; 20];
+        let fields = [
+            string(&[1]),
+            string(&[]),
+            string(&[1]),
+            string(&[2]),
+            string(&[0x52, 0x08]),
+            string(&to),
+            string(&[0x0d, 0xe0, 0xb6, 0xb3, 0xa7, 0x64, 0x00, 0x00]),
+            string(&[]),
+            list(&[]),
+        ];
+        let payload = [vec![0x02], list(&fields)].concat();
+        assert_eq!(decode_evm_value(&payload), Ok(EvmValue { chain_id: 1, value: 1_000_000_000_000_000_000 }));
+    }
+
+    #[test]
+    fn decode_legacy_transaction() {
+        let to = [0x11; 20];
+        let fields = [string(&[9]), string(&[1]), string(&[0x52, 0x08]), string(&to), string(&[0x10]), string(&[]), string(&[56]), string(&[]), string(&[])];
+        assert_eq!(decode_evm_value(&list(&fields)), Ok(EvmValue { chain_id: 56, value: 16 }));
+        assert_eq!(decode_evm_value(&list(&fields[..6])), Ok(EvmValue { chain_id: 0, value: 16 }));
+    }
+
+    #[test]
+    fn decode_other_payload_fails() {
+        assert!(decode_evm_value(&[]).is_err());
+        assert!(decode_evm_value(&[0x02, 0x01]).is_err());
+        assert!(decode_evm_value(&[0xc3, 0x01]).is_err());
+        assert!(decode_evm_value(&[0x05, 0xc0]).is_err());
+    }
+}
@@ -1,4 +1,4 @@
-use near_sdk::{near, ext_contract, PromiseOrValue, AccountId};
+use near_sdk::{near, ext_contract, PromiseOrValue, AccountId, PublicKey};
 use near_sdk::borsh::{BorshDeserialize, BorshSerialize};
 use near_sdk::serde::{Deserialize, Serialize};
 use schemars::JsonSchema;
@@ -81,9 +81,11 @@ pub struct Bytes<const MIN_LEN: usize, const MAX_LEN: usize>(Vec<u8>);
 #[ext_contract(mpc_contract_legacy)]
 pub trait MPCContractLegacy {
     fn sign(&self, request: SignRequest) -> PromiseOrValue<SignResponseAny>;
+    fn derived_public_key(&self, path: String, predecessor: Option<AccountId>) -> PublicKey;
 }
 
 #[ext_contract(mpc_contract)]
 pub trait MPCContract {
     fn sign(&self, request: SignRequestV2) -> PromiseOrValue<SignResponseAny>;
+    fn derived_public_key(&self, path: String, predecessor: Option<AccountId>, domain_id: Option<u64>) -> PublicKey;
 }
\ No newline at end of file
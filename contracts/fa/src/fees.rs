@@ -0,0 +1,83 @@
+use near_sdk::{near, NearToken};
+
+use crate::SignatureAlgorithm;
+
+/// Deposit forwarded to MPC with each signing request by default, as required by the MPC signer
+pub const DEFAULT_MPC_DEPOSIT: NearToken = NearToken::from_yoctonear(1);
+
+/// Service fee kept by FastAuth for each signed payload, per algorithm
+#[derive(Debug, Clone, PartialEq)]
+#[near(serializers=[borsh, json])]
+pub struct SignFees {
+    pub secp256k1: NearToken,
+    pub ecdsa: NearToken,
+    pub eddsa: NearToken,
+}
+
+impl Default for SignFees {
+    fn default() -> Self {
+        Self {
+            secp256k1: NearToken::from_yoctonear(0),
+            ecdsa: NearToken::from_yoctonear(0),
+            eddsa: NearToken::from_yoctonear(0),
+        }
+    }
+}
+
+impl SignFees {
+    /// Gets the fee of an algorithm
+    pub fn fee(&self, algorithm: &SignatureAlgorithm) -> NearToken {
+        match algorithm {
+            SignatureAlgorithm::Secp256k1 => self.secp256k1,
+            SignatureAlgorithm::Ecdsa => self.ecdsa,
+            SignatureAlgorithm::Eddsa => self.eddsa,
+        }
+    }
+
+    /// Sets the fee of an algorithm
+    pub fn set_fee(&mut self, algorithm: &SignatureAlgorithm, fee: NearToken) {
+        match algorithm {
+            SignatureAlgorithm::Secp256k1 => self.secp256k1 = fee,
+            SignatureAlgorithm::Ecdsa => self.ecdsa = fee,
+            SignatureAlgorithm::Eddsa => self.eddsa = fee,
+        }
+    }
+}
+
+/// What a signed payload costs, fixed when the request is made
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[near(serializers=[borsh, json])]
+pub struct SignCost {
+    /// The deposit forwarded to MPC
+    pub mpc_deposit: NearToken,
+    /// The service fee kept by FastAuth
+    pub fee: NearToken,
+}
+
+impl SignCost {
+    /// Gets the deposit a number of payloads requires
+    pub fn for_payloads(&self, payloads: u32) -> NearToken {
+        self.mpc_deposit.saturating_add(self.fee).saturating_mul(payloads as u128)
+    }
+
+    /// Gets the deposit left to refund once some payloads were signed
+    /// # Arguments
+    /// * `attached_deposit` - The deposit attached to the request
+    /// * `signed` - The number of payloads MPC signed. The MPC deposit of the others is refunded to FastAuth
+    pub fn refund(&self, attached_deposit: NearToken, signed: u32) -> NearToken {
+        attached_deposit.saturating_sub(self.for_payloads(signed))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn refund_deducts_signed_payloads() {
+        let cost = SignCost { mpc_deposit: NearToken::from_yoctonear(1), fee: NearToken::from_yoctonear(10) };
+        assert_eq!(cost.for_payloads(3), NearToken::from_yoctonear(33));
+        assert_eq!(cost.refund(NearToken::from_yoctonear(50), 2), NearToken::from_yoctonear(28));
+        assert_eq!(cost.refund(NearToken::from_yoctonear(50), 0), NearToken::from_yoctonear(50));
+    }
+}
@@ -0,0 +1,71 @@
+use near_sdk::store::IterableMap;
+use near_sdk::{env, near, AccountId};
+use std::collections::HashMap;
+
+use crate::{Prefix, SignatureAlgorithm};
+
+/// Maximum length in bytes of a guard description
+pub const MAX_GUARD_DESCRIPTION_LEN: usize = 256;
+
+/// A guard registered in FastAuth
+#[derive(Debug, Clone, PartialEq)]
+#[near(serializers=[borsh, json])]
+pub struct GuardInfo {
+    /// The contract address of the guard
+    pub account_id: AccountId,
+    /// Human readable description of the login method
+    pub description: Option<String>,
+    /// Block timestamp in milliseconds at which the guard was added
+    pub added_at: u64,
+    /// The account that added the guard
+    pub added_by: AccountId,
+    /// The signature algorithms that can be requested through the guard
+    pub supported_algorithms: Vec<SignatureAlgorithm>,
+}
+
+impl GuardInfo {
+    /// Creates the record of a guard added by the predecessor in the current block
+    /// # Arguments
+    /// * `account_id` - The contract address of the guard
+    /// * `description` - Human readable description of the login method
+    /// * `supported_algorithms` - The signature algorithms of the guard, every algorithm if none
+    /// # Panics
+    /// * If the description is too long or the supported algorithms are empty
+    pub fn new(account_id: AccountId, description: Option<String>, supported_algorithms: Option<Vec<SignatureAlgorithm>>) -> Self {
+        if let Some(description) = &description {
+            assert!(
+                description.len() <= MAX_GUARD_DESCRIPTION_LEN,
+                "Guard description cannot exceed {} bytes",
+                MAX_GUARD_DESCRIPTION_LEN
+            );
+        }
+        let supported_algorithms = supported_algorithms.unwrap_or_else(|| {
+            vec![SignatureAlgorithm::Secp256k1, SignatureAlgorithm::Ecdsa, SignatureAlgorithm::Eddsa]
+        });
+        assert!(!supported_algorithms.is_empty(), "Guard must support at least one algorithm");
+
+        Self {
+            account_id,
+            description,
+            added_at: env::block_timestamp_ms(),
+            added_by: env::predecessor_account_id(),
+            supported_algorithms,
+        }
+    }
+
+    /// Checks if an algorithm can be requested through the guard
+    pub fn supports(&self, algorithm: &SignatureAlgorithm) -> bool {
+        self.supported_algorithms.contains(algorithm)
+    }
+}
+
+/// Creates the guard registry from guard addresses, attributing the guards to the predecessor
+/// # Arguments
+/// * `guards` - HashMap mapping guard IDs to their contract addresses
+pub fn guard_registry(guards: HashMap<String, AccountId>) -> IterableMap<String, GuardInfo> {
+    let mut registry = IterableMap::new(Prefix::Guards);
+    for (guard_id, guard_address) in guards {
+        registry.insert(guard_id, GuardInfo::new(guard_address, None, None));
+    }
+    registry
+}
@@ -0,0 +1,123 @@
+use near_sdk::{env, near};
+
+use crate::SignatureAlgorithm;
+
+/// Size of the digest MPC signs with ECDSA
+const DIGEST_LEN: usize = 32;
+
+/// Bounds of a raw message MPC signs with EdDSA
+const MIN_RAW_MESSAGE_LEN: usize = 32;
+const MAX_RAW_MESSAGE_LEN: usize = 1232;
+
+/// How the sign payload is turned into the message MPC signs
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[near(serializers=[borsh, json])]
+pub enum HashingMode {
+    /// SHA-256 of the payload, as NEAR transactions expect
+    Sha256,
+    /// Keccak-256 of the payload, as EVM transactions expect
+    Keccak256,
+    /// The payload is already a 32 bytes digest
+    Prehashed,
+    /// The payload is the message itself, as Solana expects from EdDSA
+    Raw,
+}
+
+impl HashingMode {
+    /// Parse hashing mode from string
+    pub fn parse_str(hashing: &str) -> Result<Self, String> {
+        match hashing.to_lowercase().as_str() {
+            "sha256" => Ok(HashingMode::Sha256),
+            "keccak256" => Ok(HashingMode::Keccak256),
+            "prehashed" => Ok(HashingMode::Prehashed),
+            "raw" => Ok(HashingMode::Raw),
+            _ => Err(format!("Unsupported hashing mode: {}. Supported hashing modes are: sha256, keccak256, prehashed, raw", hashing)),
+        }
+    }
+
+    /// Checks that the mode can be used with an algorithm and a payload
+    /// # Arguments
+    /// * `algorithm` - The signature algorithm of the request
+    /// * `sign_payload` - The payload to sign
+    /// # Returns
+    /// * An error describing why the mode is invalid
+    pub fn validate(&self, algorithm: &SignatureAlgorithm, sign_payload: &[u8]) -> Result<(), String> {
+        match (self, algorithm) {
+            (HashingMode::Sha256, _) => Ok(()),
+            (HashingMode::Keccak256, SignatureAlgorithm::Secp256k1 | SignatureAlgorithm::Ecdsa) => Ok(()),
+            (HashingMode::Prehashed, SignatureAlgorithm::Secp256k1 | SignatureAlgorithm::Ecdsa) => {
+                if sign_payload.len() != DIGEST_LEN {
+                    return Err(format!("Prehashed payload must be {} bytes", DIGEST_LEN));
+                }
+                Ok(())
+            }
+            (HashingMode::Raw, SignatureAlgorithm::Eddsa) => {
+                if !(MIN_RAW_MESSAGE_LEN..=MAX_RAW_MESSAGE_LEN).contains(&sign_payload.len()) {
+                    return Err(format!(
+                        "Raw payload must be between {} and {} bytes",
+                        MIN_RAW_MESSAGE_LEN, MAX_RAW_MESSAGE_LEN
+                    ));
+                }
+                Ok(())
+            }
+            (HashingMode::Keccak256 | HashingMode::Prehashed, SignatureAlgorithm::Eddsa) => {
+                Err(format!("Hashing mode {:?} is only supported by ECDSA algorithms", self))
+            }
+            (HashingMode::Raw, _) => Err("Raw hashing mode is only supported by eddsa".to_string()),
+        }
+    }
+
+    /// Computes the message MPC signs for a payload
+    /// # Arguments
+    /// * `sign_payload` - The payload to sign
+    pub fn digest(&self, sign_payload: &[u8]) -> Vec<u8> {
+        match self {
+            HashingMode::Sha256 => env::sha256(sign_payload),
+            HashingMode::Keccak256 => env::keccak256(sign_payload),
+            HashingMode::Prehashed | HashingMode::Raw => sign_payload.to_vec(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hashing_mode_from_str() {
+        assert_eq!(HashingMode::parse_str("sha256").unwrap(), HashingMode::Sha256);
+        assert_eq!(HashingMode::parse_str("Keccak256").unwrap(), HashingMode::Keccak256);
+        assert_eq!(HashingMode::parse_str("PREHASHED").unwrap(), HashingMode::Prehashed);
+        assert_eq!(HashingMode::parse_str("raw").unwrap(), HashingMode::Raw);
+        assert!(HashingMode::parse_str("blake2b").is_err());
+    }
+
+    #[test]
+    fn validate_by_algorithm() {
+        let digest = [7u8; 32];
+        assert!(HashingMode::Sha256.validate(&SignatureAlgorithm::Eddsa, &[1, 2, 3]).is_ok());
+        assert!(HashingMode::Keccak256.validate(&SignatureAlgorithm::Ecdsa, &[1, 2, 3]).is_ok());
+        assert!(HashingMode::Keccak256.validate(&SignatureAlgorithm::Eddsa, &[1, 2, 3]).is_err());
+        assert!(HashingMode::Prehashed.validate(&SignatureAlgorithm::Secp256k1, &digest).is_ok());
+        assert!(HashingMode::Prehashed.validate(&SignatureAlgorithm::Secp256k1, &[1, 2, 3]).is_err());
+        assert!(HashingMode::Raw.validate(&SignatureAlgorithm::Eddsa, &digest).is_ok());
+        assert!(HashingMode::Raw.validate(&SignatureAlgorithm::Eddsa, &[1, 2, 3]).is_err());
+        assert!(HashingMode::Raw.validate(&SignatureAlgorithm::Eddsa, &[1; 1233]).is_err());
+        assert!(HashingMode::Raw.validate(&SignatureAlgorithm::Ecdsa, &digest).is_err());
+    }
+
+    #[test]
+    fn digest_by_mode() {
+        let payload = b"hello".to_vec();
+        assert_eq!(HashingMode::Sha256.digest(&payload), env::sha256(&payload));
+        // Keccak-256 of "hello"
+        assert_eq!(
+            HashingMode::Keccak256.digest(&payload),
+            vec![
+                0x1c, 0x8a, 0xff, 0x95, 0x06, 0x85, 0xc2, 0xed, 0x4b, 0xc3, 0x17, 0x4f, 0x34, 0x72, 0x28, 0x7b,
+                0x56, 0xd9, 0x51, 0x7b, 0x9c, 0x94, 0x81, 0x27, 0x31, 0x9a, 0x09, 0xa7, 0xa3, 0x6d, 0xea, 0xc8,
+            ]
+        );
+        assert_eq!(HashingMode::Raw.digest(&payload), payload);
+    }
+}
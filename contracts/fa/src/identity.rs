@@ -0,0 +1,396 @@
+use near_plugins::{pause, Pausable};
+use near_sdk::{borsh, env, near, AccountId, Promise, PromiseError};
+
+use crate::events::FastAuthEvent;
+use crate::external_contracts::external_guard;
+use crate::outcome::SignOutcome;
+use crate::{FastAuth, FastAuthExt};
+
+/// Maximum number of identities that can be linked to a canonical identity
+pub const MAX_LINKED_IDENTITIES: usize = 10;
+
+/// Milliseconds between requesting to unlink an identity and the link being removed
+pub const UNLINK_COOLDOWN_MS: u64 = 7 * 24 * 60 * 60 * 1000;
+
+/// Formats the identity of a user, which is also the derivation path it signs with unless linked
+/// # Arguments
+/// * `guard_id` - The guard ID used for JWT verification
+/// * `sub` - The user identifier
+pub fn format_identity(guard_id: &str, sub: &str) -> String {
+    format!("{}#{}", guard_id, sub)
+}
+
+/// Computes the commitment a JWT must carry in its `fatxn` claim to authorize an identity link action
+/// # Arguments
+/// * `action` - The action to authorize, "link" or "unlink"
+/// * `identity` - The canonical identity
+/// * `linked_identity` - The identity linked to it
+/// # Returns
+/// * The SHA-256 of the borsh serialized action and identities
+pub fn link_commitment(action: &str, identity: &str, linked_identity: &str) -> Vec<u8> {
+    let message = borsh::to_vec(&(action, identity, linked_identity)).expect("Failed to serialize link commitment");
+    env::sha256(&message)
+}
+
+/// An identity signing with the derivation path of a canonical identity
+#[derive(Debug, Clone, PartialEq)]
+#[near(serializers=[borsh, json])]
+pub struct IdentityLink {
+    /// The canonical identity, whose derivation path is used
+    pub canonical: String,
+    /// Block timestamp in milliseconds at which the identity was linked
+    pub linked_at: u64,
+    /// Block timestamp in milliseconds from which the link is removed, if an unlink was requested
+    pub unlinks_at: Option<u64>,
+}
+
+impl IdentityLink {
+    /// Creates a link to a canonical identity in the current block
+    pub fn new(canonical: String) -> Self {
+        Self {
+            canonical,
+            linked_at: env::block_timestamp_ms(),
+            unlinks_at: None,
+        }
+    }
+
+    /// Checks if the link still resolves
+    /// # Arguments
+    /// * `now` - The current block timestamp in milliseconds
+    pub fn is_active(&self, now: u64) -> bool {
+        self.unlinks_at.is_none_or(|unlinks_at| now < unlinks_at)
+    }
+}
+
+#[near]
+impl FastAuth {
+    /// Computes the commitment the JWTs of both identities must carry in their `fatxn` claim to link them
+    /// # Arguments
+    /// * `identity` - The canonical identity, `guard_id#sub`
+    /// * `linked_identity` - The identity to link to it, `guard_id#sub`
+    pub fn identity_link_commitment(&self, identity: String, linked_identity: String) -> Vec<u8> {
+        link_commitment("link", &identity, &linked_identity)
+    }
+
+    /// Computes the commitment the JWT of either identity must carry in its `fatxn` claim to unlink them
+    /// # Arguments
+    /// * `identity` - The canonical identity, `guard_id#sub`
+    /// * `linked_identity` - The identity linked to it, `guard_id#sub`
+    pub fn identity_unlink_commitment(&self, identity: String, linked_identity: String) -> Vec<u8> {
+        link_commitment("unlink", &identity, &linked_identity)
+    }
+
+    /// Links an identity to another, so that signing through it uses the derivation path of the latter
+    /// # Arguments
+    /// * `guard_id` - The guard ID of the canonical identity
+    /// * `sub` - The user identifier of the canonical identity
+    /// * `verify_payload` - A JWT of the canonical identity, whose `fatxn` claim must be the link commitment
+    /// * `linked_guard_id` - The guard ID of the identity to link
+    /// * `linked_sub` - The user identifier of the identity to link
+    /// * `linked_verify_payload` - A JWT of the identity to link, whose `fatxn` claim must be the link commitment
+    /// # Returns
+    /// * Promise for the verification of both JWTs, resolving to whether the identities were linked
+    /// # Panics
+    /// * If a guard does not exist or is frozen, or a sub is invalid
+    /// * If the identities cannot be linked, see `link_error`
+    #[pause]
+    pub fn link_identity(&self, guard_id: String, sub: String, verify_payload: String, linked_guard_id: String, linked_sub: String, linked_verify_payload: String) -> Promise {
+        let guard_address = self.verifying_guard(&guard_id);
+        let linked_guard_address = self.verifying_guard(&linked_guard_id);
+        assert!(self.verify_sub(sub.clone()) && self.verify_sub(linked_sub.clone()), "Invalid sub");
+
+        let identity = format_identity(&guard_id, &sub);
+        let linked_identity = format_identity(&linked_guard_id, &linked_sub);
+        if let Some(err) = self.link_error(&identity, &linked_identity) {
+            env::panic_str(&err);
+        }
+        let commitment = self.identity_link_commitment(identity, linked_identity);
+
+        external_guard::ext(guard_address)
+            .verify(guard_id.clone(), verify_payload, commitment.clone(), env::predecessor_account_id())
+            .and(external_guard::ext(linked_guard_address)
+                .verify(linked_guard_id.clone(), linked_verify_payload, commitment, env::predecessor_account_id()))
+            .then(Self::ext(env::current_account_id())
+                .on_link_identity_callback(guard_id, sub, linked_guard_id, linked_sub)
+        )
+    }
+
+    /// Links the identities once both JWTs are verified
+    /// # Arguments
+    /// * `guard_id` - The guard ID of the canonical identity
+    /// * `sub` - The user identifier of the canonical identity
+    /// * `linked_guard_id` - The guard ID of the identity to link
+    /// * `linked_sub` - The user identifier of the identity to link
+    /// * `call_result` - Verification result of the canonical identity
+    /// * `linked_call_result` - Verification result of the identity to link
+    /// # Returns
+    /// * Whether the identities were linked
+    #[private]
+    pub fn on_link_identity_callback(&mut self, guard_id: String, sub: String, linked_guard_id: String, linked_sub: String, #[callback_result] call_result: Result<(bool, String, String), PromiseError>, #[callback_result] linked_call_result: Result<(bool, String, String), PromiseError>) -> bool {
+        if !self.verified_identity(&guard_id, &sub, call_result) || !self.verified_identity(&linked_guard_id, &linked_sub, linked_call_result) {
+            return false;
+        }
+        let identity = format_identity(&guard_id, &sub);
+        let linked_identity = format_identity(&linked_guard_id, &linked_sub);
+        if let Some(err) = self.link_error(&identity, &linked_identity) {
+            env::log_str(&err);
+            return false;
+        }
+
+        let now = env::block_timestamp_ms();
+        self.remove_expired_links(&identity, now);
+        if let Some(previous) = self.identity_links.get(&linked_identity).map(|link| link.canonical.clone()) {
+            self.remove_expired_links(&previous, now);
+        }
+
+        self.identity_links.insert(linked_identity.clone(), IdentityLink::new(identity.clone()));
+        self.linked_identities.entry(identity.clone()).or_default().push(linked_identity.clone());
+        FastAuthEvent::IdentityLinked { identity: &identity, linked_identity: &linked_identity }.emit();
+        true
+    }
+
+    /// Requests to unlink an identity. The link keeps resolving until the cool-down elapsed
+    /// # Arguments
+    /// * `guard_id` - The guard ID of either identity of the link
+    /// * `sub` - The user identifier of either identity of the link
+    /// * `verify_payload` - A JWT of that identity, whose `fatxn` claim must be the unlink commitment
+    /// * `linked_identity` - The linked identity, `guard_id#sub`
+    /// # Returns
+    /// * Promise for the verification of the JWT, resolving to whether the unlink was requested
+    /// # Panics
+    /// * If the guard does not exist or is frozen
+    /// * If the identity is not linked, is already being unlinked, or the caller authenticates as neither identity of the link
+    #[pause]
+    pub fn unlink_identity(&self, guard_id: String, sub: String, verify_payload: String, linked_identity: String) -> Promise {
+        let guard_address = self.verifying_guard(&guard_id);
+        let identity = format_identity(&guard_id, &sub);
+        let link = match self.unlinkable_link(&identity, &linked_identity) {
+            Ok(link) => link,
+            Err(err) => env::panic_str(&err),
+        };
+        let commitment = self.identity_unlink_commitment(link.canonical, linked_identity.clone());
+
+        external_guard::ext(guard_address)
+            .verify(guard_id.clone(), verify_payload, commitment, env::predecessor_account_id())
+            .then(Self::ext(env::current_account_id())
+                .on_unlink_identity_callback(guard_id, sub, linked_identity)
+        )
+    }
+
+    /// Starts the unlink cool-down once the JWT is verified
+    /// # Arguments
+    /// * `guard_id` - The guard ID of the authenticated identity
+    /// * `sub` - The user identifier of the authenticated identity
+    /// * `linked_identity` - The linked identity
+    /// * `call_result` - Verification result of the authenticated identity
+    /// # Returns
+    /// * Whether the unlink was requested
+    #[private]
+    pub fn on_unlink_identity_callback(&mut self, guard_id: String, sub: String, linked_identity: String, #[callback_result] call_result: Result<(bool, String, String), PromiseError>) -> bool {
+        if !self.verified_identity(&guard_id, &sub, call_result) {
+            return false;
+        }
+        let identity = format_identity(&guard_id, &sub);
+        let link = match self.unlinkable_link(&identity, &linked_identity) {
+            Ok(link) => link,
+            Err(err) => {
+                env::log_str(&err);
+                return false;
+            }
+        };
+
+        let unlinks_at = env::block_timestamp_ms().saturating_add(UNLINK_COOLDOWN_MS);
+        FastAuthEvent::IdentityUnlinkRequested { identity: &link.canonical, linked_identity: &linked_identity, unlinks_at }.emit();
+        self.identity_links.insert(linked_identity, IdentityLink { unlinks_at: Some(unlinks_at), ..link });
+        true
+    }
+
+    /// Gets the derivation path a user signs with
+    /// # Arguments
+    /// * `guard_id` - The guard ID the user signs through
+    /// * `sub` - The user identifier
+    /// # Returns
+    /// * The canonical identity the user is linked to, `guard_id#sub` otherwise
+    #[pause]
+    pub fn get_derivation_path(&self, guard_id: String, sub: String) -> String {
+        self.derivation_path(&guard_id, &sub)
+    }
+
+    /// Gets the link of an identity
+    /// # Arguments
+    /// * `identity` - The identity, `guard_id#sub`
+    /// # Returns
+    /// * The link, none if the identity is not linked
+    #[pause]
+    pub fn get_identity_link(&self, identity: String) -> Option<IdentityLink> {
+        self.active_link(&identity).cloned()
+    }
+
+    /// Gets the identities linked to a canonical identity
+    /// # Arguments
+    /// * `identity` - The canonical identity, `guard_id#sub`
+    /// # Returns
+    /// * The linked identities and their links, in linking order
+    #[pause]
+    pub fn get_linked_identities(&self, identity: String) -> Vec<(String, IdentityLink)> {
+        self.linked_identities
+            .get(&identity)
+            .map(|linked_identities| {
+                linked_identities
+                    .iter()
+                    .filter_map(|linked_identity| {
+                        self.active_link(linked_identity)
+                            .filter(|link| link.canonical == identity)
+                            .map(|link| (linked_identity.clone(), link.clone()))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Resolves the derivation path of a user through the identity links
+    /// # Arguments
+    /// * `guard_id` - The guard ID used for JWT verification
+    /// * `sub` - The user identifier
+    pub(crate) fn derivation_path(&self, guard_id: &str, sub: &str) -> String {
+        let identity = format_identity(guard_id, sub);
+        match self.active_link(&identity) {
+            Some(link) => link.canonical.clone(),
+            None => identity,
+        }
+    }
+
+    /// Gets the link of an identity, unless its unlink cool-down elapsed
+    fn active_link(&self, identity: &str) -> Option<&IdentityLink> {
+        let now = env::block_timestamp_ms();
+        self.identity_links.get(identity).filter(|link| link.is_active(now))
+    }
+
+    /// Checks if an identity can be linked to a canonical identity
+    /// # Arguments
+    /// * `identity` - The canonical identity
+    /// * `linked_identity` - The identity to link
+    /// # Returns
+    /// * Why the identities cannot be linked, none if they can
+    fn link_error(&self, identity: &str, linked_identity: &str) -> Option<String> {
+        if identity == linked_identity {
+            return Some("Cannot link an identity to itself".to_string());
+        }
+        if self.active_link(identity).is_some() {
+            return Some(format!("Identity '{}' is linked to another identity", identity));
+        }
+        if self.active_link(linked_identity).is_some() {
+            return Some(format!("Identity '{}' is already linked", linked_identity));
+        }
+        if !self.get_linked_identities(linked_identity.to_string()).is_empty() {
+            return Some(format!("Identity '{}' has linked identities", linked_identity));
+        }
+        if self.get_linked_identities(identity.to_string()).len() >= MAX_LINKED_IDENTITIES {
+            return Some(format!("Cannot link more than {} identities", MAX_LINKED_IDENTITIES));
+        }
+        None
+    }
+
+    /// Gets the link an identity can request to remove
+    /// # Arguments
+    /// * `identity` - The authenticated identity, either side of the link
+    /// * `linked_identity` - The linked identity
+    /// # Returns
+    /// * The link, or why it cannot be removed
+    fn unlinkable_link(&self, identity: &str, linked_identity: &str) -> Result<IdentityLink, String> {
+        let link = self
+            .active_link(linked_identity)
+            .ok_or_else(|| format!("Identity '{}' is not linked", linked_identity))?;
+        if link.unlinks_at.is_some() {
+            return Err(format!("Identity '{}' is already being unlinked", linked_identity));
+        }
+        if identity != linked_identity && identity != link.canonical {
+            return Err(format!("Identity '{}' cannot unlink '{}'", identity, linked_identity));
+        }
+        Ok(link.clone())
+    }
+
+    /// Removes the links of a canonical identity whose unlink cool-down elapsed
+    /// # Arguments
+    /// * `identity` - The canonical identity
+    /// * `now` - The current block timestamp in milliseconds
+    fn remove_expired_links(&mut self, identity: &str, now: u64) {
+        let Some(linked_identities) = self.linked_identities.get(identity).cloned() else {
+            return;
+        };
+        let (active, expired): (Vec<String>, Vec<String>) = linked_identities.into_iter().partition(|linked_identity| {
+            self.identity_links
+                .get(linked_identity)
+                .is_some_and(|link| link.canonical == identity && link.is_active(now))
+        });
+        for linked_identity in expired {
+            if self.identity_links.get(&linked_identity).is_some_and(|link| link.canonical == identity) {
+                self.identity_links.remove(&linked_identity);
+            }
+        }
+        if active.is_empty() {
+            self.linked_identities.remove(identity);
+        } else {
+            self.linked_identities.insert(identity.to_string(), active);
+        }
+    }
+
+    /// Checks the guard verification result of an identity
+    /// # Arguments
+    /// * `guard_id` - The guard ID of the identity
+    /// * `sub` - The user identifier the JWT must belong to
+    /// * `call_result` - Verification result containing success, user ID or rejection reason, and guard name
+    /// # Returns
+    /// * Whether the JWT was verified for the sub, logging the reason otherwise
+    pub(crate) fn verified_identity(&self, guard_id: &str, sub: &str, call_result: Result<(bool, String, String), PromiseError>) -> bool {
+        match self.verified_user(guard_id, call_result) {
+            Ok(user) if user == sub => return true,
+            Ok(_) => env::log_str(&format!("JWT of guard '{}' does not belong to sub '{}'", guard_id, sub)),
+            Err(outcome) => {
+                if let SignOutcome::Failed { reason, .. } = outcome {
+                    env::log_str(&reason);
+                }
+            }
+        }
+        false
+    }
+
+    /// Gets the address of the guard verifying a JWT
+    /// # Arguments
+    /// * `guard_id` - The guard ID, with or without suffix
+    /// # Panics
+    /// * If the guard does not exist or is frozen
+    pub(crate) fn verifying_guard(&self, guard_id: &str) -> AccountId {
+        let guard_prefix = self.get_guard_prefix(guard_id.to_string());
+        let guard_address = match self.guards.get(&guard_prefix) {
+            Some(guard_info) => guard_info.account_id.clone(),
+            None => {
+                env::panic_str(&format!("Cannot verify: Guard with ID '{}' does not exist", guard_id));
+            }
+        };
+        self.assert_guard_not_frozen(&guard_prefix);
+        guard_address
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn link_expires_after_unlink() {
+        let mut link = IdentityLink { canonical: "jwt#alice".to_string(), linked_at: 0, unlinks_at: None };
+        assert!(link.is_active(u64::MAX));
+
+        link.unlinks_at = Some(1_000);
+        assert!(link.is_active(999));
+        assert!(!link.is_active(1_000));
+    }
+
+    #[test]
+    fn link_commitment_binds_identities() {
+        let commitment = link_commitment("link", "jwt#alice", "apple#alice");
+        assert_ne!(commitment, link_commitment("link", "apple#alice", "jwt#alice"));
+        assert_ne!(commitment, link_commitment("unlink", "jwt#alice", "apple#alice"));
+    }
+}
@@ -1,18 +1,100 @@
 // Find all our documentation at https://docs.near.org
-use near_sdk::{env, log, near, Promise, PromiseError, AccountId, NearToken, Gas};
-use std::collections::HashMap;
+// Sign callbacks receive the request context as arguments, and near-sdk doesn't forward lint attributes to their ext wrappers
+#![allow(clippy::too_many_arguments)]
+use std::slice::Iter;
+
+use near_plugins::{
+    access_control, access_control_any, pause, AccessControlRole, AccessControllable, Pausable,
+    Upgradable,
+};
+use near_sdk::{env, near, require, BorshStorageKey, Promise, PromiseError, PromiseOrValue, PromiseResult, AccountId, NearToken, Gas, PanicOnDefault, PublicKey, serde_json};
+use near_sdk::borsh::BorshDeserialize;
+use near_sdk::json_types::U128;
+use near_sdk::store::{IterableMap, LookupMap};
+use std::collections::{HashMap, HashSet};
 // Declare the interfaces module
 pub mod external_contracts;
-
+pub mod derivation;
+pub mod events;
+pub mod evm;
+pub mod fees;
+pub mod guards;
+pub mod hashing;
+pub mod identity;
+pub mod migration;
+pub mod outcome;
+pub mod ownership;
+pub mod policy;
+pub mod quorum;
+pub mod rate_limit;
+pub mod session;
+pub mod spending;
+pub mod timelock;
+pub mod transaction;
+
+use crate::derivation::{BitcoinNetwork, DerivedAddresses};
+use crate::events::FastAuthEvent;
+use crate::fees::{SignCost, SignFees, DEFAULT_MPC_DEPOSIT};
+use crate::guards::{guard_registry, GuardInfo};
+use crate::migration::FastAuthV1;
+use crate::hashing::HashingMode;
+use crate::identity::{format_identity, IdentityLink};
+use crate::outcome::{SignErrorCode, SignOutcome};
+use base_jwt_guard::{Ownable, PendingOwner};
+use crate::policy::TransactionPolicy;
+use crate::quorum::{GuardQuorum, PendingApproval};
+use crate::rate_limit::{RateLimit, RateLimits, RequestWindow};
+use crate::session::Session;
+use crate::spending::{payload_spend, validate_second_factor, verify_second_factor, Asset, RecordedSpend, SecondFactor, SpendingLimits, SpendingUpdate, SpendingWindow};
+use crate::timelock::{AdminOperation, ScheduledOperation, MAX_TIMELOCK_DELAY_MS};
 use crate::external_contracts::{external_guard, mpc_contract, mpc_contract_legacy, SignRequest, SignRequestV2, SignResponseAny, PayloadType};
 
 const DEFAULT_MPC_KEY_VERSION: u32 = 0;
 const DEFAULT_DOMAIN_ID: u64 = 1;
 
-const MIGRATION_TGAS: u64 = 10;
+const MAX_BATCH_SIZE: usize = 8;
+
+/// Prefixes of the batch Merkle tree leaves and nodes, and tag of its root
+const BATCH_LEAF_PREFIX: u8 = 0x00;
+const BATCH_NODE_PREFIX: u8 = 0x01;
+const BATCH_COMMITMENT_TAG: &[u8] = b"fast-auth:batch";
+
+/// Prefix of the pausable feature keys used to freeze guards
+const FROZEN_GUARD_KEY_PREFIX: &str = "guard:";
+
+/// Pausable feature key pausing every method
+const PAUSE_ALL_KEY: &str = "ALL";
+
+const DEFAULT_GUARDS_LIMIT: u32 = 50;
+const MAX_GUARDS_LIMIT: u32 = 100;
+
+const DEFAULT_OPERATIONS_LIMIT: u32 = 50;
+const MAX_OPERATIONS_LIMIT: u32 = 100;
+
+/// Storage key of the contract state
+const STATE_KEY: &[u8] = b"STATE";
 
 const CONTRACT_VERSION: &str = "1.0.0";
 
+#[derive(BorshStorageKey)]
+#[near(serializers = [borsh])]
+pub enum Prefix {
+    Guards,
+    ScheduledOperations,
+    RequestWindows,
+    IdentityLinks,
+    LinkedIdentities,
+    GuardPolicies,
+    GuardSpendingLimits,
+    UserSpendingLimits,
+    SpendingWindows,
+    SecondFactors,
+    Sessions,
+    IdentitySessions,
+    GuardQuorums,
+    PendingApprovals,
+}
+
 /// Supported signature algorithms
 #[derive(Debug, Clone, PartialEq)]
 #[near(serializers=[borsh, json])]
@@ -34,33 +116,78 @@ impl SignatureAlgorithm {
     }
 }
 
+#[near(serializers = [json, borsh])]
+#[derive(AccessControlRole, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Role {
+    DAO,
+    GuardManager,
+    MpcConfigManager,
+    PauseManager,
+    CodeStager,
+    CodeDeployer,
+    DurationManager,
+    Guardian,
+}
+
+impl Role {
+    pub fn iterator() -> Iter<'static, Role> {
+        static ROLES: [Role; 8] = [
+            Role::DAO,
+            Role::GuardManager,
+            Role::MpcConfigManager,
+            Role::PauseManager,
+            Role::CodeStager,
+            Role::CodeDeployer,
+            Role::DurationManager,
+            Role::Guardian,
+        ];
+        ROLES.iter()
+    }
+}
+
 // Define the contract structure
+#[access_control(role_type(Role))]
+#[derive(PanicOnDefault, Pausable, Upgradable)]
+#[pausable(
+    pause_roles(Role::PauseManager, Role::DAO),
+    unpause_roles(Role::DAO)
+)]
+#[upgradable(access_control_roles(
+    code_stagers(Role::CodeStager, Role::DAO),
+    code_deployers(Role::CodeDeployer, Role::DAO),
+    duration_initializers(Role::DurationManager, Role::DAO),
+    duration_update_stagers(Role::DurationManager, Role::DAO),
+    duration_update_appliers(Role::DurationManager, Role::DAO),
+))]
 #[near(contract_state)]
 pub struct FastAuth {
-    guards: HashMap<String, AccountId>,
+    guards: IterableMap<String, GuardInfo>,
     owner: AccountId,
+    pending_owner: Option<PendingOwner>,
     mpc_address: AccountId,
     mpc_key_version: u32,
     mpc_domain_id: u64,
     version: String,
-    pauser: AccountId,
-    paused: bool,
-}
-
-// Define the default, which automatically initializes the contract
-impl Default for FastAuth {
-    fn default() -> Self {
-        Self {
-            guards: HashMap::new(),
-            owner: env::current_account_id(),
-            mpc_address: env::current_account_id(),
-            mpc_key_version: DEFAULT_MPC_KEY_VERSION,
-            mpc_domain_id: DEFAULT_DOMAIN_ID,
-            version: CONTRACT_VERSION.to_string(),
-            pauser: env::current_account_id(),
-            paused: false,
-        }
-    }
+    timelock_delay_ms: u64,
+    scheduled_operations: IterableMap<u64, ScheduledOperation>,
+    next_operation_id: u64,
+    rate_limits: RateLimits,
+    request_windows: LookupMap<String, RequestWindow>,
+    mpc_deposit: NearToken,
+    sign_fees: SignFees,
+    collected_fees: NearToken,
+    treasury: Option<AccountId>,
+    identity_links: LookupMap<String, IdentityLink>,
+    linked_identities: LookupMap<String, Vec<String>>,
+    guard_policies: LookupMap<String, TransactionPolicy>,
+    guard_spending_limits: LookupMap<String, SpendingLimits>,
+    user_spending_limits: LookupMap<String, SpendingLimits>,
+    spending_windows: LookupMap<String, SpendingWindow>,
+    second_factors: LookupMap<String, SecondFactor>,
+    sessions: LookupMap<PublicKey, Session>,
+    identity_sessions: LookupMap<String, Vec<PublicKey>>,
+    guard_quorums: LookupMap<String, GuardQuorum>,
+    pending_approvals: LookupMap<Vec<u8>, PendingApproval>,
 }
 
 // Implement the contract structure
@@ -76,73 +203,69 @@ impl FastAuth {
     /// Gets the current owner of the contract
     /// # Returns
     /// * The AccountId of the current owner
+    #[pause]
     pub fn owner(&self) -> AccountId {
-        self.non_paused_only();
         self.owner.clone()
     }
 
-    /// Changes the owner of the contract
-    /// # Arguments
-    /// * `new_owner` - The AccountId of the new owner
-    /// # Panics
-    /// * If the caller is not the current owner
-    pub fn change_owner(&mut self, new_owner: AccountId) {
-        self.non_paused_only();
-        self.only_owner();
-        self.owner = new_owner;
-    }
-
-    /// Pauses the contract
-    /// # Panics
-    /// * If the caller is not the owner
-    pub fn pause(&mut self) {
-        self.only_pauser();
-        self.paused = true;
-    }
-
-    /// Unpauses the contract
-    /// # Panics
-    /// * If the caller is not the owner
-    pub fn unpause(&mut self) {
-        self.only_owner();
-        self.paused = false;
+    /// Gets the ownership transfer awaiting acceptance
+    /// # Returns
+    /// * The proposed owner and expiry of the transfer, if any
+    #[pause]
+    pub fn pending_owner(&self) -> Option<PendingOwner> {
+        self.pending_owner.clone()
     }
 
-    /// Sets the pauser of the contract
+    /// Proposes a new owner, who takes over once they accept the ownership
     /// # Arguments
-    /// * `pauser` - The AccountId of the new pauser
+    /// * `new_owner` - The AccountId of the proposed owner
+    /// * `expires_in_ms` - Milliseconds after which the proposal can no longer be accepted, never if none
     /// # Panics
-    /// * If the caller is not the owner
-    pub fn set_pauser(&mut self, pauser: AccountId) {
+    /// * If the caller is not the current owner
+    /// * If the proposed owner is the current owner
+    #[pause]
+    pub fn propose_owner(&mut self, new_owner: AccountId, expires_in_ms: Option<u64>) {
         self.only_owner();
-        self.pauser = pauser;
+        self.internal_propose_owner(new_owner, expires_in_ms);
     }
 
-    /// Checks if the caller is the pauser
+    /// Cancels the ownership transfer awaiting acceptance
     /// # Panics
-    /// * If the caller is not the pauser
-    pub fn only_pauser(&self) {
-        assert!(env::signer_account_id() == self.pauser, "Only the pauser can call this function");
+    /// * If the caller is not the current owner
+    /// * If there is no pending transfer
+    #[pause]
+    pub fn cancel_ownership_transfer(&mut self) {
+        self.only_owner();
+        self.internal_cancel_ownership_transfer();
     }
 
-    /// Checks if the contract is paused
+    /// Accepts the ownership transfer, moving the super admin and DAO role to the caller
     /// # Panics
-    /// * If the contract is paused
-    fn non_paused_only(&self) {
-        assert!(!self.paused, "Contract is paused");
-    }
-
-    /// Gets the current paused state of the contract
-    /// # Returns
-    /// * The current paused state of the contract
-    pub fn paused(&self) -> bool {
-        self.paused
+    /// * If there is no pending transfer
+    /// * If the caller is not the proposed owner or the transfer expired
+    #[pause]
+    pub fn accept_ownership(&mut self) {
+        let previous_owner = self.internal_accept_ownership();
+        let new_owner = self.owner.clone();
+
+        let mut acl = self.acl_get_or_init();
+        acl.revoke_role_unchecked(Role::DAO, &previous_owner);
+        acl.revoke_super_admin_unchecked(&previous_owner);
+        require!(
+            acl.add_super_admin_unchecked(&new_owner),
+            "Failed to add super admin"
+        );
+        require!(
+            acl.grant_role_unchecked(Role::DAO, &new_owner),
+            "Failed to grant DAO role"
+        );
     }
 
     /// Initializes the contract with initial guards and owner
     /// # Arguments
     /// * `init_guards` - HashMap mapping guard IDs to their contract addresses
-    /// * `owner` - The AccountId to set as contract owner
+    /// * `owner` - The AccountId to set as contract owner, super admin and DAO
+    /// * `pauser` - The AccountId granted the PauseManager role
     /// # Returns
     /// * The initialized FastAuth contract instance
     /// # Panics
@@ -153,127 +276,296 @@ impl FastAuth {
         if env::state_exists() {
             env::panic_str("Contract is already initialized");
         }
-        Self {
-            guards: init_guards,
-            owner,
+        let mut this = Self {
+            guards: guard_registry(init_guards),
+            owner: owner.clone(),
+            pending_owner: None,
             mpc_address: env::current_account_id(),
             mpc_key_version: DEFAULT_MPC_KEY_VERSION,
             mpc_domain_id: DEFAULT_DOMAIN_ID,
             version: CONTRACT_VERSION.to_string(),
-            pauser,
-            paused: false,
+            timelock_delay_ms: 0,
+            scheduled_operations: IterableMap::new(Prefix::ScheduledOperations),
+            next_operation_id: 0,
+            rate_limits: RateLimits::default(),
+            request_windows: LookupMap::new(Prefix::RequestWindows),
+            mpc_deposit: DEFAULT_MPC_DEPOSIT,
+            sign_fees: SignFees::default(),
+            collected_fees: NearToken::from_yoctonear(0),
+            treasury: None,
+            identity_links: LookupMap::new(Prefix::IdentityLinks),
+            linked_identities: LookupMap::new(Prefix::LinkedIdentities),
+            guard_policies: LookupMap::new(Prefix::GuardPolicies),
+            guard_spending_limits: LookupMap::new(Prefix::GuardSpendingLimits),
+            user_spending_limits: LookupMap::new(Prefix::UserSpendingLimits),
+            spending_windows: LookupMap::new(Prefix::SpendingWindows),
+            second_factors: LookupMap::new(Prefix::SecondFactors),
+            sessions: LookupMap::new(Prefix::Sessions),
+            identity_sessions: LookupMap::new(Prefix::IdentitySessions),
+            guard_quorums: LookupMap::new(Prefix::GuardQuorums),
+            pending_approvals: LookupMap::new(Prefix::PendingApprovals),
+        };
+        this.init_acl(&owner, &pauser);
+        this
+    }
+
+    /// Migrates the contract state from the current layout or the layout prior to
+    /// near-plugins roles, see `migration`
+    /// # Returns
+    /// * The migrated contract state
+    /// # Panics
+    /// * If the previous state cannot be read
+    #[private]
+    #[init(ignore_state)]
+    pub fn migrate() -> Self {
+        let state = env::storage_read(STATE_KEY).expect("Error: No previous state");
+
+        if let Ok(prev_state) = Self::try_from_slice(&state) {
+            return prev_state;
+        }
+        let prev_state = FastAuthV1::try_from_slice(&state).expect("Error: Cannot deserialize previous state");
+        let (owner, pauser, paused) = (prev_state.owner.clone(), prev_state.pauser.clone(), prev_state.paused);
+        let mut this = Self::from(prev_state);
+        this.init_acl(&owner, &pauser);
+        if paused {
+            // The previous pause carries over as a pause of every method, which only the DAO can lift
+            let paused_keys = HashSet::from([PAUSE_ALL_KEY.to_string()]);
+            env::storage_write(this.pa_storage_key(), &near_sdk::borsh::to_vec(&paused_keys).expect("Failed to serialize paused keys"));
         }
+        this
+    }
+
+    /// Initializes the ACL
+    /// # Arguments
+    /// * `owner` - The account made super admin and granted the DAO role
+    /// * `pauser` - The account granted the PauseManager role
+    fn init_acl(&mut self, owner: &AccountId, pauser: &AccountId) {
+        let mut acl = self.acl_get_or_init();
+        require!(
+            acl.add_super_admin_unchecked(owner),
+            "Failed to initialize super admin"
+        );
+        require!(
+            acl.grant_role_unchecked(Role::DAO, owner),
+            "Failed to grant DAO role"
+        );
+        require!(
+            acl.grant_role_unchecked(Role::PauseManager, pauser),
+            "Failed to grant PauseManager role"
+        );
     }
 
     // FastAuth MPC methods
     
-    /// Sets the MPC contract address
+    /// Schedules setting the MPC contract address
     /// # Arguments
     /// * `mpc_address` - The AccountId of the new MPC contract
+    /// # Returns
+    /// * The ID of the scheduled operation, none if it took effect immediately
     /// # Panics
-    /// * If the caller is not the owner
-    pub fn set_mpc_address(&mut self, mpc_address: AccountId) {
-        self.non_paused_only();
-        self.only_owner();
-        env::log_str(&format!("Setting MPC address to {}", mpc_address));
-        self.mpc_address = mpc_address;
+    /// * If the caller is not a MpcConfigManager or DAO
+    #[pause]
+    #[access_control_any(roles(Role::MpcConfigManager, Role::DAO))]
+    pub fn set_mpc_address(&mut self, mpc_address: AccountId) -> Option<u64> {
+        self.schedule_operation(AdminOperation::SetMpcAddress { mpc_address })
     }
 
     /// Gets the current MPC contract address
     /// # Returns
     /// * The AccountId of the current MPC contract
+    #[pause]
     pub fn mpc_address(&self) -> AccountId {
-        self.non_paused_only();
         self.mpc_address.clone()
     }
 
-    /// Sets the MPC key version
+    /// Schedules setting the MPC key version
     /// # Arguments
     /// * `mpc_key_version` - The new MPC key version number
+    /// # Returns
+    /// * The ID of the scheduled operation, none if it took effect immediately
     /// # Panics
-    /// * If the caller is not the owner
-    pub fn set_mpc_key_version(&mut self, mpc_key_version: u32) {
-        self.non_paused_only();
-        self.only_owner();
-        env::log_str(&format!("Setting MPC key version to {}", mpc_key_version));
-        self.mpc_key_version = mpc_key_version;
+    /// * If the caller is not a MpcConfigManager or DAO
+    #[pause]
+    #[access_control_any(roles(Role::MpcConfigManager, Role::DAO))]
+    pub fn set_mpc_key_version(&mut self, mpc_key_version: u32) -> Option<u64> {
+        self.schedule_operation(AdminOperation::SetMpcKeyVersion { mpc_key_version })
     }
 
     /// Gets the current MPC key version
     /// # Returns
     /// * The current MPC key version number
+    #[pause]
     pub fn mpc_key_version(&self) -> u32 {
-        self.non_paused_only();
         self.mpc_key_version
     }
 
-    /// Updates the contract
+    /// Schedules setting the MPC domain ID
+    /// # Arguments
+    /// * `mpc_domain_id` - The new MPC domain ID number
+    /// # Returns
+    /// * The ID of the scheduled operation, none if it took effect immediately
     /// # Panics
-    /// * If the caller is not the owner
+    /// * If the caller is not a MpcConfigManager or DAO
+    #[pause]
+    #[access_control_any(roles(Role::MpcConfigManager, Role::DAO))]
+    pub fn set_mpc_domain_id(&mut self, mpc_domain_id: u64) -> Option<u64> {
+        self.schedule_operation(AdminOperation::SetMpcDomainId { mpc_domain_id })
+    }
+
+    /// Emits the current MPC configuration
+    fn emit_mpc_config_changed(&self) {
+        FastAuthEvent::MpcConfigChanged {
+            mpc_address: &self.mpc_address,
+            mpc_key_version: self.mpc_key_version,
+            mpc_domain_id: self.mpc_domain_id,
+            mpc_deposit: self.mpc_deposit,
+        }.emit();
+    }
+
+    /// Gets the current MPC domain ID
     /// # Returns
-    /// * Promise resolving to the contract update result
-    pub fn update_contract(&self) -> Promise {
-        self.only_owner();
-        let code = env::input().expect("Error: No input").to_vec();
+    /// * The current MPC domain ID number
+    #[pause]
+    pub fn mpc_domain_id(&self) -> u64 {
+        self.mpc_domain_id
+    }
 
-        // Deploy the contract on self
-        Promise::new(env::current_account_id())
-            .deploy_contract(code)
-            // When the contract update requires a state migration, you need to make a function call to 
-            // the `migrate` function, to handle all the state migrations
-            .function_call(
-                "migrate".to_string(),
-                vec![],
-                NearToken::from_near(0),
-                Gas::from_tgas(MIGRATION_TGAS),
-            )
-            .as_return()
+    /// Schedules setting the deposit forwarded to MPC with each signing request
+    /// # Arguments
+    /// * `mpc_deposit` - The deposit MPC charges per signature
+    /// # Returns
+    /// * The ID of the scheduled operation, none if it took effect immediately
+    /// # Panics
+    /// * If the caller is not a MpcConfigManager or DAO
+    #[pause]
+    #[access_control_any(roles(Role::MpcConfigManager, Role::DAO))]
+    pub fn set_mpc_deposit(&mut self, mpc_deposit: NearToken) -> Option<u64> {
+        self.schedule_operation(AdminOperation::SetMpcDeposit { mpc_deposit })
+    }
+
+    /// Gets the deposit forwarded to MPC with each signing request
+    #[pause]
+    pub fn mpc_deposit(&self) -> NearToken {
+        self.mpc_deposit
+    }
+
+    // FastAuth fee methods
+
+    /// Sets the service fee kept for each payload signed with an algorithm
+    /// # Arguments
+    /// * `algorithm` - The signature algorithm ("secp256k1", "ecdsa", or "eddsa")
+    /// * `fee` - The fee per signed payload
+    /// # Panics
+    /// * If the caller is not a DAO
+    /// * If the algorithm is not supported
+    #[pause]
+    #[access_control_any(roles(Role::DAO))]
+    pub fn set_sign_fee(&mut self, algorithm: String, fee: NearToken) {
+        let signature_algorithm = SignatureAlgorithm::parse_str(&algorithm).unwrap_or_else(|err| env::panic_str(&err));
+        self.sign_fees.set_fee(&signature_algorithm, fee);
+        FastAuthEvent::SignFeeChanged { algorithm: &signature_algorithm, fee }.emit();
+    }
+
+    /// Gets the service fees per signed payload
+    #[pause]
+    pub fn sign_fees(&self) -> SignFees {
+        self.sign_fees.clone()
     }
 
-    /// Migrates the contract state
+    /// Gets the deposit to attach to a signing request
+    /// # Arguments
+    /// * `algorithm` - The signature algorithm ("secp256k1", "ecdsa", or "eddsa")
+    /// * `payloads` - The number of payloads to sign, 1 by default
     /// # Returns
-    /// * The migrated contract state
-    #[private]
-    #[init(ignore_state)]
-    pub fn migrate() -> Self {
-        env::log_str("migrate");
-        if env::state_exists() {
-            env::log_str("state exists: migrating state");
-            let prev_state = env::state_read::<Self>().expect("Error: No previous state");
-            Self {
-                guards: prev_state.guards,
-                owner: prev_state.owner,
-                mpc_address: prev_state.mpc_address,
-                mpc_domain_id: prev_state.mpc_domain_id,
-                mpc_key_version: prev_state.mpc_key_version,
-                version: prev_state.version,
-                pauser: prev_state.pauser,
-                paused: prev_state.paused,
-            }
-        } else {
-            env::log_str("state does not exist: initializing default state");
-            Self::default()
-        }
+    /// * The MPC deposit plus the service fee of every payload. The fee is only kept for payloads MPC signs
+    /// # Panics
+    /// * If the algorithm is not supported
+    #[pause]
+    pub fn quote_sign_cost(&self, algorithm: String, payloads: Option<u32>) -> NearToken {
+        let signature_algorithm = SignatureAlgorithm::parse_str(&algorithm).unwrap_or_else(|err| env::panic_str(&err));
+        self.sign_cost(&signature_algorithm).for_payloads(payloads.unwrap_or(1))
     }
 
-    /// Sets the MPC domain ID
+    /// Sets the account the collected fees are withdrawn to
     /// # Arguments
-    /// * `mpc_domain_id` - The new MPC domain ID number
+    /// * `treasury` - The treasury account
     /// # Panics
-    /// * If the caller is not the owner
-    pub fn set_mpc_domain_id(&mut self, mpc_domain_id: u64) {
-        self.non_paused_only();
-        self.only_owner();
-        env::log_str(&format!("Setting MPC domain ID to {}", mpc_domain_id));
-        self.mpc_domain_id = mpc_domain_id;
+    /// * If the caller is not a DAO
+    #[pause]
+    #[access_control_any(roles(Role::DAO))]
+    pub fn set_treasury(&mut self, treasury: AccountId) {
+        FastAuthEvent::TreasuryChanged { treasury: &treasury }.emit();
+        self.treasury = Some(treasury);
     }
 
-    /// Gets the current MPC domain ID
+    /// Gets the account the collected fees are withdrawn to
+    #[pause]
+    pub fn treasury(&self) -> Option<AccountId> {
+        self.treasury.clone()
+    }
+
+    /// Gets the fees collected and not withdrawn yet
+    #[pause]
+    pub fn collected_fees(&self) -> NearToken {
+        self.collected_fees
+    }
+
+    /// Transfers collected fees to the treasury
+    /// # Arguments
+    /// * `amount` - The amount to withdraw, every collected fee if none
     /// # Returns
-    /// * The current MPC domain ID number
-    pub fn mpc_domain_id(&self) -> u64 {
-        self.non_paused_only();
-        self.mpc_domain_id
+    /// * Promise resolving to whether the fees were transferred
+    /// # Panics
+    /// * If the treasury is not set
+    /// * If the caller is neither the treasury nor a DAO
+    /// * If the amount is zero or exceeds the collected fees
+    #[pause]
+    pub fn withdraw_fees(&mut self, amount: Option<NearToken>) -> Promise {
+        let treasury = self.treasury.clone().unwrap_or_else(|| env::panic_str("Treasury is not set"));
+        let caller = env::predecessor_account_id();
+        require!(
+            caller == treasury || self.acl_has_role(Role::DAO.into(), caller),
+            "Only the treasury or a DAO can withdraw fees"
+        );
+        let amount = amount.unwrap_or(self.collected_fees);
+        require!(!amount.is_zero(), "No fees to withdraw");
+        require!(amount <= self.collected_fees, "Cannot withdraw more than the collected fees");
+
+        self.collected_fees = self.collected_fees.saturating_sub(amount);
+        Promise::new(treasury.clone())
+            .transfer(amount)
+            .then(Self::ext(env::current_account_id()).on_withdraw_fees_callback(treasury, amount))
+    }
+
+    /// Restores the collected fees if their transfer to the treasury failed
+    /// # Arguments
+    /// * `treasury` - The treasury account
+    /// * `amount` - The amount transferred
+    /// # Returns
+    /// * Whether the fees were transferred
+    #[private]
+    pub fn on_withdraw_fees_callback(&mut self, treasury: AccountId, amount: NearToken) -> bool {
+        if !matches!(env::promise_result(0), PromiseResult::Successful(_)) {
+            self.collected_fees = self.collected_fees.saturating_add(amount);
+            return false;
+        }
+        FastAuthEvent::FeesWithdrawn { treasury: &treasury, amount }.emit();
+        true
+    }
+
+    /// Gets the cost of a payload signed with an algorithm under the current configuration
+    fn sign_cost(&self, algorithm: &SignatureAlgorithm) -> SignCost {
+        SignCost { mpc_deposit: self.mpc_deposit, fee: self.sign_fees.fee(algorithm) }
+    }
+
+    /// Checks that a deposit covers the cost of signing a number of payloads
+    /// # Panics
+    /// * If the deposit is lower than the cost
+    fn assert_deposit_covers(attached_deposit: NearToken, sign_cost: &SignCost, payloads: u32) {
+        let required = sign_cost.for_payloads(payloads);
+        if attached_deposit < required {
+            env::panic_str(&format!("Attached deposit must cover the sign cost of {} yoctoNEAR", required.as_yoctonear()));
+        }
     }
     
     // FastAuth Guard methods
@@ -285,172 +577,964 @@ impl FastAuth {
     /// * The AccountId of the guard contract
     /// # Panics
     /// * If the guard_id does not exist
+    /// * If the guard is frozen
+    #[pause]
     pub fn get_guard(&self, guard_id: String) -> AccountId {
-        self.non_paused_only();
-        self.guards.get(&guard_id).cloned().unwrap_or_else(|| {
-            env::panic_str(&format!("Guard with ID '{}' does not exist", guard_id));
-        })
+        self.assert_guard_not_frozen(&guard_id);
+        self.guard_info(&guard_id).account_id.clone()
+    }
+
+    /// Gets the metadata of a guard
+    /// # Arguments
+    /// * `guard_id` - The ID of the guard to look up
+    /// # Returns
+    /// * The registry record of the guard
+    /// # Panics
+    /// * If the guard_id does not exist
+    #[pause]
+    pub fn get_guard_info(&self, guard_id: String) -> GuardInfo {
+        self.guard_info(&guard_id).clone()
+    }
+
+    /// Lists the registered guards
+    /// # Arguments
+    /// * `from_index` - The index of the first guard to return, 0 by default
+    /// * `limit` - The maximum number of guards to return, 50 by default and at most 100
+    /// # Returns
+    /// * Pairs of guard ID and registry record, in registration order
+    #[pause]
+    pub fn get_guards(&self, from_index: Option<u32>, limit: Option<u32>) -> Vec<(String, GuardInfo)> {
+        self.guards
+            .iter()
+            .skip(from_index.unwrap_or(0) as usize)
+            .take(limit.unwrap_or(DEFAULT_GUARDS_LIMIT).min(MAX_GUARDS_LIMIT) as usize)
+            .map(|(guard_id, guard_info)| (guard_id.clone(), guard_info.clone()))
+            .collect()
+    }
+
+    /// Gets the number of registered guards
+    #[pause]
+    pub fn get_guards_count(&self) -> u32 {
+        self.guards.len()
     }
 
-    /// Adds a new guard to the contract
+    /// Schedules adding a new guard to the contract, replacing the guard registered under the same ID
     /// # Arguments
     /// * `guard_id` - The ID to associate with the guard
     /// * `guard_address` - The contract address of the guard
+    /// * `description` - Human readable description of the login method
+    /// * `supported_algorithms` - The signature algorithms that can be requested through the guard, every algorithm if none
+    /// # Returns
+    /// * The ID of the scheduled operation, none if it took effect immediately
     /// # Panics
-    /// * If the caller is not the owner
-    pub fn add_guard(&mut self, guard_id: String, guard_address: AccountId) {
-        self.non_paused_only();
-        self.only_owner();
+    /// * If the caller is not a GuardManager or DAO
+    /// * If the description is too long, or an algorithm is not supported
+    #[pause]
+    #[access_control_any(roles(Role::GuardManager, Role::DAO))]
+    pub fn add_guard(&mut self, guard_id: String, guard_address: AccountId, description: Option<String>, supported_algorithms: Option<Vec<String>>) -> Option<u64> {
         assert!(!guard_id.contains('#'), "Guard ID cannot contain '#'");
-        self.guards.insert(guard_id, guard_address);
+        let supported_algorithms = supported_algorithms.map(|algorithms| {
+            algorithms
+                .iter()
+                .map(|algorithm| SignatureAlgorithm::parse_str(algorithm).unwrap_or_else(|err| env::panic_str(&err)))
+                .collect()
+        });
+        let guard_info = GuardInfo::new(guard_address, description, supported_algorithms);
+        self.schedule_operation(AdminOperation::AddGuard { guard_id, guard_info })
     }
 
-    /// Removes a guard from the contract
+    /// Schedules removing a guard from the contract
     /// # Arguments
     /// * `guard_id` - The ID of the guard to remove
+    /// # Returns
+    /// * The ID of the scheduled operation, none if it took effect immediately
     /// # Panics
-    /// * If the caller is not the owner
-    pub fn remove_guard(&mut self, guard_id: String) {
-        self.non_paused_only();
-        self.only_owner();
-        log!("Removing guard: {guard_id}");
-        self.guards.remove(&guard_id);
+    /// * If the caller is not a GuardManager or DAO
+    #[pause]
+    #[access_control_any(roles(Role::GuardManager, Role::DAO))]
+    pub fn remove_guard(&mut self, guard_id: String) -> Option<u64> {
+        self.schedule_operation(AdminOperation::RemoveGuard { guard_id })
     }
 
-    /// Gets the prefix from a guard ID
+    /// Schedules setting the transaction policy of a guard, restricting what can be signed through it
     /// # Arguments
-    /// * `guard_id` - The guard ID to parse
+    /// * `guard_id` - The ID of the guard, without suffix
+    /// * `policy` - The policy of the guard, none to sign any payload
     /// # Returns
-    /// * The prefix portion of the guard ID
+    /// * The ID of the scheduled operation, none if it took effect immediately
     /// # Panics
-    /// * If guard_id is empty
-    /// * If guard_id is not in format 'guard' or 'prefix#suffix'
-    fn get_guard_prefix(&self, guard_id: String) -> String {
-        if guard_id.is_empty() {
-            env::panic_str("Guard ID cannot be empty");
+    /// * If the caller is not a GuardManager or DAO
+    /// * If the guard does not exist
+    /// * If the policy allows more than 100 receivers or methods
+    #[pause]
+    #[access_control_any(roles(Role::GuardManager, Role::DAO))]
+    pub fn set_guard_policy(&mut self, guard_id: String, policy: Option<TransactionPolicy>) -> Option<u64> {
+        if !self.guards.contains_key(&guard_id) {
+            env::panic_str(&format!("Guard with ID '{}' does not exist", guard_id));
         }
-
-        match guard_id.split('#').next() {
-            Some(prefix) if !prefix.is_empty() => prefix.to_string(),
-            _ => env::panic_str("Guard ID must be in format 'guard' or 'prefix#suffix'")
+        if let Some(Err(err)) = policy.as_ref().map(TransactionPolicy::validate) {
+            env::panic_str(&err);
         }
+        self.schedule_operation(AdminOperation::SetGuardPolicy { guard_id, policy })
     }
 
-    // Verification methods
+    /// Gets the transaction policy of a guard
+    /// # Arguments
+    /// * `guard_id` - The ID of the guard, with or without suffix
+    /// # Returns
+    /// * The policy, none if any payload can be signed through the guard
+    #[pause]
+    pub fn get_guard_policy(&self, guard_id: String) -> Option<TransactionPolicy> {
+        self.guard_policies.get(&self.get_guard_prefix(guard_id)).cloned()
+    }
 
-    /// Verifies a payload using a specified guard
+    /// Checks a payload against the transaction policy of a guard, without signing it
     /// # Arguments
-    /// * `guard_id` - The ID of the guard to use
-    /// * `verify_payload` - The data to verify
-    /// * `sign_payload` - Additional data to be signed if verification succeeds
+    /// * `guard_id` - The ID of the guard, with or without suffix
+    /// * `sign_payload` - The payload to sign
     /// # Returns
-    /// * Promise resolving to a tuple containing:
-    ///   * Boolean indicating verification success
-    ///   * String containing user identifier
+    /// * The violation, none if the payload can be signed through the guard
+    #[pause]
+    pub fn check_guard_policy(&self, guard_id: String, sign_payload: Vec<u8>) -> Option<String> {
+        self.guard_policies
+            .get(&self.get_guard_prefix(guard_id))
+            .and_then(|policy| policy.check(&sign_payload).err())
+    }
+
+    /// Checks that payloads comply with the transaction policy of a guard
+    /// # Arguments
+    /// * `guard_prefix` - The ID of the guard, without suffix
+    /// * `sign_payloads` - The payloads to sign
     /// # Panics
-    /// * If the specified guard does not exist
-    pub fn verify(&self, guard_id: String, verify_payload: String, sign_payload: Vec<u8>) -> Promise {
-        self.non_paused_only();
-        let guard_prefix = self.get_guard_prefix(guard_id.clone());
-        let guard_address = match self.guards.get(&guard_prefix) {
-            Some(address) => address.clone(),
-            None => {
-                env::panic_str(&format!("Cannot verify: Guard with ID '{}' does not exist", guard_id));
-            }
+    /// * If a payload violates the policy
+    fn assert_guard_policy_allows(&self, guard_prefix: &str, sign_payloads: &[Vec<u8>]) {
+        let Some(policy) = self.guard_policies.get(guard_prefix) else {
+            return;
         };
-
-        external_guard::ext(guard_address.clone())
-        .verify(guard_id, verify_payload, sign_payload, env::predecessor_account_id())
-        .then(Self::ext(env::current_account_id())
-            .on_verify_callback()
-        )
+        for sign_payload in sign_payloads {
+            if let Err(err) = policy.check(sign_payload) {
+                env::panic_str(&format!("Sign payload violates the policy of guard '{}': {}", guard_prefix, err));
+            }
+        }
     }
 
-    /// Executes a function call on another contract
+    // FastAuth timelock methods
+
+    /// Schedules setting the delay between scheduling an administrative operation and executing it
     /// # Arguments
-    /// * `contract_address` - The target contract's AccountId
-    /// * `method_name` - The name of the method to call
-    /// * `args` - The arguments as a JSON string
-    /// * `gas` - Amount of gas to attach to the call
+    /// * `delay_ms` - The new delay in milliseconds, 0 for operations to take effect immediately
     /// # Returns
-    /// * Promise resolving to the function call result
+    /// * The ID of the scheduled operation, none if it took effect immediately
     /// # Panics
-    /// * If the caller is not the contract owner
-    pub fn execute(&self, contract_address: AccountId, method_name: String, args: String, gas: u64) -> Promise {
-        self.non_paused_only();
-        self.only_owner();
-        
-        Promise::new(contract_address)
-            .function_call(
-                method_name,
-                args.as_bytes().to_vec(),
-                NearToken::from_yoctonear(0),  // No attached deposit
-                Gas::from_tgas(gas),
-            )
+    /// * If the caller is not a DAO
+    /// * If the delay exceeds 30 days
+    #[pause]
+    #[access_control_any(roles(Role::DAO))]
+    pub fn set_timelock_delay(&mut self, delay_ms: u64) -> Option<u64> {
+        assert!(delay_ms <= MAX_TIMELOCK_DELAY_MS, "Timelock delay cannot exceed {} ms", MAX_TIMELOCK_DELAY_MS);
+        self.schedule_operation(AdminOperation::SetTimelockDelay { delay_ms })
     }
 
-    /// Processes verification results
+    /// Gets the delay between scheduling an administrative operation and executing it
+    /// # Returns
+    /// * The delay in milliseconds
+    #[pause]
+    pub fn timelock_delay(&self) -> u64 {
+        self.timelock_delay_ms
+    }
+
+    /// Gets a scheduled operation
     /// # Arguments
-    /// * `call_result` - Result containing:
-    ///   * Boolean for verification success
-    ///   * String for user identifier
+    /// * `operation_id` - The ID of the operation
     /// # Returns
-    /// * Tuple containing:
-    ///   * Boolean for overall success
-    ///   * String for user identifier
-    #[private]
-    pub fn on_verify_callback(&mut self, #[callback_result] call_result: Result<(bool, String), PromiseError>) -> (bool, String) {
-        if call_result.is_err() {
-            env::log_str("Guard verification failed");
-            return (false, String::new());
+    /// * The scheduled operation, if it is still pending
+    #[pause]
+    pub fn get_scheduled_operation(&self, operation_id: u64) -> Option<ScheduledOperation> {
+        self.scheduled_operations.get(&operation_id).cloned()
+    }
+
+    /// Lists the pending scheduled operations
+    /// # Arguments
+    /// * `from_index` - The index of the first operation to return, 0 by default
+    /// * `limit` - The maximum number of operations to return, 50 by default and at most 100
+    /// # Returns
+    /// * Pairs of operation ID and scheduled operation
+    #[pause]
+    pub fn get_scheduled_operations(&self, from_index: Option<u32>, limit: Option<u32>) -> Vec<(u64, ScheduledOperation)> {
+        self.scheduled_operations
+            .iter()
+            .skip(from_index.unwrap_or(0) as usize)
+            .take(limit.unwrap_or(DEFAULT_OPERATIONS_LIMIT).min(MAX_OPERATIONS_LIMIT) as usize)
+            .map(|(operation_id, operation)| (*operation_id, operation.clone()))
+            .collect()
+    }
+
+    /// Executes a scheduled operation once its timelock delay elapsed. Anyone can execute it
+    /// # Arguments
+    /// * `operation_id` - The ID of the operation
+    /// # Panics
+    /// * If the operation does not exist, was executed or was cancelled
+    /// * If the timelock delay of the operation has not elapsed
+    #[pause]
+    pub fn execute_operation(&mut self, operation_id: u64) {
+        let scheduled = self.scheduled_operation(operation_id);
+        if !scheduled.is_ready() {
+            env::panic_str(&format!("Operation with ID {} cannot be executed before {}", operation_id, scheduled.executable_at));
         }
-        let (verification_result, user) = call_result.unwrap();
-        if !verification_result {
-            env::log_str("Guard verification rejected");
-            return (false, user);
+        let scheduled = self.scheduled_operations.remove(&operation_id).unwrap();
+        FastAuthEvent::AdminOperationExecuted { operation_id }.emit();
+        self.apply_operation(scheduled.operation);
+    }
+
+    /// Cancels a scheduled operation
+    /// # Arguments
+    /// * `operation_id` - The ID of the operation
+    /// # Panics
+    /// * If the caller is not a Guardian or DAO
+    /// * If the operation does not exist, was executed or was cancelled
+    #[access_control_any(roles(Role::Guardian, Role::DAO))]
+    pub fn cancel_operation(&mut self, operation_id: u64) {
+        if self.scheduled_operations.remove(&operation_id).is_none() {
+            env::panic_str(&format!("Operation with ID {} does not exist", operation_id));
         }
-        (verification_result, user)
+        FastAuthEvent::AdminOperationCancelled { operation_id, cancelled_by: &env::predecessor_account_id() }.emit();
     }
 
-    // Signing methods
-    
-    /// Initiates signing with ECDSA algorithm by verifying JWT then signing payload
+    /// Schedules an operation, applying it immediately when there is no timelock delay
     /// # Arguments
-    /// * `guard_id` - The guard ID for JWT verification
-    /// * `verify_payload` - The JWT to verify
-    /// * `sign_payload` - The data to sign
-    /// * `algorithm` - The signature algorithm to use ("secp256k1", "ecdsa", or "eddsa")
+    /// * `operation` - The operation to schedule
     /// # Returns
-    /// * Promise chain for verification then signing
-    /// # Notes
-    /// * Requires an attached deposit for MPC costs
-    #[payable]
-    pub fn sign(&mut self, guard_id: String, verify_payload: String, sign_payload: Vec<u8>, algorithm: String) -> Promise {
-        self.non_paused_only();
-        let attached_deposit = env::attached_deposit();
-        let caller = env::predecessor_account_id();
+    /// * The ID of the scheduled operation, none if it took effect immediately
+    fn schedule_operation(&mut self, operation: AdminOperation) -> Option<u64> {
+        if self.timelock_delay_ms == 0 {
+            self.apply_operation(operation);
+            return None;
+        }
 
-        // Validate algorithm
-        let signature_algorithm = match SignatureAlgorithm::parse_str(&algorithm) {
+        let operation_id = self.next_operation_id;
+        self.next_operation_id += 1;
+        let scheduled = ScheduledOperation::new(operation, self.timelock_delay_ms);
+        FastAuthEvent::AdminOperationScheduled {
+            operation_id,
+            operation: &scheduled.operation,
+            executable_at: scheduled.executable_at,
+        }.emit();
+        self.scheduled_operations.insert(operation_id, scheduled);
+        Some(operation_id)
+    }
+
+    /// Gets a scheduled operation
+    /// # Arguments
+    /// * `operation_id` - The ID of the operation
+    /// # Panics
+    /// * If the operation does not exist
+    fn scheduled_operation(&self, operation_id: u64) -> &ScheduledOperation {
+        self.scheduled_operations.get(&operation_id).unwrap_or_else(|| {
+            env::panic_str(&format!("Operation with ID {} does not exist", operation_id));
+        })
+    }
+
+    /// Applies an administrative operation to the contract state
+    /// # Arguments
+    /// * `operation` - The operation to apply
+    fn apply_operation(&mut self, operation: AdminOperation) {
+        match operation {
+            AdminOperation::SetMpcAddress { mpc_address } => {
+                self.mpc_address = mpc_address;
+                self.emit_mpc_config_changed();
+            }
+            AdminOperation::SetMpcKeyVersion { mpc_key_version } => {
+                self.mpc_key_version = mpc_key_version;
+                self.emit_mpc_config_changed();
+            }
+            AdminOperation::SetMpcDomainId { mpc_domain_id } => {
+                self.mpc_domain_id = mpc_domain_id;
+                self.emit_mpc_config_changed();
+            }
+            AdminOperation::SetMpcDeposit { mpc_deposit } => {
+                self.mpc_deposit = mpc_deposit;
+                self.emit_mpc_config_changed();
+            }
+            AdminOperation::AddGuard { guard_id, mut guard_info } => {
+                guard_info.added_at = env::block_timestamp_ms();
+                FastAuthEvent::GuardAdded { guard_id: &guard_id, guard_address: &guard_info.account_id }.emit();
+                self.guards.insert(guard_id, guard_info);
+            }
+            AdminOperation::RemoveGuard { guard_id } => {
+                self.guards.remove(&guard_id);
+                self.guard_policies.remove(&guard_id);
+                self.guard_spending_limits.remove(&guard_id);
+                FastAuthEvent::GuardRemoved { guard_id: &guard_id }.emit();
+            }
+            AdminOperation::SetGuardPolicy { guard_id, policy } => {
+                FastAuthEvent::GuardPolicyChanged { guard_id: &guard_id, policy: policy.as_ref() }.emit();
+                match policy {
+                    Some(policy) => self.guard_policies.insert(guard_id, policy),
+                    None => self.guard_policies.remove(&guard_id),
+                };
+            }
+            AdminOperation::SetGuardSpendingLimits { guard_id, limits } => {
+                FastAuthEvent::GuardSpendingLimitsChanged { guard_id: &guard_id, limits: limits.as_ref() }.emit();
+                match limits {
+                    Some(limits) => self.guard_spending_limits.insert(guard_id, limits),
+                    None => self.guard_spending_limits.remove(&guard_id),
+                };
+            }
+            AdminOperation::SetTimelockDelay { delay_ms } => {
+                self.timelock_delay_ms = delay_ms;
+                FastAuthEvent::TimelockDelayChanged { delay_ms }.emit();
+            }
+        }
+    }
+
+    /// Pauses every method, the way `pa_pause_feature("ALL")` does, logging the pause as a FastAuth event
+    /// # Panics
+    /// * If the caller is not a PauseManager or DAO
+    #[access_control_any(roles(Role::PauseManager, Role::DAO))]
+    pub fn pause(&mut self) {
+        self.pa_pause_feature(PAUSE_ALL_KEY.to_string());
+        FastAuthEvent::Paused { by: &env::predecessor_account_id() }.emit();
+    }
+
+    /// Unpauses every method, the way `pa_unpause_feature("ALL")` does, logging it as a FastAuth event
+    /// # Panics
+    /// * If the caller is not a DAO
+    #[access_control_any(roles(Role::DAO))]
+    pub fn unpause(&mut self) {
+        self.pa_unpause_feature(PAUSE_ALL_KEY.to_string());
+        FastAuthEvent::Unpaused { by: &env::predecessor_account_id() }.emit();
+    }
+
+    /// Freezes a guard, rejecting its verifications and signatures until it is unfrozen
+    /// # Arguments
+    /// * `guard_id` - The ID of the guard to freeze
+    /// # Panics
+    /// * If the caller is not a PauseManager or DAO
+    /// * If the guard_id does not exist
+    #[access_control_any(roles(Role::PauseManager, Role::DAO))]
+    pub fn freeze_guard(&mut self, guard_id: String) {
+        if !self.guards.contains_key(&guard_id) {
+            env::panic_str(&format!("Guard with ID '{}' does not exist", guard_id));
+        }
+        self.pa_pause_feature(Self::frozen_guard_key(&guard_id));
+        FastAuthEvent::GuardFrozen { guard_id: &guard_id }.emit();
+    }
+
+    /// Unfreezes a guard
+    /// # Arguments
+    /// * `guard_id` - The ID of the guard to unfreeze
+    /// # Panics
+    /// * If the caller is not a DAO
+    #[access_control_any(roles(Role::DAO))]
+    pub fn unfreeze_guard(&mut self, guard_id: String) {
+        self.pa_unpause_feature(Self::frozen_guard_key(&guard_id));
+        FastAuthEvent::GuardUnfrozen { guard_id: &guard_id }.emit();
+    }
+
+    /// Checks if a guard is frozen
+    /// # Arguments
+    /// * `guard_id` - The ID of the guard
+    /// # Returns
+    /// * Whether the guard is frozen
+    pub fn is_guard_frozen(&self, guard_id: String) -> bool {
+        self.pa_is_paused(Self::frozen_guard_key(&guard_id))
+    }
+
+    /// Gets the frozen guards
+    /// # Returns
+    /// * The IDs of the frozen guards, sorted
+    pub fn frozen_guards(&self) -> Vec<String> {
+        let mut frozen_guards: Vec<String> = self
+            .pa_all_paused()
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|key| key.strip_prefix(FROZEN_GUARD_KEY_PREFIX).map(str::to_string))
+            .collect();
+        frozen_guards.sort();
+        frozen_guards
+    }
+
+    /// Gets the pausable feature key of a guard
+    fn frozen_guard_key(guard_id: &str) -> String {
+        format!("{}{}", FROZEN_GUARD_KEY_PREFIX, guard_id)
+    }
+
+    /// Checks that a guard is not frozen
+    /// # Arguments
+    /// * `guard_prefix` - The ID of the guard, without suffix
+    /// # Panics
+    /// * If the guard is frozen
+    fn assert_guard_not_frozen(&self, guard_prefix: &str) {
+        if self.pa_is_paused(Self::frozen_guard_key(guard_prefix)) {
+            env::panic_str(&format!("Guard with ID '{}' is frozen", guard_prefix));
+        }
+    }
+
+    /// Gets the registry record of a guard
+    /// # Arguments
+    /// * `guard_id` - The ID of the guard, without suffix
+    /// # Panics
+    /// * If the guard_id does not exist
+    fn guard_info(&self, guard_id: &str) -> &GuardInfo {
+        self.guards.get(guard_id).unwrap_or_else(|| {
+            env::panic_str(&format!("Guard with ID '{}' does not exist", guard_id));
+        })
+    }
+
+    /// Checks that an algorithm can be requested through a guard
+    /// # Arguments
+    /// * `guard_prefix` - The ID of the guard, without suffix
+    /// * `algorithm` - The requested signature algorithm
+    /// # Panics
+    /// * If the guard does not support the algorithm
+    fn assert_guard_supports(&self, guard_prefix: &str, algorithm: &SignatureAlgorithm) {
+        if !self.guard_info(guard_prefix).supports(algorithm) {
+            env::panic_str(&format!("Guard with ID '{}' does not support algorithm {:?}", guard_prefix, algorithm));
+        }
+    }
+
+    /// Gets the prefix from a guard ID
+    /// # Arguments
+    /// * `guard_id` - The guard ID to parse
+    /// # Returns
+    /// * The prefix portion of the guard ID
+    /// # Panics
+    /// * If guard_id is empty
+    /// * If guard_id is not in format 'guard' or 'prefix#suffix'
+    fn get_guard_prefix(&self, guard_id: String) -> String {
+        if guard_id.is_empty() {
+            env::panic_str("Guard ID cannot be empty");
+        }
+
+        match guard_id.split('#').next() {
+            Some(prefix) if !prefix.is_empty() => prefix.to_string(),
+            _ => env::panic_str("Guard ID must be in format 'guard' or 'prefix#suffix'")
+        }
+    }
+
+    // Verification methods
+
+    /// Verifies a payload using a specified guard
+    /// # Arguments
+    /// * `guard_id` - The ID of the guard to use
+    /// * `verify_payload` - The data to verify
+    /// * `sign_payload` - Additional data to be signed if verification succeeds
+    /// # Returns
+    /// * Promise resolving to a tuple containing:
+    ///   * Boolean indicating verification success
+    ///   * String containing user identifier
+    /// # Panics
+    /// * If the specified guard does not exist
+    #[pause]
+    pub fn verify(&self, guard_id: String, verify_payload: String, sign_payload: Vec<u8>) -> Promise {
+        let guard_prefix = self.get_guard_prefix(guard_id.clone());
+        let guard_address = match self.guards.get(&guard_prefix) {
+            Some(guard_info) => guard_info.account_id.clone(),
+            None => {
+                env::panic_str(&format!("Cannot verify: Guard with ID '{}' does not exist", guard_id));
+            }
+        };
+        self.assert_guard_not_frozen(&guard_prefix);
+
+        external_guard::ext(guard_address.clone())
+        .verify(guard_id, verify_payload, sign_payload, env::predecessor_account_id())
+        .then(Self::ext(env::current_account_id())
+            .on_verify_callback()
+        )
+    }
+
+    /// Executes a function call on another contract
+    /// # Arguments
+    /// * `contract_address` - The target contract's AccountId
+    /// * `method_name` - The name of the method to call
+    /// * `args` - The arguments as a JSON string
+    /// * `gas` - Amount of gas to attach to the call
+    /// # Returns
+    /// * Promise resolving to the function call result
+    /// # Panics
+    /// * If the caller is not a DAO
+    #[pause]
+    #[access_control_any(roles(Role::DAO))]
+    pub fn execute(&self, contract_address: AccountId, method_name: String, args: String, gas: u64) -> Promise {
+        Promise::new(contract_address)
+            .function_call(
+                method_name,
+                args.as_bytes().to_vec(),
+                NearToken::from_yoctonear(0),  // No attached deposit
+                Gas::from_tgas(gas),
+            )
+    }
+
+    /// Processes verification results
+    /// # Arguments
+    /// * `call_result` - Result containing:
+    ///   * Boolean for verification success
+    ///   * String for user identifier
+    /// # Returns
+    /// * Tuple containing:
+    ///   * Boolean for overall success
+    ///   * String for user identifier
+    #[private]
+    pub fn on_verify_callback(&mut self, #[callback_result] call_result: Result<(bool, String), PromiseError>) -> (bool, String) {
+        if call_result.is_err() {
+            env::log_str("Guard verification failed");
+            return (false, String::new());
+        }
+        let (verification_result, user) = call_result.unwrap();
+        if !verification_result {
+            env::log_str("Guard verification rejected");
+            return (false, user);
+        }
+        (verification_result, user)
+    }
+
+    // Signing methods
+    
+    /// Initiates signing with ECDSA algorithm by verifying JWT then signing payload
+    /// # Arguments
+    /// * `guard_id` - The guard ID for JWT verification
+    /// * `verify_payload` - The JWT to verify
+    /// * `sign_payload` - The data to sign
+    /// * `algorithm` - The signature algorithm to use ("secp256k1", "ecdsa", or "eddsa")
+    /// * `hashing` - How the payload is hashed before signing ("sha256", "keccak256", "prehashed" or "raw"), sha256 by default
+    /// * `second_factor_signature` - Signature of the payload by the second factor of the user, to spend over its limits
+    /// # Returns
+    /// * Promise chain for verification then signing, resolving to a `SignOutcome`
+    /// # Notes
+    /// * Requires an attached deposit for MPC costs
+    #[payable]
+    #[pause]
+    pub fn sign(&mut self, guard_id: String, verify_payload: String, sign_payload: Vec<u8>, algorithm: String, hashing: Option<String>, second_factor_signature: Option<Vec<u8>>) -> Promise {
+        let attached_deposit = env::attached_deposit();
+        let caller = env::predecessor_account_id();
+
+        // Validate algorithm
+        let signature_algorithm = match SignatureAlgorithm::parse_str(&algorithm) {
             Ok(alg) => alg,
             Err(err) => env::panic_str(&err),
         };
+        let hashing_mode = self.hashing_mode(hashing, &signature_algorithm, std::slice::from_ref(&sign_payload));
 
         let guard_prefix = self.get_guard_prefix(guard_id.clone());
         let guard_address = match self.guards.get(&guard_prefix) {
-            Some(address) => address.clone(),
+            Some(guard_info) => guard_info.account_id.clone(),
             None => {
                 env::panic_str(&format!("Cannot verify: Guard with ID '{}' does not exist", guard_id));
             }
         };
+        self.assert_guard_not_frozen(&guard_prefix);
+        self.assert_guard_supports(&guard_prefix, &signature_algorithm);
+        self.assert_guard_policy_allows(&guard_prefix, std::slice::from_ref(&sign_payload));
+        let sign_cost = self.sign_cost(&signature_algorithm);
+        Self::assert_deposit_covers(attached_deposit, &sign_cost, 1);
+
+        FastAuthEvent::SignRequested {
+            guard_id: &guard_id,
+            caller: &caller,
+            algorithm: &signature_algorithm,
+            payloads: 1,
+            deposit: attached_deposit,
+        }.emit();
 
         external_guard::ext(guard_address.clone())
         .verify(guard_id.clone(), verify_payload, sign_payload.clone(), env::predecessor_account_id())
         .then(Self::ext(env::current_account_id())
-            .on_verify_sign_callback(guard_id.clone(), sign_payload, attached_deposit, signature_algorithm, caller)
+            .on_verify_sign_callback(guard_id.clone(), sign_payload, attached_deposit, sign_cost, signature_algorithm, hashing_mode, caller, second_factor_signature)
+        )
+    }
+
+    /// Parses and validates the hashing mode of a signing request
+    /// # Arguments
+    /// * `hashing` - The requested hashing mode, sha256 if none
+    /// * `algorithm` - The signature algorithm of the request
+    /// * `sign_payloads` - The payloads to sign
+    /// # Returns
+    /// * The hashing mode
+    /// # Panics
+    /// * If the mode is not supported, or not valid for the algorithm or one of the payloads
+    fn hashing_mode(&self, hashing: Option<String>, algorithm: &SignatureAlgorithm, sign_payloads: &[Vec<u8>]) -> HashingMode {
+        let hashing_mode = match hashing {
+            Some(hashing) => match HashingMode::parse_str(&hashing) {
+                Ok(mode) => mode,
+                Err(err) => env::panic_str(&err),
+            },
+            None => HashingMode::Sha256,
+        };
+        for sign_payload in sign_payloads {
+            if let Err(err) = hashing_mode.validate(algorithm, sign_payload) {
+                env::panic_str(&err);
+            }
+        }
+        hashing_mode
+    }
+
+    // Rate limiting methods
+
+    /// Sets the rate limits of signature requests
+    /// # Arguments
+    /// * `rate_limits` - The limits per user and per guard, none for unlimited
+    /// # Panics
+    /// * If the caller is not a GuardManager or DAO
+    /// * If a limit allows no request, more than 100 requests or has an empty window
+    #[pause]
+    #[access_control_any(roles(Role::GuardManager, Role::DAO))]
+    pub fn set_rate_limits(&mut self, rate_limits: RateLimits) {
+        for limit in [&rate_limits.subject, &rate_limits.guard].into_iter().flatten() {
+            if let Err(err) = limit.validate() {
+                env::panic_str(&err);
+            }
+        }
+        FastAuthEvent::RateLimitsChanged { rate_limits: &rate_limits }.emit();
+        self.rate_limits = rate_limits;
+    }
+
+    /// Gets the rate limits of signature requests
+    #[pause]
+    pub fn rate_limits(&self) -> RateLimits {
+        self.rate_limits.clone()
+    }
+
+    /// Gets the number of payloads a user can still request to sign
+    /// # Arguments
+    /// * `guard_id` - The guard ID the user signs through
+    /// * `sub` - The user identifier
+    /// # Returns
+    /// * The remaining requests under the tightest of the user and guard limits, none if unlimited
+    #[pause]
+    pub fn get_remaining_requests(&self, guard_id: String, sub: String) -> Option<u32> {
+        let now = env::block_timestamp_ms();
+        self.limited_windows(&guard_id, &sub)
+            .iter()
+            .map(|(key, limit)| self.request_window(key).remaining(limit, now))
+            .min()
+    }
+
+    /// Checks the requests of a verified user against the rate limits
+    /// # Arguments
+    /// * `guard_id` - The guard ID used for JWT verification
+    /// * `user` - The verified user identifier
+    /// * `requests` - The number of payloads to sign
+    /// # Returns
+    /// * The request windows to count the requests in, or the failed outcome of the request if a limit would be exceeded
+    fn check_rate_limits(&self, guard_id: &str, user: &str, requests: u32) -> Result<Vec<(String, RateLimit)>, SignOutcome> {
+        let now = env::block_timestamp_ms();
+        let limited_windows = self.limited_windows(guard_id, user);
+        if limited_windows.iter().any(|(key, limit)| self.request_window(key).remaining(limit, now) < requests) {
+            return Err(SignOutcome::failed(SignErrorCode::RateLimited, String::new()));
+        }
+        Ok(limited_windows)
+    }
+
+    /// Counts the requests of a verified user against its rate limits and what its payloads spend against its spending limits
+    ///
+    /// Both limits are checked before either is counted, so a request rejected by one does not use up the other
+    /// # Arguments
+    /// * `guard_id` - The guard ID used for JWT verification
+    /// * `user` - The verified user identifier
+    /// * `sign_payloads` - The payloads to sign
+    /// * `hashing` - How the payloads are hashed before signing
+    /// * `commitment` - The commitment of the request, which the second factor signs
+    /// * `second_factor_signature` - Signature of the commitment by the second factor of the user
+    /// # Returns
+    /// * The user and the spend recorded for each payload, or the failed outcome of the request if a limit would be exceeded
+    fn consume_sign_limits(&mut self, guard_id: &str, user: String, sign_payloads: &[Vec<u8>], hashing: HashingMode, commitment: &[u8], second_factor_signature: Option<&[u8]>) -> Result<(String, Vec<Option<RecordedSpend>>), SignOutcome> {
+        let requests = sign_payloads.len() as u32;
+        let limited_windows = self.check_rate_limits(guard_id, &user, requests)?;
+        let spends = self.check_spending(guard_id, &user, sign_payloads, hashing, commitment, second_factor_signature)?;
+
+        let now = env::block_timestamp_ms();
+        for (key, limit) in limited_windows {
+            let mut window = self.request_window(&key);
+            window.record(&limit, now, requests);
+            self.request_windows.insert(key, window);
+        }
+        let recorded_spends = spends
+            .into_iter()
+            .map(|spend| spend.map(|(key, amount)| {
+                let mut window = self.spending_window(&key);
+                window.record(now, amount);
+                self.spending_windows.insert(key.clone(), window);
+                RecordedSpend { key, amount: U128(amount), spent_at: now }
+            }))
+            .collect();
+        Ok((user, recorded_spends))
+    }
+
+    /// Gets the request windows a user is limited by, keyed by `guard_id#sub` for the user and by guard prefix for the guard
+    /// # Arguments
+    /// * `guard_id` - The guard ID used for JWT verification
+    /// * `sub` - The user identifier
+    fn limited_windows(&self, guard_id: &str, sub: &str) -> Vec<(String, RateLimit)> {
+        let mut windows = Vec::new();
+        if let Some(limit) = &self.rate_limits.subject {
+            windows.push((self.derivation_path(guard_id, sub), limit.clone()));
+        }
+        if let Some(limit) = &self.rate_limits.guard {
+            windows.push((self.get_guard_prefix(guard_id.to_string()), limit.clone()));
+        }
+        windows
+    }
+
+    /// Gets the request window stored under a key
+    fn request_window(&self, key: &str) -> RequestWindow {
+        self.request_windows.get(key).cloned().unwrap_or_default()
+    }
+
+    // Spending limit methods
+
+    /// Schedules setting the spending limits of the users of a guard
+    /// # Arguments
+    /// * `guard_id` - The ID of the guard, without suffix
+    /// * `limits` - What each user can spend within 24 hours, none for unlimited
+    /// # Returns
+    /// * The ID of the scheduled operation, none if it took effect immediately
+    /// # Panics
+    /// * If the caller is not a GuardManager or DAO
+    /// * If the guard does not exist
+    #[pause]
+    #[access_control_any(roles(Role::GuardManager, Role::DAO))]
+    pub fn set_guard_spending_limits(&mut self, guard_id: String, limits: Option<SpendingLimits>) -> Option<u64> {
+        if !self.guards.contains_key(&guard_id) {
+            env::panic_str(&format!("Guard with ID '{}' does not exist", guard_id));
+        }
+        self.schedule_operation(AdminOperation::SetGuardSpendingLimits { guard_id, limits })
+    }
+
+    /// Gets the spending limits of the users of a guard
+    /// # Arguments
+    /// * `guard_id` - The ID of the guard, with or without suffix
+    #[pause]
+    pub fn get_guard_spending_limits(&self, guard_id: String) -> Option<SpendingLimits> {
+        self.guard_spending_limits.get(&self.get_guard_prefix(guard_id)).cloned()
+    }
+
+    /// Gets the spending limits a user signs under
+    /// # Arguments
+    /// * `guard_id` - The guard ID the user signs through
+    /// * `sub` - The user identifier
+    /// # Returns
+    /// * The tightest of the guard limits and the limits chosen by the user, per asset
+    #[pause]
+    pub fn get_spending_limits(&self, guard_id: String, sub: String) -> SpendingLimits {
+        self.spending_limits(&guard_id, &self.derivation_path(&guard_id, &sub))
+    }
+
+    /// Gets what a user spent within the last 24 hours
+    /// # Arguments
+    /// * `guard_id` - The guard ID the user signs through
+    /// * `sub` - The user identifier
+    /// * `asset` - The asset ("near" or "evm:<chain_id>")
+    /// # Returns
+    /// * The amount spent by the derivation path of the user, in yoctoNEAR or wei
+    /// # Panics
+    /// * If the asset is not supported
+    #[pause]
+    pub fn get_spent(&self, guard_id: String, sub: String, asset: String) -> U128 {
+        let asset = match Asset::parse_str(&asset) {
+            Ok(asset) => asset,
+            Err(err) => env::panic_str(&err),
+        };
+        let key = Self::spending_key(&self.derivation_path(&guard_id, &sub), &asset);
+        U128(self.spending_window(&key).spent(env::block_timestamp_ms()))
+    }
+
+    /// Gets the second factor key of a user
+    /// # Arguments
+    /// * `guard_id` - The guard ID the user signs through
+    /// * `sub` - The user identifier
+    /// # Returns
+    /// * The ed25519 key registered for the derivation path of the user and when it becomes active, if any
+    #[pause]
+    pub fn get_second_factor(&self, guard_id: String, sub: String) -> Option<SecondFactor> {
+        self.second_factors.get(&self.derivation_path(&guard_id, &sub)).cloned()
+    }
+
+    /// Computes the commitment the JWT of a user must carry in its `fatxn` claim to update its spending controls
+    /// # Arguments
+    /// * `identity` - The identity of the user, `guard_id#sub`
+    /// * `update` - The update to authorize
+    pub fn spending_update_commitment(&self, identity: String, update: SpendingUpdate) -> Vec<u8> {
+        update.commitment(&identity)
+    }
+
+    /// Updates the spending limits or the second factor of a user
+    /// # Arguments
+    /// * `guard_id` - The guard ID the user signs through
+    /// * `sub` - The user identifier
+    /// * `verify_payload` - A JWT of the user, whose `fatxn` claim must be the spending update commitment
+    /// * `update` - The limits or second factor to set
+    /// * `second_factor_signature` - Signature of the commitment by the active second factor,
+    ///   required to loosen or remove the limits, or to change the second factor, once one is active
+    /// # Returns
+    /// * Promise for the verification of the JWT, resolving to whether the update was applied
+    /// # Panics
+    /// * If the guard does not exist or is frozen, or the sub is invalid
+    /// * If the second factor is not an ed25519 key
+    #[pause]
+    pub fn update_spending_controls(&self, guard_id: String, sub: String, verify_payload: String, update: SpendingUpdate, second_factor_signature: Option<Vec<u8>>) -> Promise {
+        let guard_address = self.verifying_guard(&guard_id);
+        assert!(self.verify_sub(sub.clone()), "Invalid sub");
+        if let SpendingUpdate::SecondFactor { public_key: Some(public_key) } = &update {
+            if let Err(err) = validate_second_factor(public_key) {
+                env::panic_str(&err);
+            }
+        }
+        let commitment = update.commitment(&format_identity(&guard_id, &sub));
+
+        external_guard::ext(guard_address)
+            .verify(guard_id.clone(), verify_payload, commitment, env::predecessor_account_id())
+            .then(Self::ext(env::current_account_id())
+                .on_update_spending_controls_callback(guard_id, sub, update, second_factor_signature)
         )
     }
 
+    /// Applies a spending update once the JWT is verified
+    /// # Arguments
+    /// * `guard_id` - The guard ID the user signs through
+    /// * `sub` - The user identifier
+    /// * `update` - The limits or second factor to set
+    /// * `second_factor_signature` - Signature of the commitment by the registered second factor
+    /// * `call_result` - Verification result of the user
+    /// # Returns
+    /// * Whether the update was applied
+    #[private]
+    pub fn on_update_spending_controls_callback(&mut self, guard_id: String, sub: String, update: SpendingUpdate, second_factor_signature: Option<Vec<u8>>, #[callback_result] call_result: Result<(bool, String, String), PromiseError>) -> bool {
+        if !self.verified_identity(&guard_id, &sub, call_result) {
+            return false;
+        }
+        let path = self.derivation_path(&guard_id, &sub);
+        let requires_second_factor = match &update {
+            SpendingUpdate::Limits { limits } => match (limits, self.user_spending_limits.get(&path)) {
+                (_, None) => false,
+                (None, Some(_)) => true,
+                (Some(limits), Some(current)) => limits.is_looser_than(current),
+            },
+            SpendingUpdate::SecondFactor { .. } => true,
+        };
+        let now = env::block_timestamp_ms();
+        if requires_second_factor && self.second_factors.get(&path).is_some_and(|second_factor| second_factor.is_active(now)) {
+            let commitment = update.commitment(&format_identity(&guard_id, &sub));
+            if !self.second_factor_verified(&path, &commitment, second_factor_signature.as_deref()) {
+                env::log_str("Invalid second factor signature");
+                return false;
+            }
+        }
+
+        match update {
+            SpendingUpdate::Limits { limits } => {
+                FastAuthEvent::SpendingLimitsChanged { path: &path, limits: limits.as_ref() }.emit();
+                match limits {
+                    Some(limits) => self.user_spending_limits.insert(path, limits),
+                    None => self.user_spending_limits.remove(&path),
+                };
+            }
+            SpendingUpdate::SecondFactor { public_key } => {
+                let second_factor = public_key.map(SecondFactor::new);
+                FastAuthEvent::SecondFactorChanged { path: &path, second_factor: second_factor.as_ref() }.emit();
+                match second_factor {
+                    Some(second_factor) => self.second_factors.insert(path, second_factor),
+                    None => self.second_factors.remove(&path),
+                };
+            }
+        }
+        true
+    }
+
+    /// Checks what the payloads of a verified user spend against its spending limits
+    /// # Arguments
+    /// * `guard_id` - The guard ID used for JWT verification
+    /// * `user` - The verified user identifier
+    /// * `sign_payloads` - The payloads to sign
+    /// * `hashing` - How the payloads are hashed before signing
+    /// * `commitment` - The commitment of the request, which the second factor signs
+    /// * `second_factor_signature` - Signature of the commitment by the second factor of the user
+    /// # Returns
+    /// * The spending window and amount to count for each payload spending a limited asset, or the failed outcome
+    ///   of the request if a limit would be exceeded without a valid second factor signature
+    fn check_spending(&self, guard_id: &str, user: &str, sign_payloads: &[Vec<u8>], hashing: HashingMode, commitment: &[u8], second_factor_signature: Option<&[u8]>) -> Result<Vec<Option<(String, u128)>>, SignOutcome> {
+        let path = self.derivation_path(guard_id, user);
+        let limits = self.spending_limits(guard_id, &path);
+
+        let mut spends: Vec<Option<(String, u128)>> = Vec::with_capacity(sign_payloads.len());
+        let mut limited_spends: Vec<(String, u128, u128)> = Vec::new();
+        let mut unvalued = false;
+        for sign_payload in sign_payloads {
+            let Some((asset, amount)) = payload_spend(sign_payload, hashing) else {
+                unvalued = true;
+                spends.push(None);
+                continue;
+            };
+            let Some(limit) = limits.limit(&asset) else {
+                spends.push(None);
+                continue;
+            };
+            let key = Self::spending_key(&path, &asset);
+            match limited_spends.iter_mut().find(|(spent_key, _, _)| *spent_key == key) {
+                Some((_, total, _)) => *total = total.saturating_add(amount),
+                None => limited_spends.push((key.clone(), amount, limit)),
+            }
+            spends.push(Some((key, amount)));
+        }
+
+        let now = env::block_timestamp_ms();
+        // Payloads that cannot be valued could spend anything, so they exceed any limit
+        let exceeded = (unvalued && limits.is_limited())
+            || limited_spends
+                .iter()
+                .any(|(key, amount, limit)| self.spending_window(key).spent(now).saturating_add(*amount) > *limit);
+        if exceeded && !self.second_factor_verified(&path, commitment, second_factor_signature) {
+            return Err(SignOutcome::failed(SignErrorCode::SpendingLimitExceeded, String::new()));
+        }
+        Ok(spends)
+    }
+
+    /// Releases a spend of a payload MPC failed to sign
+    /// # Arguments
+    /// * `spend` - The spend recorded for the payload
+    fn release_spending(&mut self, spend: &RecordedSpend) {
+        let mut window = self.spending_window(&spend.key);
+        window.release(spend.spent_at, spend.amount.0);
+        self.spending_windows.insert(spend.key.clone(), window);
+    }
+
+    /// Gets the tightest of the guard limits and the limits chosen by the user
+    /// # Arguments
+    /// * `guard_id` - The guard ID used for JWT verification
+    /// * `path` - The derivation path of the user
+    fn spending_limits(&self, guard_id: &str, path: &str) -> SpendingLimits {
+        let guard_limits = self.guard_spending_limits.get(&self.get_guard_prefix(guard_id.to_string()));
+        let user_limits = self.user_spending_limits.get(path);
+        match (guard_limits, user_limits) {
+            (Some(guard_limits), Some(user_limits)) => guard_limits.tightest(user_limits),
+            (limits, other) => limits.or(other).cloned().unwrap_or_default(),
+        }
+    }
+
+    /// Checks a signature of the active second factor of a derivation path
+    /// # Arguments
+    /// * `path` - The derivation path of the user
+    /// * `message` - The signed message
+    /// * `signature` - The signature, if presented
+    fn second_factor_verified(&self, path: &str, message: &[u8], signature: Option<&[u8]>) -> bool {
+        let now = env::block_timestamp_ms();
+        match (self.second_factors.get(path), signature) {
+            (Some(second_factor), Some(signature)) if second_factor.is_active(now) => {
+                verify_second_factor(&second_factor.public_key, message, signature)
+            }
+            _ => false,
+        }
+    }
+
+    /// Formats the key of the spending window of a derivation path, `path@asset`
+    fn spending_key(path: &str, asset: &Asset) -> String {
+        format!("{}@{}", path, asset.key())
+    }
+
+    /// Gets the spending window stored under a key
+    fn spending_window(&self, key: &str) -> SpendingWindow {
+        self.spending_windows.get(key).cloned().unwrap_or_default()
+    }
+
     /// Verifies if a sub is valid
     /// # Arguments
     /// * `sub` - The sub to verify
@@ -460,31 +1544,23 @@ impl FastAuth {
         !sub.contains('#') && sub.len() <= 256
     }
 
-    /// Initiates signing with legacy algorithm by verifying JWT then signing payload
+    /// Creates an MPC signing request with legacy algorithm
     /// # Arguments
-    /// * `guard_id` - The guard ID for JWT verification
-    /// * `verify_payload` - The JWT to verify
-    /// * `sign_payload` - The data to sign
-    /// * `caller` - The original caller to refund deposit to
+    /// * `path` - The derivation path of the verified user
+    /// * `payload` - The message to sign, already hashed
+    /// * `attached_deposit` - Deposit to forward to MPC
     /// # Returns
-    /// * Promise chain for verification then signing
-    /// # Notes
-    /// * Requires an attached deposit for MPC costs
-    fn sign_request_secp256k1(&self, guard_id: String, user: String, sign_payload: Vec<u8>, attached_deposit: NearToken, caller: AccountId) -> Promise {
-        let payload_hash = env::sha256(&sign_payload);
-
+    /// * Promise for the MPC signing
+    fn sign_request_secp256k1(&self, path: String, payload: Vec<u8>, attached_deposit: NearToken) -> Promise {
         let request: SignRequest = SignRequest {
-            payload: payload_hash,
-            path: format!("{}#{}", guard_id.clone(), user),
+            payload,
+            path,
             key_version: self.mpc_key_version,
         };
 
         mpc_contract_legacy::ext(self.mpc_address.clone())
             .with_attached_deposit(attached_deposit)
             .sign(request)
-            .then(Self::ext(env::current_account_id())
-                .on_sign_callback(caller, attached_deposit)
-        )
     }
 
     /// Converts bytes to hex string
@@ -492,124 +1568,488 @@ impl FastAuth {
         bytes.iter().map(|b| format!("{:02x}", b)).collect()
     }
 
-    /// Initiates signing with ECDSA algorithm by verifying JWT then signing payload
+    /// Creates an MPC signing request with ECDSA algorithm
     /// # Arguments
-    /// * `guard_id` - The guard ID for JWT verification
-    /// * `verify_payload` - The JWT to verify
-    /// * `sign_payload` - The data to sign
-    /// * `caller` - The original caller to refund deposit to
+    /// * `path` - The derivation path of the verified user
+    /// * `payload` - The message to sign, already hashed
+    /// * `attached_deposit` - Deposit to forward to MPC
     /// # Returns
-    /// * Promise chain for verification then signing
-    /// # Notes
-    /// * Requires an attached deposit for MPC costs
-    fn sign_request_ecdsa(&self, guard_id: String, user: String, sign_payload: Vec<u8>, attached_deposit: NearToken, caller: AccountId) -> Promise {
-        let payload_hash = env::sha256(&sign_payload);
-        let hex_payload = self.bytes_to_hex(&payload_hash);
+    /// * Promise for the MPC signing
+    fn sign_request_ecdsa(&self, path: String, payload: Vec<u8>, attached_deposit: NearToken) -> Promise {
+        let hex_payload = self.bytes_to_hex(&payload);
 
         let request: SignRequestV2 = SignRequestV2 {
             payload_v2: PayloadType::Ecdsa(hex_payload),
-            path: format!("{}#{}", guard_id.clone(), user),
+            path,
             domain_id: self.mpc_domain_id,
         };
 
         mpc_contract::ext(self.mpc_address.clone())
             .with_attached_deposit(attached_deposit)
             .sign(request)
-            .then(Self::ext(env::current_account_id())
-                .on_sign_callback(caller, attached_deposit)
-        )
     }
 
-    /// Initiates signing by verifying JWT then signing payload
+    /// Creates an MPC signing request with EdDSA algorithm
     /// # Arguments
-    /// * `guard_id` - The guard ID for JWT verification
-    /// * `verify_payload` - The JWT to verify
-    /// * `sign_payload` - The data to sign
-    /// * `caller` - The original caller to refund deposit to
+    /// * `path` - The derivation path of the verified user
+    /// * `payload` - The message to sign, already hashed
+    /// * `attached_deposit` - Deposit to forward to MPC
     /// # Returns
-    /// * Promise chain for verification then signing
-    /// # Notes
-    /// * Requires an attached deposit for MPC costs
-    fn sign_request_eddsa(&self, guard_id: String, user: String, sign_payload: Vec<u8>, attached_deposit: NearToken, caller: AccountId) -> Promise {
-        let payload_hash = env::sha256(&sign_payload);
-        let hex_payload = self.bytes_to_hex(&payload_hash);
+    /// * Promise for the MPC signing
+    fn sign_request_eddsa(&self, path: String, payload: Vec<u8>, attached_deposit: NearToken) -> Promise {
+        let hex_payload = self.bytes_to_hex(&payload);
 
         let request: SignRequestV2 = SignRequestV2 {
             payload_v2: PayloadType::Eddsa(hex_payload),
-            path: format!("{}#{}", guard_id.clone(), user),
+            path,
             domain_id: self.mpc_domain_id,
         };
 
         mpc_contract::ext(self.mpc_address.clone())
             .with_attached_deposit(attached_deposit)
             .sign(request)
-            .then(Self::ext(env::current_account_id())
-                .on_sign_callback(caller, attached_deposit)
-        )
     }
 
     /// Processes verification and initiates MPC signing
     /// # Arguments
     /// * `sign_payload` - The data to sign
-    /// * `attached_deposit` - Deposit to forward to MPC
+    /// * `attached_deposit` - Deposit attached to the request
+    /// * `sign_cost` - MPC deposit and fee of the request
     /// * `algorithm` - The signature algorithm to use
+    /// * `hashing` - How the payload is hashed before signing
     /// * `caller` - The original caller to refund deposit to
+    /// * `second_factor_signature` - Signature of the payload by the second factor of the user
     /// * `call_result` - Verification result containing success and user ID
     /// # Returns
-    /// * Promise for MPC signing or empty promise if verification failed
+    /// * Promise for MPC signing, or the failed outcome if verification failed
     #[private]
-    pub fn on_verify_sign_callback(&mut self, guard_id: String, sign_payload: Vec<u8>, attached_deposit: NearToken, algorithm: SignatureAlgorithm, caller: AccountId, #[callback_result] call_result: Result<(bool, String, String), PromiseError>) -> Promise {
-        if call_result.is_err() {
-            env::log_str("Guard verification failed");
-            return Promise::new(caller)
-                .transfer(attached_deposit);
-        } 
-        let (verification_result, user, _guard) = call_result.unwrap();
+    pub fn on_verify_sign_callback(&mut self, guard_id: String, sign_payload: Vec<u8>, attached_deposit: NearToken, sign_cost: SignCost, algorithm: SignatureAlgorithm, hashing: HashingMode, caller: AccountId, second_factor_signature: Option<Vec<u8>>, #[callback_result] call_result: Result<(bool, String, String), PromiseError>) -> PromiseOrValue<SignOutcome> {
+        let sign_payloads = std::slice::from_ref(&sign_payload);
+        let (user, spend) = match self
+            .verified_user(&guard_id, call_result)
+            .and_then(|user| self.check_guard_quorum(&guard_id, user, sign_payloads, hashing))
+            .and_then(|user| self.consume_sign_limits(&guard_id, user, sign_payloads, hashing, &sign_payload, second_factor_signature.as_deref()))
+        {
+            Ok((user, mut spends)) => (user, spends.pop().flatten()),
+            Err(outcome) => {
+                Self::emit_sign_failed(&guard_id, &caller, None, &outcome);
+                let _ = Promise::new(caller).transfer(attached_deposit);
+                return PromiseOrValue::Value(outcome);
+            }
+        };
+
+        PromiseOrValue::Promise(
+            self.sign_request(guard_id.clone(), user, sign_payload, sign_cost.mpc_deposit, algorithm, hashing)
+                .then(Self::ext(env::current_account_id())
+                    .on_sign_callback(guard_id, caller, attached_deposit, sign_cost, spend)
+            )
+        )
+    }
+
+    /// Checks the guard verification result of a signing request
+    /// # Arguments
+    /// * `guard_id` - The guard ID used for JWT verification
+    /// * `call_result` - Verification result containing success, user ID or rejection reason, and guard name
+    /// # Returns
+    /// * The verified user identifier, or the failed outcome of the request
+    fn verified_user(&self, guard_id: &str, call_result: Result<(bool, String, String), PromiseError>) -> Result<String, SignOutcome> {
+        let (verification_result, user, _guard) = match call_result {
+            Ok(result) => result,
+            Err(_) => return Err(SignOutcome::failed(SignErrorCode::GuardCallFailed, String::new())),
+        };
         if !verification_result {
-            env::log_str("Guard verification rejected");
-            return Promise::new(caller)
-                .transfer(attached_deposit);
+            // Rejections carry the reason of the guard in place of the user
+            return Err(SignOutcome::failed(SignErrorCode::VerificationRejected, user));
+        }
+        if !self.verify_sub(user.clone()) {
+            return Err(SignOutcome::failed(SignErrorCode::InvalidSub, String::new()));
         }
+        if self.is_guard_frozen(self.get_guard_prefix(guard_id.to_string())) {
+            return Err(SignOutcome::failed(SignErrorCode::GuardFrozen, String::new()));
+        }
+        Ok(user)
+    }
 
-        assert!(self.verify_sub(user.clone()), "Invalid sub");
+    /// Logs a failed signing request as a `sign_failed` event
+    /// # Arguments
+    /// * `guard_id` - The guard ID used for JWT verification
+    /// * `caller` - The original caller of the request
+    /// * `index` - The payload position within a batch
+    /// * `outcome` - The failed outcome of the request
+    fn emit_sign_failed(guard_id: &str, caller: &AccountId, index: Option<u32>, outcome: &SignOutcome) {
+        if let SignOutcome::Failed { reason, .. } = outcome {
+            FastAuthEvent::SignFailed { guard_id, caller, index, reason: reason.as_str() }.emit();
+        }
+    }
 
+    /// Creates an MPC signing request for the given algorithm
+    /// # Arguments
+    /// * `guard_id` - The guard ID used for JWT verification
+    /// * `user` - The verified user identifier
+    /// * `sign_payload` - The data to sign
+    /// * `attached_deposit` - Deposit to forward to MPC
+    /// * `algorithm` - The signature algorithm to use
+    /// * `hashing` - How the payload is hashed before signing
+    /// # Returns
+    /// * Promise for the MPC signing
+    fn sign_request(&self, guard_id: String, user: String, sign_payload: Vec<u8>, attached_deposit: NearToken, algorithm: SignatureAlgorithm, hashing: HashingMode) -> Promise {
+        let payload = hashing.digest(&sign_payload);
+        let path = self.derivation_path(&guard_id, &user);
         match algorithm {
             SignatureAlgorithm::Secp256k1 => {
-                self.sign_request_secp256k1(guard_id, user, sign_payload, attached_deposit, caller)
+                self.sign_request_secp256k1(path, payload, attached_deposit)
             },
             SignatureAlgorithm::Ecdsa => {
-                self.sign_request_ecdsa(guard_id, user, sign_payload, attached_deposit, caller)
+                self.sign_request_ecdsa(path, payload, attached_deposit)
             },
             SignatureAlgorithm::Eddsa => {
-                self.sign_request_eddsa(guard_id, user, sign_payload, attached_deposit, caller)
+                self.sign_request_eddsa(path, payload, attached_deposit)
             }
         }
     }
 
-    /// Processes MPC signing result and returns deposit to caller
+    /// Processes MPC signing result, keeps the fee and refunds the rest of the deposit to caller
     /// # Arguments
+    /// * `guard_id` - The guard ID used for JWT verification
     /// * `caller` - The original caller to refund deposit to
-    /// * `original_deposit` - The original deposit amount to refund
+    /// * `attached_deposit` - The deposit attached to the request
+    /// * `sign_cost` - MPC deposit and fee of the request
+    /// * `spend` - The spend recorded for the payload, released if MPC signing failed
     /// * `call_result` - The signing result from MPC
     /// # Returns
-    /// * Option containing signature if successful
+    /// * The signature, or the failed outcome if MPC signing failed
     #[private]
     #[payable]
-    pub fn on_sign_callback(&mut self, caller: AccountId, original_deposit: NearToken, #[callback_result] call_result: Result<SignResponseAny, PromiseError>) -> Option<SignResponseAny> {
-        if call_result.is_err() {
-            env::log_str("MPC signing failed");
-            // Return the deposit to the caller
-            let _ = Promise::new(caller).transfer(original_deposit);
-            return None;
-        } 
+    pub fn on_sign_callback(&mut self, guard_id: String, caller: AccountId, attached_deposit: NearToken, sign_cost: SignCost, spend: Option<RecordedSpend>, #[callback_result] call_result: Result<SignResponseAny, PromiseError>) -> SignOutcome {
+        let (outcome, signed) = match call_result {
+            Ok(signature) => {
+                FastAuthEvent::SignCompleted { guard_id: &guard_id, caller: &caller, index: None }.emit();
+                (SignOutcome::Signed { signature }, 1)
+            }
+            Err(_) => {
+                if let Some(spend) = &spend {
+                    self.release_spending(spend);
+                }
+                let outcome = SignOutcome::failed(SignErrorCode::MpcFailed, String::new());
+                Self::emit_sign_failed(&guard_id, &caller, None, &outcome);
+                (outcome, 0)
+            }
+        };
 
-        let sign_response = call_result.unwrap();
-        env::log_str(&format!("MPC signing successful: {:?}", sign_response));
-        
-        // Return the refunded deposit to the original caller
-        let _ = Promise::new(caller).transfer(original_deposit);
-        
-        Some(sign_response)
+        self.settle_deposit(caller, attached_deposit, &sign_cost, signed);
+        outcome
+    }
+
+    /// Collects the fees of the signed payloads and refunds the rest of the deposit
+    /// # Arguments
+    /// * `caller` - The original caller to refund deposit to
+    /// * `attached_deposit` - The deposit attached to the request
+    /// * `sign_cost` - MPC deposit and fee of each payload
+    /// * `signed` - The number of payloads MPC signed
+    fn settle_deposit(&mut self, caller: AccountId, attached_deposit: NearToken, sign_cost: &SignCost, signed: u32) {
+        self.collected_fees = self.collected_fees.saturating_add(sign_cost.fee.saturating_mul(signed as u128));
+        let refund = sign_cost.refund(attached_deposit, signed);
+        if !refund.is_zero() {
+            let _ = Promise::new(caller).transfer(refund);
+        }
+    }
+
+    // Batch signing methods
+
+    /// Computes the commitment a JWT must carry in its `fatxn` claim to authorize a batch
+    /// # Arguments
+    /// * `sign_payloads` - The payloads of the batch, in signing order
+    /// # Returns
+    /// * The tagged SHA-256 Merkle root of the payloads, `sha256("fast-auth:batch" || root)`.
+    ///   Leaves are `sha256(0x00 || payload)`, parents `sha256(0x01 || left || right)` and odd
+    ///   nodes are carried up, so no batch shares its root with a batch of its inner nodes
+    /// # Panics
+    /// * If `sign_payloads` is empty
+    pub fn batch_commitment(&self, sign_payloads: Vec<Vec<u8>>) -> Vec<u8> {
+        assert!(!sign_payloads.is_empty(), "Batch cannot be empty");
+
+        let mut level: Vec<Vec<u8>> = sign_payloads
+            .iter()
+            .map(|payload| env::sha256([&[BATCH_LEAF_PREFIX], payload.as_slice()].concat()))
+            .collect();
+        while level.len() > 1 {
+            level = level
+                .chunks(2)
+                .map(|pair| match pair {
+                    [left, right] => {
+                        env::sha256([&[BATCH_NODE_PREFIX], left.as_slice(), right.as_slice()].concat())
+                    }
+                    [node] => node.clone(),
+                    _ => unreachable!(),
+                })
+                .collect();
+        }
+        env::sha256([BATCH_COMMITMENT_TAG, level[0].as_slice()].concat())
+    }
+
+    /// Initiates batch signing by verifying one JWT then signing every payload
+    /// # Arguments
+    /// * `guard_id` - The guard ID for JWT verification
+    /// * `verify_payload` - The JWT to verify, whose `fatxn` claim must be the batch commitment
+    /// * `sign_payloads` - The payloads to sign
+    /// * `algorithm` - The signature algorithm to use ("secp256k1", "ecdsa", or "eddsa")
+    /// * `hashing` - How every payload is hashed before signing ("sha256", "keccak256", "prehashed" or "raw"), sha256 by default
+    /// * `second_factor_signature` - Signature of the batch commitment by the second factor of the user, to spend over its limits
+    /// # Returns
+    /// * Promise chain for verification then signing, resolving to one `SignOutcome` per payload
+    /// # Notes
+    /// * Requires an attached deposit for MPC costs, which is split evenly between the payloads
+    #[payable]
+    #[pause]
+    pub fn sign_batch(&mut self, guard_id: String, verify_payload: String, sign_payloads: Vec<Vec<u8>>, algorithm: String, hashing: Option<String>, second_factor_signature: Option<Vec<u8>>) -> Promise {
+        let attached_deposit = env::attached_deposit();
+        let caller = env::predecessor_account_id();
+
+        assert!(!sign_payloads.is_empty(), "Batch cannot be empty");
+        assert!(sign_payloads.len() <= MAX_BATCH_SIZE, "Batch cannot exceed {} payloads", MAX_BATCH_SIZE);
+
+        let signature_algorithm = match SignatureAlgorithm::parse_str(&algorithm) {
+            Ok(alg) => alg,
+            Err(err) => env::panic_str(&err),
+        };
+        let hashing_mode = self.hashing_mode(hashing, &signature_algorithm, &sign_payloads);
+
+        let guard_prefix = self.get_guard_prefix(guard_id.clone());
+        let guard_address = match self.guards.get(&guard_prefix) {
+            Some(guard_info) => guard_info.account_id.clone(),
+            None => {
+                env::panic_str(&format!("Cannot verify: Guard with ID '{}' does not exist", guard_id));
+            }
+        };
+        self.assert_guard_not_frozen(&guard_prefix);
+        self.assert_guard_supports(&guard_prefix, &signature_algorithm);
+        self.assert_guard_policy_allows(&guard_prefix, &sign_payloads);
+        let sign_cost = self.sign_cost(&signature_algorithm);
+        Self::assert_deposit_covers(attached_deposit, &sign_cost, sign_payloads.len() as u32);
+
+        let commitment = self.batch_commitment(sign_payloads.clone());
+
+        FastAuthEvent::SignRequested {
+            guard_id: &guard_id,
+            caller: &caller,
+            algorithm: &signature_algorithm,
+            payloads: sign_payloads.len() as u32,
+            deposit: attached_deposit,
+        }.emit();
+
+        external_guard::ext(guard_address.clone())
+        .verify(guard_id.clone(), verify_payload, commitment.clone(), env::predecessor_account_id())
+        .then(Self::ext(env::current_account_id())
+            .on_verify_sign_batch_callback(guard_id.clone(), sign_payloads, commitment, attached_deposit, sign_cost, signature_algorithm, hashing_mode, caller, second_factor_signature)
+        )
+    }
+
+    /// Processes verification and initiates one MPC signing per payload
+    /// # Arguments
+    /// * `guard_id` - The guard ID used for JWT verification
+    /// * `sign_payloads` - The payloads to sign
+    /// * `commitment` - The batch commitment the JWT carries
+    /// * `attached_deposit` - Deposit attached to the request
+    /// * `sign_cost` - MPC deposit and fee of each payload
+    /// * `algorithm` - The signature algorithm to use
+    /// * `hashing` - How every payload is hashed before signing
+    /// * `caller` - The original caller to refund deposit to
+    /// * `second_factor_signature` - Signature of the batch commitment by the second factor of the user
+    /// * `call_result` - Verification result containing success and user ID
+    /// # Returns
+    /// * Promise for the joint MPC signings, or one failed outcome per payload if verification failed
+    #[private]
+    pub fn on_verify_sign_batch_callback(&mut self, guard_id: String, sign_payloads: Vec<Vec<u8>>, commitment: Vec<u8>, attached_deposit: NearToken, sign_cost: SignCost, algorithm: SignatureAlgorithm, hashing: HashingMode, caller: AccountId, second_factor_signature: Option<Vec<u8>>, #[callback_result] call_result: Result<(bool, String, String), PromiseError>) -> PromiseOrValue<Vec<SignOutcome>> {
+        let (user, spends) = match self
+            .verified_user(&guard_id, call_result)
+            .and_then(|user| self.check_guard_quorum(&guard_id, user, &sign_payloads, hashing))
+            .and_then(|user| self.consume_sign_limits(&guard_id, user, &sign_payloads, hashing, &commitment, second_factor_signature.as_deref()))
+        {
+            Ok(result) => result,
+            Err(outcome) => {
+                Self::emit_sign_failed(&guard_id, &caller, None, &outcome);
+                let _ = Promise::new(caller).transfer(attached_deposit);
+                return PromiseOrValue::Value(vec![outcome; sign_payloads.len()]);
+            }
+        };
+
+        let sign_requests = sign_payloads
+            .into_iter()
+            .map(|sign_payload| self.sign_request(guard_id.clone(), user.clone(), sign_payload, sign_cost.mpc_deposit, algorithm.clone(), hashing))
+            .reduce(|joint, request| joint.and(request))
+            .unwrap();
+
+        PromiseOrValue::Promise(
+            sign_requests.then(Self::ext(env::current_account_id())
+                .on_sign_batch_callback(guard_id, caller, attached_deposit, sign_cost, spends)
+            )
+        )
+    }
+
+    /// Processes the MPC signing results of a batch, keeps the fees and refunds the rest of the deposit to caller
+    /// # Arguments
+    /// * `guard_id` - The guard ID used for JWT verification
+    /// * `caller` - The original caller to refund deposit to
+    /// * `attached_deposit` - The deposit attached to the request
+    /// * `sign_cost` - MPC deposit and fee of each payload
+    /// * `spends` - The spend recorded for each payload, released for the payloads MPC failed to sign
+    /// # Returns
+    /// * One outcome per payload, containing the signature if successful
+    #[private]
+    #[payable]
+    pub fn on_sign_batch_callback(&mut self, guard_id: String, caller: AccountId, attached_deposit: NearToken, sign_cost: SignCost, spends: Vec<Option<RecordedSpend>>) -> Vec<SignOutcome> {
+        let results_count = env::promise_results_count();
+        let mut signed = 0;
+        let mut outcomes = Vec::with_capacity(results_count as usize);
+
+        for index in 0..results_count {
+            let sign_response = match env::promise_result(index) {
+                PromiseResult::Successful(value) => serde_json::from_slice::<SignResponseAny>(&value).ok(),
+                PromiseResult::Failed => None,
+            };
+            let outcome = match sign_response {
+                Some(signature) => {
+                    FastAuthEvent::SignCompleted { guard_id: &guard_id, caller: &caller, index: Some(index as u32) }.emit();
+                    signed += 1;
+                    SignOutcome::Signed { signature }
+                }
+                None => {
+                    if let Some(Some(spend)) = spends.get(index as usize) {
+                        self.release_spending(spend);
+                    }
+                    let outcome = SignOutcome::failed(SignErrorCode::MpcFailed, String::new());
+                    Self::emit_sign_failed(&guard_id, &caller, Some(index as u32), &outcome);
+                    outcome
+                }
+            };
+            outcomes.push(outcome);
+        }
+
+        self.settle_deposit(caller, attached_deposit, &sign_cost, signed);
+        outcomes
+    }
+
+    // Derivation methods
+
+    /// Gets the public key FastAuth signs with for a guard/sub pair
+    /// # Arguments
+    /// * `guard_id` - The guard ID used for JWT verification
+    /// * `sub` - The user identifier
+    /// * `algorithm` - The signature algorithm to use ("secp256k1", "ecdsa", or "eddsa")
+    /// # Returns
+    /// * Promise resolving to the public key derived by MPC for the derivation path of `guard_id#sub`
+    /// # Panics
+    /// * If the algorithm is not supported
+    /// * If the guard does not exist or the sub is invalid
+    #[pause]
+    pub fn derived_public_key(&self, guard_id: String, sub: String, algorithm: String) -> Promise {
+        let signature_algorithm = match SignatureAlgorithm::parse_str(&algorithm) {
+            Ok(alg) => alg,
+            Err(err) => env::panic_str(&err),
+        };
+        self.derived_public_key_request(guard_id, sub, signature_algorithm)
+    }
+
+    /// Gets the addresses controlled by the key FastAuth signs with for a guard/sub pair
+    /// # Arguments
+    /// * `guard_id` - The guard ID used for JWT verification
+    /// * `sub` - The user identifier
+    /// * `algorithm` - The signature algorithm to use ("secp256k1", "ecdsa", or "eddsa")
+    /// * `bitcoin_network` - The Bitcoin network ("mainnet" or "testnet"), defaults to mainnet
+    /// # Returns
+    /// * Promise resolving to the derived public key and the addresses supported by its curve
+    /// # Panics
+    /// * If the algorithm or network is not supported
+    /// * If the guard does not exist or the sub is invalid
+    #[pause]
+    pub fn derived_addresses(&self, guard_id: String, sub: String, algorithm: String, bitcoin_network: Option<String>) -> Promise {
+        let signature_algorithm = match SignatureAlgorithm::parse_str(&algorithm) {
+            Ok(alg) => alg,
+            Err(err) => env::panic_str(&err),
+        };
+        let network = match BitcoinNetwork::parse_str(bitcoin_network.as_deref().unwrap_or("mainnet")) {
+            Ok(network) => network,
+            Err(err) => env::panic_str(&err),
+        };
+
+        self.derived_public_key_request(guard_id, sub, signature_algorithm)
+            .then(Self::ext(env::current_account_id())
+                .on_derived_addresses_callback(network)
+        )
+    }
+
+    /// Formats the derived public key as addresses
+    /// # Arguments
+    /// * `bitcoin_network` - The Bitcoin network of the P2WPKH address
+    /// * `call_result` - The derived public key from MPC
+    /// # Returns
+    /// * The derived public key and the addresses supported by its curve
+    /// # Panics
+    /// * If the MPC derivation failed
+    #[private]
+    pub fn on_derived_addresses_callback(&self, bitcoin_network: BitcoinNetwork, #[callback_result] call_result: Result<PublicKey, PromiseError>) -> DerivedAddresses {
+        match call_result {
+            Ok(public_key) => DerivedAddresses::new(public_key, &bitcoin_network),
+            Err(_) => env::panic_str("MPC key derivation failed"),
+        }
+    }
+
+    /// Formats an ed25519 public key as a NEAR implicit account
+    /// # Arguments
+    /// * `public_key` - The ed25519 public key
+    /// # Panics
+    /// * If the key is not an ed25519 key
+    pub fn near_implicit_account(&self, public_key: PublicKey) -> AccountId {
+        derivation::near_implicit_account(&public_key).unwrap_or_else(|err| env::panic_str(&err))
+    }
+
+    /// Formats a secp256k1 public key as an EIP-55 checksummed EVM address
+    /// # Arguments
+    /// * `public_key` - The secp256k1 public key
+    /// # Panics
+    /// * If the key is not a secp256k1 key
+    pub fn evm_address(&self, public_key: PublicKey) -> String {
+        derivation::evm_address(&public_key).unwrap_or_else(|err| env::panic_str(&err))
+    }
+
+    /// Formats a secp256k1 public key as a Bitcoin P2WPKH address
+    /// # Arguments
+    /// * `public_key` - The secp256k1 public key
+    /// * `network` - The Bitcoin network ("mainnet" or "testnet")
+    /// # Panics
+    /// * If the key is not a secp256k1 key or the network is not supported
+    pub fn bitcoin_p2wpkh_address(&self, public_key: PublicKey, network: String) -> String {
+        let network = BitcoinNetwork::parse_str(&network).unwrap_or_else(|err| env::panic_str(&err));
+        derivation::bitcoin_p2wpkh_address(&public_key, &network).unwrap_or_else(|err| env::panic_str(&err))
+    }
+
+    /// Creates an MPC request for the public key of a guard/sub pair
+    /// # Arguments
+    /// * `guard_id` - The guard ID used for JWT verification
+    /// * `sub` - The user identifier
+    /// * `algorithm` - The signature algorithm to use
+    /// # Returns
+    /// * Promise for the MPC key derivation, with FastAuth as predecessor since it requests the signatures
+    fn derived_public_key_request(&self, guard_id: String, sub: String, algorithm: SignatureAlgorithm) -> Promise {
+        let guard_prefix = self.get_guard_prefix(guard_id.clone());
+        if !self.guards.contains_key(&guard_prefix) {
+            env::panic_str(&format!("Guard with ID '{}' does not exist", guard_id));
+        }
+        assert!(self.verify_sub(sub.clone()), "Invalid sub");
+
+        let path = self.derivation_path(&guard_id, &sub);
+        let predecessor = Some(env::current_account_id());
+
+        match algorithm {
+            SignatureAlgorithm::Secp256k1 => {
+                mpc_contract_legacy::ext(self.mpc_address.clone())
+                    .derived_public_key(path, predecessor)
+            },
+            SignatureAlgorithm::Ecdsa | SignatureAlgorithm::Eddsa => {
+                mpc_contract::ext(self.mpc_address.clone())
+                    .derived_public_key(path, predecessor, Some(self.mpc_domain_id))
+            }
+        }
     }
 
     // Version methods
@@ -617,8 +2057,8 @@ impl FastAuth {
     /// Gets the contract version
     /// # Returns
     /// * The current version string
+    #[pause]
     pub fn version(&self) -> String {
-        self.non_paused_only();
         self.version.clone()
     }
 }
@@ -630,31 +2070,62 @@ impl FastAuth {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use near_sdk::test_utils::{accounts, VMContextBuilder};
+    use near_sdk::testing_env;
+    use crate::external_contracts::EdDsaSignResponse;
+    use crate::identity::UNLINK_COOLDOWN_MS;
+    use crate::policy::ActionKind;
+    use crate::quorum::{Approver, QuorumUpdate, SignApproval};
+    use crate::session::{SessionRequest, SessionScope, MAX_SESSIONS_PER_IDENTITY};
+    use crate::spending::SECOND_FACTOR_ACTIVATION_MS;
+    use crate::transaction::{Action, TransactionV0, TxPublicKey};
+    use near_sdk::CurveType;
+
+    fn new_contract() -> FastAuth {
+        FastAuth { guards: IterableMap::new(Prefix::Guards), owner: env::current_account_id(), pending_owner: None, mpc_address: env::current_account_id(), mpc_key_version: DEFAULT_MPC_KEY_VERSION, mpc_domain_id: DEFAULT_DOMAIN_ID, version: CONTRACT_VERSION.to_string(), timelock_delay_ms: 0, scheduled_operations: IterableMap::new(Prefix::ScheduledOperations), next_operation_id: 0, rate_limits: RateLimits::default(), request_windows: LookupMap::new(Prefix::RequestWindows), mpc_deposit: DEFAULT_MPC_DEPOSIT, sign_fees: SignFees::default(), collected_fees: NearToken::from_yoctonear(0), treasury: None, identity_links: LookupMap::new(Prefix::IdentityLinks), linked_identities: LookupMap::new(Prefix::LinkedIdentities), guard_policies: LookupMap::new(Prefix::GuardPolicies), guard_spending_limits: LookupMap::new(Prefix::GuardSpendingLimits), user_spending_limits: LookupMap::new(Prefix::UserSpendingLimits), spending_windows: LookupMap::new(Prefix::SpendingWindows), second_factors: LookupMap::new(Prefix::SecondFactors), sessions: LookupMap::new(Prefix::Sessions), identity_sessions: LookupMap::new(Prefix::IdentitySessions), guard_quorums: LookupMap::new(Prefix::GuardQuorums), pending_approvals: LookupMap::new(Prefix::PendingApprovals) }
+    }
+
+    fn set_predecessor(predecessor: AccountId) {
+        let mut context = VMContextBuilder::new();
+        context
+            .current_account_id(accounts(0))
+            .predecessor_account_id(predecessor);
+        testing_env!(context.build());
+    }
+
+    fn setup_contract() -> (FastAuth, AccountId, AccountId) {
+        let owner = accounts(1);
+        let pauser = accounts(2);
+        set_predecessor(owner.clone());
+        let contract = FastAuth::init(HashMap::new(), owner.clone(), pauser.clone());
+        (contract, owner, pauser)
+    }
 
     #[test]
     fn get_existing_guard() {
         let addr: AccountId = "jwt.fast-auth.near".parse().unwrap();
-        let contract = FastAuth { guards: HashMap::from([("jwt".to_string(), addr.clone())]), owner: env::current_account_id(), mpc_address: env::current_account_id(), mpc_key_version: DEFAULT_MPC_KEY_VERSION, mpc_domain_id: DEFAULT_DOMAIN_ID, version: CONTRACT_VERSION.to_string(), paused: false, pauser: env::current_account_id() };
+        let mut contract = new_contract();
+        contract.guards.insert("jwt".to_string(), GuardInfo::new(addr.clone(), None, None));
         assert_eq!(contract.get_guard("jwt".to_string()), addr);
     }
 
     #[test]
     #[should_panic]
     fn get_non_existing_guard() {
-        let contract = FastAuth { guards: HashMap::new(), owner: env::current_account_id(), mpc_address: env::current_account_id(), mpc_key_version: DEFAULT_MPC_KEY_VERSION, mpc_domain_id: DEFAULT_DOMAIN_ID, version: CONTRACT_VERSION.to_string(), paused: false, pauser: env::current_account_id() };
+        let contract = new_contract();
         contract.get_guard("jwt".to_string());
     }
 
     #[test]
     #[should_panic]
     fn get_guard_prefix_with_empty_guard_id() {
-        let contract = FastAuth { guards: HashMap::new(), owner: env::current_account_id(), mpc_address: env::current_account_id(), mpc_key_version: DEFAULT_MPC_KEY_VERSION, mpc_domain_id: DEFAULT_DOMAIN_ID, version: CONTRACT_VERSION.to_string(), paused: false, pauser: env::current_account_id() };
+        let contract = new_contract();
         contract.get_guard_prefix("".to_string());
     }
 
     #[test]
     fn get_guard_prefix_without_prefix() {
-        let contract = FastAuth { guards: HashMap::new(), owner: env::current_account_id(), mpc_address: env::current_account_id(), mpc_key_version: DEFAULT_MPC_KEY_VERSION, mpc_domain_id: DEFAULT_DOMAIN_ID, version: CONTRACT_VERSION.to_string(), paused: false, pauser: env::current_account_id() };
+        let contract = new_contract();
         assert_eq!(contract.get_guard_prefix("jwt".to_string()), "jwt");
 
         assert_eq!(contract.get_guard_prefix("jwt#".to_string()), "jwt");
@@ -662,114 +2133,339 @@ mod tests {
 
     #[test]
     fn get_guard_prefix_with_prefix_and_single_suffix() {
-        let contract = FastAuth { guards: HashMap::new(), owner: env::current_account_id(), mpc_address: env::current_account_id(), mpc_key_version: DEFAULT_MPC_KEY_VERSION, mpc_domain_id: DEFAULT_DOMAIN_ID, version: CONTRACT_VERSION.to_string(), paused: false, pauser: env::current_account_id() };
+        let contract = new_contract();
         assert_eq!(contract.get_guard_prefix("jwt#sub".to_string()), "jwt");
     }
 
     #[test]
     fn get_guard_prefix_with_prefix_and_multiple_suffixes() {
-        let contract = FastAuth { guards: HashMap::new(), owner: env::current_account_id(), mpc_address: env::current_account_id(), mpc_key_version: DEFAULT_MPC_KEY_VERSION, mpc_domain_id: DEFAULT_DOMAIN_ID, version: CONTRACT_VERSION.to_string(), paused: false, pauser: env::current_account_id() };
+        let contract = new_contract();
         assert_eq!(contract.get_guard_prefix("jwt#sub#suffix".to_string()), "jwt");
     }
 
     #[test]
     fn owner() {
-        let contract = FastAuth { guards: HashMap::new(), owner: env::current_account_id(), mpc_address: env::current_account_id(), mpc_key_version: DEFAULT_MPC_KEY_VERSION, mpc_domain_id: DEFAULT_DOMAIN_ID, version: CONTRACT_VERSION.to_string(), paused: false, pauser: env::current_account_id() };
+        let contract = new_contract();
         assert_eq!(contract.owner(), env::current_account_id());
     }
 
-    #[test]
-    fn paused() {
-        let contract = FastAuth { guards: HashMap::new(), owner: env::current_account_id(), mpc_address: env::current_account_id(), mpc_key_version: DEFAULT_MPC_KEY_VERSION, mpc_domain_id: DEFAULT_DOMAIN_ID, version: CONTRACT_VERSION.to_string(), paused: false, pauser: env::current_account_id() };
-        assert_eq!(contract.paused(), false);
-    }
-    
     #[test]
     fn mpc_address() {
-        let contract = FastAuth { guards: HashMap::new(), owner: env::current_account_id(), mpc_address: env::current_account_id(), mpc_key_version: DEFAULT_MPC_KEY_VERSION, mpc_domain_id: DEFAULT_DOMAIN_ID, version: CONTRACT_VERSION.to_string(), paused: false, pauser: env::current_account_id() };
+        let contract = new_contract();
         assert_eq!(contract.mpc_address(), env::current_account_id());
     }
 
     #[test]
     fn mpc_key_version() {
-        let contract = FastAuth { guards: HashMap::new(), owner: env::current_account_id(), mpc_address: env::current_account_id(), mpc_key_version: DEFAULT_MPC_KEY_VERSION, mpc_domain_id: DEFAULT_DOMAIN_ID, version: CONTRACT_VERSION.to_string(), paused: false, pauser: env::current_account_id() };
+        let contract = new_contract();
         assert_eq!(contract.mpc_key_version(), DEFAULT_MPC_KEY_VERSION);
     }
 
     #[test]
     fn mpc_domain_id() {
-        let contract = FastAuth { guards: HashMap::new(), owner: env::current_account_id(), mpc_address: env::current_account_id(), mpc_key_version: DEFAULT_MPC_KEY_VERSION, mpc_domain_id: DEFAULT_DOMAIN_ID, version: CONTRACT_VERSION.to_string(), paused: false, pauser: env::current_account_id() };
+        let contract = new_contract();
         assert_eq!(contract.mpc_domain_id(), DEFAULT_DOMAIN_ID);
     }
     
     #[test]
     fn version() {
         let version = "0.1.0".to_string();
-        let contract = FastAuth { guards: HashMap::new(), owner: env::current_account_id(), mpc_address: env::current_account_id(), mpc_key_version: DEFAULT_MPC_KEY_VERSION, mpc_domain_id: DEFAULT_DOMAIN_ID, version: version.clone(), paused: false, pauser: env::current_account_id() };
+        let mut contract = new_contract();
+        contract.version = version.clone();
         assert_eq!(contract.version(), version);
     }
 
     #[test]
-    fn update_contract_owner_success() {
-        use near_sdk::test_utils::{accounts, VMContextBuilder};
-        use near_sdk::testing_env;
+    fn init_grants_roles() {
+        let (contract, owner, pauser) = setup_contract();
+        assert!(contract.acl_is_super_admin(owner.clone()));
+        assert!(contract.acl_has_role(Role::DAO.into(), owner));
+        assert!(contract.acl_has_role(Role::PauseManager.into(), pauser));
+        assert!(!contract.pa_is_paused("ALL".to_string()));
+    }
+
+    #[test]
+    fn accept_ownership_moves_roles() {
+        let (mut contract, owner, _) = setup_contract();
+        let new_owner = accounts(3);
+
+        contract.propose_owner(new_owner.clone(), None);
+        assert_eq!(contract.owner(), owner);
+        assert_eq!(contract.pending_owner().unwrap().account_id, new_owner);
+
+        set_predecessor(new_owner.clone());
+        contract.accept_ownership();
+
+        assert_eq!(contract.owner(), new_owner);
+        assert!(contract.pending_owner().is_none());
+        assert!(contract.acl_is_super_admin(new_owner.clone()));
+        assert!(contract.acl_has_role(Role::DAO.into(), new_owner));
+        assert!(!contract.acl_is_super_admin(owner.clone()));
+        assert!(!contract.acl_has_role(Role::DAO.into(), owner));
+    }
+
+    #[test]
+    #[should_panic(expected = "Only the pending owner can accept the ownership")]
+    fn accept_ownership_other_account_fails() {
+        let (mut contract, _, pauser) = setup_contract();
+        contract.propose_owner(accounts(3), None);
+
+        set_predecessor(pauser);
+        contract.accept_ownership();
+    }
+
+    #[test]
+    #[should_panic(expected = "Ownership transfer expired")]
+    fn accept_expired_ownership_fails() {
+        let (mut contract, _, _) = setup_contract();
+        contract.propose_owner(accounts(3), Some(1_000));
 
-        let owner = accounts(1);
         let mut context = VMContextBuilder::new();
         context
             .current_account_id(accounts(0))
-            .signer_account_id(owner.clone())
-            .predecessor_account_id(owner.clone());
+            .predecessor_account_id(accounts(3))
+            .block_timestamp(1_001 * 1_000_000);
         testing_env!(context.build());
+        contract.accept_ownership();
+    }
 
-        let contract = FastAuth { 
-            guards: HashMap::new(), 
-            owner: owner.clone(), 
-            mpc_address: env::current_account_id(), 
-            mpc_domain_id: DEFAULT_DOMAIN_ID,
-            mpc_key_version: DEFAULT_MPC_KEY_VERSION, 
-            version: CONTRACT_VERSION.to_string(), 
-            paused: false, 
-            pauser: env::current_account_id() 
-        };
+    #[test]
+    #[should_panic(expected = "No pending ownership transfer")]
+    fn accept_cancelled_ownership_fails() {
+        let (mut contract, _, _) = setup_contract();
+        contract.propose_owner(accounts(3), None);
+        contract.cancel_ownership_transfer();
+        assert!(contract.pending_owner().is_none());
+
+        set_predecessor(accounts(3));
+        contract.accept_ownership();
+    }
 
-        // This should not panic for owner (input handling is tested in integration tests)
-        let _promise = contract.update_contract();
+    #[test]
+    fn add_guard_guard_manager_success() {
+        let (mut contract, _, _) = setup_contract();
+        let guard_manager = accounts(3);
+        contract.acl_grant_role(Role::GuardManager.into(), guard_manager.clone());
+
+        set_predecessor(guard_manager);
+        contract.add_guard("jwt".to_string(), accounts(4), None, None);
+        assert_eq!(contract.get_guard("jwt".to_string()), accounts(4));
     }
 
     #[test]
-    #[should_panic(expected = "Only the owner can call this function")]
-    fn update_contract_non_owner_fails() {
-        use near_sdk::test_utils::{accounts, VMContextBuilder};
-        use near_sdk::testing_env;
+    fn add_guard_records_guard_info() {
+        let (mut contract, owner, _) = setup_contract();
+        contract.add_guard("jwt".to_string(), accounts(3), Some("Auth0 login".to_string()), Some(vec!["eddsa".to_string()]));
+
+        let guard_info = contract.get_guard_info("jwt".to_string());
+        assert_eq!(guard_info.account_id, accounts(3));
+        assert_eq!(guard_info.description, Some("Auth0 login".to_string()));
+        assert_eq!(guard_info.added_by, owner);
+        assert_eq!(guard_info.supported_algorithms, vec![SignatureAlgorithm::Eddsa]);
+    }
+
+    #[test]
+    fn get_guards_paginated() {
+        let (mut contract, _, _) = setup_contract();
+        for (i, guard_id) in ["a", "b", "c"].iter().enumerate() {
+            contract.add_guard(guard_id.to_string(), accounts(i + 3), None, None);
+        }
+
+        assert_eq!(contract.get_guards_count(), 3);
+        let guard_ids: Vec<String> = contract.get_guards(Some(1), Some(5)).into_iter().map(|(guard_id, _)| guard_id).collect();
+        assert_eq!(guard_ids, vec!["b".to_string(), "c".to_string()]);
+        assert_eq!(contract.get_guards(None, Some(1)).len(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "does not support algorithm")]
+    fn sign_unsupported_algorithm_fails() {
+        let (mut contract, _, _) = setup_contract();
+        contract.add_guard("jwt".to_string(), accounts(3), None, Some(vec!["ecdsa".to_string()]));
+        let _ = contract.sign("jwt".to_string(), "jwt".to_string(), vec![1, 2, 3], "eddsa".to_string(), None, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Insufficient permissions")]
+    fn add_guard_unauthorized_fails() {
+        let (mut contract, _, pauser) = setup_contract();
+
+        set_predecessor(pauser);
+        contract.add_guard("jwt".to_string(), accounts(4), None, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Insufficient permissions")]
+    fn set_mpc_address_guard_manager_fails() {
+        let (mut contract, _, _) = setup_contract();
+        let guard_manager = accounts(3);
+        contract.acl_grant_role(Role::GuardManager.into(), guard_manager.clone());
+
+        set_predecessor(guard_manager);
+        contract.set_mpc_address(accounts(4));
+    }
+
+    #[test]
+    #[should_panic(expected = "Method is paused")]
+    fn pause_manager_pauses_contract() {
+        let (mut contract, _, pauser) = setup_contract();
+
+        set_predecessor(pauser);
+        contract.pa_pause_feature("ALL".to_string());
+        contract.mpc_address();
+    }
+
+    #[test]
+    fn add_guard_emits_event() {
+        let (mut contract, _, _) = setup_contract();
+        contract.add_guard("jwt".to_string(), accounts(3), None, None);
+
+        let logs = near_sdk::test_utils::get_logs();
+        assert_eq!(
+            logs.last().unwrap(),
+            &format!(
+                r#"EVENT_JSON:{{"standard":"fast-auth","version":"1.0.0","event":"guard_added","data":{{"guard_id":"jwt","guard_address":"{}"}}}}"#,
+                accounts(3)
+            )
+        );
+    }
+
+    #[test]
+    fn timelocked_operation_executes_after_delay() {
+        let (mut contract, owner, _) = setup_contract();
+        assert_eq!(contract.set_timelock_delay(1_000), None);
+        assert_eq!(contract.timelock_delay(), 1_000);
+
+        let operation_id = contract.set_mpc_address(accounts(3)).unwrap();
+        assert_eq!(contract.mpc_address(), accounts(0));
+        let scheduled = contract.get_scheduled_operation(operation_id).unwrap();
+        assert_eq!(scheduled.operation, AdminOperation::SetMpcAddress { mpc_address: accounts(3) });
+        assert_eq!(scheduled.scheduled_by, owner);
+        assert_eq!(scheduled.executable_at, 1_000);
+        assert_eq!(contract.get_scheduled_operations(None, None).len(), 1);
 
-        let owner = accounts(1);
-        let non_owner = accounts(2);
         let mut context = VMContextBuilder::new();
         context
             .current_account_id(accounts(0))
-            .signer_account_id(non_owner.clone())
-            .predecessor_account_id(non_owner.clone());
+            .predecessor_account_id(accounts(4))
+            .block_timestamp(1_000 * 1_000_000);
         testing_env!(context.build());
+        contract.execute_operation(operation_id);
 
-        let contract = FastAuth { 
-            guards: HashMap::new(), 
-            owner: owner.clone(), 
-            mpc_address: env::current_account_id(), 
-            mpc_domain_id: DEFAULT_DOMAIN_ID,
-            mpc_key_version: DEFAULT_MPC_KEY_VERSION, 
-            version: CONTRACT_VERSION.to_string(), 
-            paused: false, 
-            pauser: env::current_account_id() 
-        };
+        assert_eq!(contract.mpc_address(), accounts(3));
+        assert!(contract.get_scheduled_operation(operation_id).is_none());
+    }
 
-        // This should panic because non-owner is calling
-        contract.update_contract();
+    #[test]
+    #[should_panic(expected = "cannot be executed before 1000")]
+    fn execute_operation_before_delay_fails() {
+        let (mut contract, _, _) = setup_contract();
+        contract.set_timelock_delay(1_000);
+
+        let operation_id = contract.add_guard("jwt".to_string(), accounts(3), None, None).unwrap();
+        contract.execute_operation(operation_id);
+    }
+
+    #[test]
+    #[should_panic(expected = "Operation with ID 0 does not exist")]
+    fn guardian_cancels_operation() {
+        let (mut contract, _, _) = setup_contract();
+        let guardian = accounts(3);
+        contract.acl_grant_role(Role::Guardian.into(), guardian.clone());
+        contract.set_timelock_delay(1_000);
+        let operation_id = contract.remove_guard("jwt".to_string()).unwrap();
+
+        set_predecessor(guardian);
+        contract.cancel_operation(operation_id);
+        assert!(contract.get_scheduled_operations(None, None).is_empty());
+
+        contract.execute_operation(operation_id);
+    }
+
+    #[test]
+    #[should_panic(expected = "Insufficient permissions")]
+    fn cancel_operation_unauthorized_fails() {
+        let (mut contract, _, pauser) = setup_contract();
+        contract.set_timelock_delay(1_000);
+        let operation_id = contract.set_mpc_domain_id(2).unwrap();
+
+        set_predecessor(pauser);
+        contract.cancel_operation(operation_id);
+    }
+
+    #[test]
+    fn pause_and_unpause_emit_events() {
+        let (mut contract, owner, pauser) = setup_contract();
+
+        set_predecessor(pauser.clone());
+        contract.pause();
+        assert!(contract.pa_is_paused("ALL".to_string()));
+        assert_eq!(
+            near_sdk::test_utils::get_logs().last().unwrap(),
+            &format!(r#"EVENT_JSON:{{"standard":"fast-auth","version":"1.0.0","event":"paused","data":{{"by":"{}"}}}}"#, pauser)
+        );
+
+        set_predecessor(owner.clone());
+        contract.unpause();
+        assert!(!contract.pa_is_paused("ALL".to_string()));
+        assert_eq!(
+            near_sdk::test_utils::get_logs().last().unwrap(),
+            &format!(r#"EVENT_JSON:{{"standard":"fast-auth","version":"1.0.0","event":"unpaused","data":{{"by":"{}"}}}}"#, owner)
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Insufficient permissions")]
+    fn unpause_pause_manager_fails() {
+        let (mut contract, _, pauser) = setup_contract();
+
+        set_predecessor(pauser);
+        contract.pause();
+        contract.unpause();
+    }
+
+    #[test]
+    fn freeze_guard_pause_manager_success() {
+        let (mut contract, owner, pauser) = setup_contract();
+        contract.add_guard("jwt".to_string(), accounts(3), None, None);
+        contract.add_guard("custom".to_string(), accounts(4), None, None);
+
+        set_predecessor(pauser);
+        contract.freeze_guard("jwt".to_string());
+        assert!(contract.is_guard_frozen("jwt".to_string()));
+        assert!(!contract.is_guard_frozen("custom".to_string()));
+        assert_eq!(contract.frozen_guards(), vec!["jwt".to_string()]);
+        assert_eq!(contract.get_guard("custom".to_string()), accounts(4));
+
+        set_predecessor(owner);
+        contract.unfreeze_guard("jwt".to_string());
+        assert!(contract.frozen_guards().is_empty());
+        assert_eq!(contract.get_guard("jwt".to_string()), accounts(3));
+    }
+
+    #[test]
+    #[should_panic(expected = "Guard with ID 'jwt' is frozen")]
+    fn get_frozen_guard_fails() {
+        let (mut contract, _, _) = setup_contract();
+        contract.add_guard("jwt".to_string(), accounts(3), None, None);
+        contract.freeze_guard("jwt".to_string());
+        contract.get_guard("jwt".to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "Insufficient permissions")]
+    fn unfreeze_guard_pause_manager_fails() {
+        let (mut contract, _, pauser) = setup_contract();
+        contract.add_guard("jwt".to_string(), accounts(3), None, None);
+
+        set_predecessor(pauser);
+        contract.freeze_guard("jwt".to_string());
+        contract.unfreeze_guard("jwt".to_string());
     }
 
     #[test]
     fn bytes_to_hex() {
-        let contract = FastAuth { guards: HashMap::new(), owner: env::current_account_id(), mpc_address: env::current_account_id(), mpc_key_version: DEFAULT_MPC_KEY_VERSION, mpc_domain_id: DEFAULT_DOMAIN_ID, version: CONTRACT_VERSION.to_string(), paused: false, pauser: env::current_account_id() };
+        let contract = new_contract();
         
         // Test empty bytes
         assert_eq!(contract.bytes_to_hex(&[]), "");
@@ -787,6 +2483,626 @@ mod tests {
         assert_eq!(contract.bytes_to_hex(&hash), expected);
     }
 
+    #[test]
+    fn batch_commitment() {
+        let contract = new_contract();
+        let (a, b, c) = (vec![1u8], vec![2u8], vec![3u8]);
+        let leaf = |payload: &[u8]| env::sha256([&[0x00], payload].concat());
+        let node = |left: &[u8], right: &[u8]| env::sha256([&[0x01], left, right].concat());
+        let tagged = |root: &[u8]| env::sha256([b"fast-auth:batch".as_slice(), root].concat());
+        let (leaf_a, leaf_b, leaf_c) = (leaf(&a), leaf(&b), leaf(&c));
+
+        // A single payload commits to its own leaf
+        assert_eq!(contract.batch_commitment(vec![a.clone()]), tagged(&leaf_a));
+
+        // Two payloads commit to the node of both leaves
+        let root_ab = node(&leaf_a, &leaf_b);
+        assert_eq!(contract.batch_commitment(vec![a.clone(), b.clone()]), tagged(&root_ab));
+
+        // Odd nodes are carried up to the next level
+        let root_abc = node(&root_ab, &leaf_c);
+        assert_eq!(contract.batch_commitment(vec![a.clone(), b.clone(), c]), tagged(&root_abc));
+
+        // Order matters
+        assert_ne!(contract.batch_commitment(vec![b, a]), contract.batch_commitment(vec![vec![1u8], vec![2u8]]));
+    }
+
+    #[test]
+    fn batch_commitment_inner_nodes_do_not_collide() {
+        let contract = new_contract();
+        let (a, b) = (vec![1u8], vec![2u8]);
+
+        // A batch of the concatenated leaf hashes committed to the same root before leaves and
+        // nodes were domain separated
+        let inner = [env::sha256(&a), env::sha256(&b)].concat();
+        assert_ne!(contract.batch_commitment(vec![a, b]), contract.batch_commitment(vec![inner]));
+    }
+
+    #[test]
+    #[should_panic(expected = "Batch cannot be empty")]
+    fn batch_commitment_empty_fails() {
+        let contract = new_contract();
+        contract.batch_commitment(vec![]);
+    }
+
+    #[test]
+    fn sign_rejected_returns_guard_reason() {
+        let (mut contract, _, _) = setup_contract();
+        let result = contract.on_verify_sign_callback(
+            "jwt".to_string(),
+            vec![1, 2, 3],
+            NearToken::from_yoctonear(1),
+            SignCost { mpc_deposit: DEFAULT_MPC_DEPOSIT, fee: NearToken::from_yoctonear(0) },
+            SignatureAlgorithm::Ecdsa,
+            HashingMode::Sha256,
+            accounts(3),
+            None,
+            Ok((false, "Token expired".to_string(), String::new())),
+        );
+        match result {
+            PromiseOrValue::Value(SignOutcome::Failed { code, reason }) => {
+                assert_eq!(code, SignErrorCode::VerificationRejected);
+                assert_eq!(reason, "Token expired");
+            }
+            _ => panic!("Expected a failed outcome"),
+        }
+    }
+
+    #[test]
+    fn sign_invalid_sub_fails() {
+        let (mut contract, _, _) = setup_contract();
+        let result = contract.on_verify_sign_callback(
+            "jwt".to_string(),
+            vec![1, 2, 3],
+            NearToken::from_yoctonear(1),
+            SignCost { mpc_deposit: DEFAULT_MPC_DEPOSIT, fee: NearToken::from_yoctonear(0) },
+            SignatureAlgorithm::Ecdsa,
+            HashingMode::Sha256,
+            accounts(3),
+            None,
+            Ok((true, "issuer#sub".to_string(), "jwt".to_string())),
+        );
+        match result {
+            PromiseOrValue::Value(SignOutcome::Failed { code, .. }) => assert_eq!(code, SignErrorCode::InvalidSub),
+            _ => panic!("Expected a failed outcome"),
+        }
+    }
+
+    #[test]
+    fn sign_over_rate_limit_fails() {
+        let (mut contract, _, _) = setup_contract();
+        contract.set_rate_limits(RateLimits {
+            subject: Some(RateLimit { max_requests: 2, window_ms: 60_000 }),
+            guard: Some(RateLimit { max_requests: 3, window_ms: 60_000 }),
+        });
+        assert_eq!(contract.get_remaining_requests("jwt#issuer".to_string(), "alice".to_string()), Some(2));
+
+        let verify_sign = |contract: &mut FastAuth, user: &str| {
+            contract.on_verify_sign_callback(
+                "jwt#issuer".to_string(),
+                vec![1, 2, 3],
+                NearToken::from_yoctonear(1),
+                SignCost { mpc_deposit: DEFAULT_MPC_DEPOSIT, fee: NearToken::from_yoctonear(0) },
+                SignatureAlgorithm::Ecdsa,
+                HashingMode::Sha256,
+                accounts(3),
+                None,
+                Ok((true, user.to_string(), "jwt".to_string())),
+            )
+        };
+        assert!(matches!(verify_sign(&mut contract, "alice"), PromiseOrValue::Promise(_)));
+        assert!(matches!(verify_sign(&mut contract, "alice"), PromiseOrValue::Promise(_)));
+        assert_eq!(contract.get_remaining_requests("jwt#issuer".to_string(), "alice".to_string()), Some(0));
+        // Bob is limited by the requests left to the guard
+        assert_eq!(contract.get_remaining_requests("jwt#issuer".to_string(), "bob".to_string()), Some(1));
+
+        match verify_sign(&mut contract, "alice") {
+            PromiseOrValue::Value(SignOutcome::Failed { code, .. }) => assert_eq!(code, SignErrorCode::RateLimited),
+            _ => panic!("Expected a failed outcome"),
+        }
+        assert!(matches!(verify_sign(&mut contract, "bob"), PromiseOrValue::Promise(_)));
+    }
+
+    #[test]
+    fn linked_identities_share_rate_limit() {
+        let (mut contract, _, _) = setup_contract();
+        contract.set_rate_limits(RateLimits {
+            subject: Some(RateLimit { max_requests: 2, window_ms: 60_000 }),
+            guard: None,
+        });
+        assert!(contract.on_link_identity_callback("jwt#google".to_string(), "alice".to_string(), "jwt#apple".to_string(), "a1".to_string(), verified("alice"), verified("a1")));
+
+        let sign_payloads = [vec![1, 2, 3]];
+        assert!(contract.consume_sign_limits("jwt#google", "alice".to_string(), &sign_payloads, HashingMode::Sha256, &[], None).is_ok());
+        assert_eq!(contract.get_remaining_requests("jwt#apple".to_string(), "a1".to_string()), Some(1));
+        assert!(contract.consume_sign_limits("jwt#apple", "a1".to_string(), &sign_payloads, HashingMode::Sha256, &[], None).is_ok());
+        assert!(contract.consume_sign_limits("jwt#apple", "a1".to_string(), &sign_payloads, HashingMode::Sha256, &[], None).is_err());
+        assert_eq!(contract.get_remaining_requests("jwt#google".to_string(), "alice".to_string()), Some(0));
+    }
+
+    #[test]
+    fn get_remaining_requests_unlimited() {
+        let (contract, _, _) = setup_contract();
+        assert_eq!(contract.get_remaining_requests("jwt".to_string(), "alice".to_string()), None);
+    }
+
+    fn verified(sub: &str) -> Result<(bool, String, String), PromiseError> {
+        Ok((true, sub.to_string(), "jwt".to_string()))
+    }
+
+    #[test]
+    fn linked_identity_signs_with_canonical_path() {
+        let (mut contract, _, _) = setup_contract();
+        assert!(contract.on_link_identity_callback("jwt#google".to_string(), "alice".to_string(), "jwt#apple".to_string(), "a1".to_string(), verified("alice"), verified("a1")));
+
+        assert_eq!(contract.get_derivation_path("jwt#apple".to_string(), "a1".to_string()), "jwt#google#alice");
+        assert_eq!(contract.get_derivation_path("jwt#google".to_string(), "alice".to_string()), "jwt#google#alice");
+        let linked_identities = contract.get_linked_identities("jwt#google#alice".to_string());
+        assert_eq!(linked_identities.len(), 1);
+        assert_eq!(linked_identities[0].0, "jwt#apple#a1");
+
+        // Links are not chained
+        assert!(!contract.on_link_identity_callback("jwt#apple".to_string(), "a1".to_string(), "jwt#github".to_string(), "a2".to_string(), verified("a1"), verified("a2")));
+        assert!(!contract.on_link_identity_callback("jwt#github".to_string(), "a2".to_string(), "jwt#google".to_string(), "alice".to_string(), verified("a2"), verified("alice")));
+    }
+
+    #[test]
+    fn link_identity_other_sub_fails() {
+        let (mut contract, _, _) = setup_contract();
+        assert!(!contract.on_link_identity_callback("jwt#google".to_string(), "alice".to_string(), "jwt#apple".to_string(), "a1".to_string(), verified("alice"), verified("mallory")));
+        assert!(contract.get_identity_link("jwt#apple#a1".to_string()).is_none());
+    }
+
+    #[test]
+    fn unlinked_identity_resolves_after_cooldown() {
+        let (mut contract, _, _) = setup_contract();
+        contract.on_link_identity_callback("jwt#google".to_string(), "alice".to_string(), "jwt#apple".to_string(), "a1".to_string(), verified("alice"), verified("a1"));
+        assert!(contract.on_unlink_identity_callback("jwt#apple".to_string(), "a1".to_string(), "jwt#apple#a1".to_string(), verified("a1")));
+        assert_eq!(contract.get_identity_link("jwt#apple#a1".to_string()).unwrap().unlinks_at, Some(UNLINK_COOLDOWN_MS));
+        assert_eq!(contract.get_derivation_path("jwt#apple".to_string(), "a1".to_string()), "jwt#google#alice");
+
+        let mut context = VMContextBuilder::new();
+        context
+            .current_account_id(accounts(0))
+            .predecessor_account_id(accounts(1))
+            .block_timestamp(UNLINK_COOLDOWN_MS * 1_000_000);
+        testing_env!(context.build());
+        assert_eq!(contract.get_derivation_path("jwt#apple".to_string(), "a1".to_string()), "jwt#apple#a1");
+        assert!(contract.get_linked_identities("jwt#google#alice".to_string()).is_empty());
+
+        // The identity can be linked again once unlinked
+        assert!(contract.on_link_identity_callback("jwt#google".to_string(), "alice".to_string(), "jwt#apple".to_string(), "a1".to_string(), verified("alice"), verified("a1")));
+        assert_eq!(contract.get_linked_identities("jwt#google#alice".to_string()).len(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Identity 'jwt#github#a2' cannot unlink 'jwt#apple#a1'")]
+    fn unlink_identity_unrelated_identity_fails() {
+        let (mut contract, _, _) = setup_contract();
+        contract.add_guard("jwt".to_string(), accounts(3), None, None);
+        contract.on_link_identity_callback("jwt#google".to_string(), "alice".to_string(), "jwt#apple".to_string(), "a1".to_string(), verified("alice"), verified("a1"));
+        let _ = contract.unlink_identity("jwt#github".to_string(), "a2".to_string(), "jwt".to_string(), "jwt#apple#a1".to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "Raw hashing mode is only supported by eddsa")]
+    fn sign_raw_hashing_with_ecdsa_fails() {
+        let (mut contract, _, _) = setup_contract();
+        let _ = contract.sign("jwt".to_string(), "jwt".to_string(), vec![1; 32], "ecdsa".to_string(), Some("raw".to_string()), None);
+    }
+
+    #[test]
+    fn mpc_failure_returns_failed_outcome() {
+        let (mut contract, _, _) = setup_contract();
+        let sign_cost = SignCost { mpc_deposit: DEFAULT_MPC_DEPOSIT, fee: NearToken::from_yoctonear(10) };
+        let outcome = contract.on_sign_callback("jwt".to_string(), accounts(3), NearToken::from_yoctonear(11), sign_cost, None, Err(PromiseError::Failed));
+        assert!(contract.collected_fees().is_zero());
+        match outcome {
+            SignOutcome::Failed { code, reason } => {
+                assert_eq!(code, SignErrorCode::MpcFailed);
+                assert_eq!(reason, "MPC signing failed");
+            }
+            SignOutcome::Signed { .. } => panic!("Expected a failed outcome"),
+        }
+    }
+
+    #[test]
+    fn signed_payload_collects_fee() {
+        let (mut contract, _, _) = setup_contract();
+        contract.set_sign_fee("eddsa".to_string(), NearToken::from_yoctonear(10));
+        assert_eq!(contract.quote_sign_cost("eddsa".to_string(), Some(2)), NearToken::from_yoctonear(22));
+        assert_eq!(contract.quote_sign_cost("ecdsa".to_string(), None), DEFAULT_MPC_DEPOSIT);
+
+        let sign_cost = contract.sign_cost(&SignatureAlgorithm::Eddsa);
+        let signature = SignResponseAny::EdDsa(EdDsaSignResponse { scheme: "Ed25519".to_string(), signature: vec![1, 2] });
+        let outcome = contract.on_sign_callback("jwt".to_string(), accounts(3), NearToken::from_yoctonear(20), sign_cost, None, Ok(signature));
+        assert!(matches!(outcome, SignOutcome::Signed { .. }));
+        assert_eq!(contract.collected_fees(), NearToken::from_yoctonear(10));
+    }
+
+    #[test]
+    #[should_panic(expected = "Attached deposit must cover the sign cost of 11 yoctoNEAR")]
+    fn sign_insufficient_deposit_fails() {
+        let (mut contract, _, _) = setup_contract();
+        contract.add_guard("jwt".to_string(), accounts(3), None, None);
+        contract.set_sign_fee("ecdsa".to_string(), NearToken::from_yoctonear(10));
+        let _ = contract.sign("jwt".to_string(), "jwt".to_string(), vec![1, 2, 3], "ecdsa".to_string(), None, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Only the treasury or a DAO can withdraw fees")]
+    fn withdraw_fees_unauthorized_fails() {
+        let (mut contract, _, pauser) = setup_contract();
+        contract.set_treasury(accounts(4));
+        contract.collected_fees = NearToken::from_yoctonear(10);
+
+        set_predecessor(pauser);
+        let _ = contract.withdraw_fees(None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot withdraw more than the collected fees")]
+    fn withdraw_fees_over_collected_fails() {
+        let (mut contract, _, _) = setup_contract();
+        contract.set_treasury(accounts(4));
+        contract.collected_fees = NearToken::from_yoctonear(10);
+
+        set_predecessor(accounts(4));
+        let _ = contract.withdraw_fees(Some(NearToken::from_yoctonear(11)));
+    }
+
+    fn transfer_transaction(receiver_id: &str, deposit: u128) -> Vec<u8> {
+        near_sdk::borsh::to_vec(&TransactionV0 {
+            signer_id: accounts(3),
+            public_key: TxPublicKey::Ed25519([1; 32]),
+            nonce: 1,
+            receiver_id: receiver_id.parse().unwrap(),
+            block_hash: [0; 32],
+            actions: vec![Action::Transfer { deposit }],
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn check_guard_policy() {
+        let (mut contract, _, _) = setup_contract();
+        contract.add_guard("jwt".to_string(), accounts(3), None, None);
+        let policy = TransactionPolicy { max_deposit: Some(NearToken::from_yoctonear(100)), ..Default::default() };
+        contract.set_guard_policy("jwt".to_string(), Some(policy.clone()));
+        assert_eq!(contract.get_guard_policy("jwt#issuer".to_string()), Some(policy));
+
+        assert_eq!(contract.check_guard_policy("jwt#issuer".to_string(), transfer_transaction("bob.near", 100)), None);
+        assert!(contract.check_guard_policy("jwt#issuer".to_string(), transfer_transaction("bob.near", 101)).is_some());
+        assert!(contract.check_guard_policy("jwt#issuer".to_string(), vec![1; 32]).is_some());
+
+        contract.set_guard_policy("jwt".to_string(), None);
+        assert_eq!(contract.check_guard_policy("jwt#issuer".to_string(), vec![1; 32]), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Sign payload violates the policy of guard 'jwt': Action Transfer is not allowed")]
+    fn sign_policy_violation_fails() {
+        let (mut contract, _, _) = setup_contract();
+        contract.add_guard("jwt".to_string(), accounts(3), None, None);
+        contract.set_guard_policy("jwt".to_string(), Some(TransactionPolicy { denied_actions: vec![ActionKind::Transfer], ..Default::default() }));
+        let _ = contract.sign("jwt#issuer".to_string(), "jwt".to_string(), transfer_transaction("bob.near", 1), "ecdsa".to_string(), None, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Guard with ID 'jwt' does not exist")]
+    fn set_guard_policy_unknown_guard_fails() {
+        let (mut contract, _, _) = setup_contract();
+        contract.set_guard_policy("jwt".to_string(), Some(TransactionPolicy::default()));
+    }
+
+    /// Ed25519 key and signature of the message 0x72, from RFC 8032 test vector 2
+    const SECOND_FACTOR_PUBLIC_KEY: &str = "3d4017c3e843895a92b70aa74d1b7ebc9c982ccf2ec4968cc0cd55f12af4660c";
+    const SECOND_FACTOR_SIGNATURE: &str = "92a009a9f0d4cab8720e820b5f642540a2b27b5416503f8fb3762223ebdb69da085ac1e43e15996e458f3613d0f11d8c387b2eaeb4302aeeb00d291612bb0c00";
+
+    fn from_hex(hex: &str) -> Vec<u8> {
+        (0..hex.len()).step_by(2).map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap()).collect()
+    }
+
+    fn near_limit(yoctonear: u128) -> Option<SpendingLimits> {
+        Some(SpendingLimits { near: Some(NearToken::from_yoctonear(yoctonear)), evm: None })
+    }
+
+    #[test]
+    fn sign_over_spending_limit_fails() {
+        let (mut contract, _, _) = setup_contract();
+        contract.add_guard("jwt".to_string(), accounts(3), None, None);
+        contract.set_guard_spending_limits("jwt".to_string(), near_limit(10));
+
+        let verify_sign = |contract: &mut FastAuth, sign_payload: Vec<u8>| {
+            contract.on_verify_sign_callback(
+                "jwt#issuer".to_string(),
+                sign_payload,
+                NearToken::from_yoctonear(1),
+                SignCost { mpc_deposit: DEFAULT_MPC_DEPOSIT, fee: NearToken::from_yoctonear(0) },
+                SignatureAlgorithm::Ecdsa,
+                HashingMode::Sha256,
+                accounts(3),
+                None,
+                verified("alice"),
+            )
+        };
+        assert!(matches!(verify_sign(&mut contract, transfer_transaction("bob.near", 6)), PromiseOrValue::Promise(_)));
+        assert_eq!(contract.get_spent("jwt#issuer".to_string(), "alice".to_string(), "near".to_string()), U128(6));
+
+        match verify_sign(&mut contract, transfer_transaction("bob.near", 5)) {
+            PromiseOrValue::Value(SignOutcome::Failed { code, .. }) => assert_eq!(code, SignErrorCode::SpendingLimitExceeded),
+            _ => panic!("Expected a failed outcome"),
+        }
+        assert!(matches!(verify_sign(&mut contract, transfer_transaction("bob.near", 4)), PromiseOrValue::Promise(_)));
+        // Payloads that are not transactions cannot be valued, so they exceed the limit
+        match verify_sign(&mut contract, vec![1, 2, 3]) {
+            PromiseOrValue::Value(SignOutcome::Failed { code, .. }) => assert_eq!(code, SignErrorCode::SpendingLimitExceeded),
+            _ => panic!("Expected a failed outcome"),
+        }
+        assert_eq!(contract.get_spent("jwt#issuer".to_string(), "alice".to_string(), "near".to_string()), U128(10));
+    }
+
+    #[test]
+    fn spending_rejection_keeps_rate_limit() {
+        let (mut contract, _, _) = setup_contract();
+        contract.set_rate_limits(RateLimits {
+            subject: Some(RateLimit { max_requests: 2, window_ms: 60_000 }),
+            guard: None,
+        });
+        assert!(contract.on_update_spending_controls_callback("jwt".to_string(), "alice".to_string(), SpendingUpdate::Limits { limits: near_limit(10) }, None, verified("alice")));
+
+        let outcome = contract.consume_sign_limits("jwt", "alice".to_string(), &[transfer_transaction("bob.near", 20)], HashingMode::Sha256, &[], None).unwrap_err();
+        assert!(matches!(outcome, SignOutcome::Failed { code: SignErrorCode::SpendingLimitExceeded, .. }));
+        assert_eq!(contract.get_remaining_requests("jwt".to_string(), "alice".to_string()), Some(2));
+    }
+
+    #[test]
+    fn mpc_failure_releases_spending() {
+        let (mut contract, _, _) = setup_contract();
+        assert!(contract.on_update_spending_controls_callback("jwt".to_string(), "alice".to_string(), SpendingUpdate::Limits { limits: near_limit(10) }, None, verified("alice")));
+        let sign_cost = SignCost { mpc_deposit: DEFAULT_MPC_DEPOSIT, fee: NearToken::from_yoctonear(0) };
+
+        let (_, mut spends) = contract.consume_sign_limits("jwt", "alice".to_string(), &[transfer_transaction("bob.near", 6)], HashingMode::Sha256, &[], None).unwrap();
+        assert_eq!(contract.get_spent("jwt".to_string(), "alice".to_string(), "near".to_string()), U128(6));
+        let _ = contract.on_sign_callback("jwt".to_string(), accounts(3), NearToken::from_yoctonear(1), sign_cost, spends.pop().flatten(), Err(PromiseError::Failed));
+        assert_eq!(contract.get_spent("jwt".to_string(), "alice".to_string(), "near".to_string()), U128(0));
+
+        // Only the spends of the payloads MPC failed to sign are released
+        let sign_payloads = [transfer_transaction("bob.near", 6), transfer_transaction("bob.near", 3)];
+        let (_, spends) = contract.consume_sign_limits("jwt", "alice".to_string(), &sign_payloads, HashingMode::Sha256, &[], None).unwrap();
+        assert_eq!(contract.get_spent("jwt".to_string(), "alice".to_string(), "near".to_string()), U128(9));
+        let signature = SignResponseAny::EdDsa(EdDsaSignResponse { scheme: "Ed25519".to_string(), signature: vec![1, 2] });
+        testing_env!(
+            VMContextBuilder::new().current_account_id(accounts(0)).predecessor_account_id(accounts(0)).build(),
+            near_sdk::test_vm_config(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(serde_json::to_vec(&signature).unwrap()), PromiseResult::Failed],
+        );
+        let outcomes = contract.on_sign_batch_callback("jwt".to_string(), accounts(3), NearToken::from_yoctonear(2), sign_cost, spends);
+        assert!(matches!(outcomes[..], [SignOutcome::Signed { .. }, SignOutcome::Failed { .. }]));
+        assert_eq!(contract.get_spent("jwt".to_string(), "alice".to_string(), "near".to_string()), U128(6));
+    }
+
+    #[test]
+    fn unvalued_payloads_require_second_factor() {
+        let (mut contract, _, _) = setup_contract();
+        let spend = |contract: &mut FastAuth, sign_payload: Vec<u8>, hashing: HashingMode, signature: Option<&[u8]>| {
+            contract.consume_sign_limits("jwt", "alice".to_string(), &[sign_payload], hashing, &[0x72], signature)
+        };
+        // Without limits, nothing needs to be valued
+        assert!(spend(&mut contract, vec![1; 32], HashingMode::Raw, None).is_ok());
+
+        let public_key = PublicKey::from_parts(CurveType::ED25519, from_hex(SECOND_FACTOR_PUBLIC_KEY)).unwrap();
+        assert!(contract.on_update_spending_controls_callback("jwt".to_string(), "alice".to_string(), SpendingUpdate::Limits { limits: near_limit(10) }, None, verified("alice")));
+        assert!(contract.on_update_spending_controls_callback("jwt".to_string(), "alice".to_string(), SpendingUpdate::SecondFactor { public_key: Some(public_key) }, None, verified("alice")));
+
+        // A prehashed or raw 32-byte payload can be the hash of a NEAR transaction spending over the limit
+        let unvalued = [
+            (vec![1, 2, 3], HashingMode::Sha256),
+            (env::sha256(transfer_transaction("bob.near", 20)), HashingMode::Prehashed),
+            (env::sha256(transfer_transaction("bob.near", 20)), HashingMode::Raw),
+        ];
+        for (sign_payload, hashing) in unvalued.iter() {
+            let outcome = spend(&mut contract, sign_payload.clone(), *hashing, None).unwrap_err();
+            assert!(matches!(outcome, SignOutcome::Failed { code: SignErrorCode::SpendingLimitExceeded, .. }));
+        }
+
+        let mut context = VMContextBuilder::new();
+        context
+            .current_account_id(accounts(0))
+            .predecessor_account_id(accounts(1))
+            .block_timestamp(SECOND_FACTOR_ACTIVATION_MS * 1_000_000);
+        testing_env!(context.build());
+        let signature = from_hex(SECOND_FACTOR_SIGNATURE);
+        for (sign_payload, hashing) in unvalued {
+            assert!(spend(&mut contract, sign_payload, hashing, Some(&signature)).is_ok());
+        }
+        assert_eq!(contract.get_spent("jwt".to_string(), "alice".to_string(), "near".to_string()), U128(0));
+    }
+
+    #[test]
+    fn second_factor_allows_spending_over_limit() {
+        let (mut contract, _, _) = setup_contract();
+        let public_key = PublicKey::from_parts(CurveType::ED25519, from_hex(SECOND_FACTOR_PUBLIC_KEY)).unwrap();
+        assert!(contract.on_update_spending_controls_callback("jwt".to_string(), "alice".to_string(), SpendingUpdate::Limits { limits: near_limit(10) }, None, verified("alice")));
+        assert!(contract.on_update_spending_controls_callback("jwt".to_string(), "alice".to_string(), SpendingUpdate::SecondFactor { public_key: Some(public_key) }, None, verified("alice")));
+        assert_eq!(contract.get_spending_limits("jwt".to_string(), "alice".to_string()), near_limit(10).unwrap());
+
+        let signature = from_hex(SECOND_FACTOR_SIGNATURE);
+        let spend = |contract: &mut FastAuth| {
+            contract.consume_sign_limits("jwt", "alice".to_string(), &[transfer_transaction("bob.near", 20)], HashingMode::Sha256, &[0x72], Some(&signature))
+        };
+        // The second factor cannot be used before its activation
+        assert!(spend(&mut contract).is_err());
+
+        let mut context = VMContextBuilder::new();
+        context
+            .current_account_id(accounts(0))
+            .predecessor_account_id(accounts(1))
+            .block_timestamp(SECOND_FACTOR_ACTIVATION_MS * 1_000_000);
+        testing_env!(context.build());
+        assert!(spend(&mut contract).is_ok());
+        assert_eq!(contract.get_spent("jwt".to_string(), "alice".to_string(), "near".to_string()), U128(20));
+
+        // Once active, loosening the limits requires the second factor
+        assert!(!contract.on_update_spending_controls_callback("jwt".to_string(), "alice".to_string(), SpendingUpdate::Limits { limits: None }, None, verified("alice")));
+        assert!(contract.on_update_spending_controls_callback("jwt".to_string(), "alice".to_string(), SpendingUpdate::Limits { limits: near_limit(5) }, None, verified("alice")));
+    }
+
+    #[test]
+    #[should_panic(expected = "Second factor must be an ed25519 key")]
+    fn update_spending_controls_secp256k1_second_factor_fails() {
+        let (mut contract, _, _) = setup_contract();
+        contract.add_guard("jwt".to_string(), accounts(3), None, None);
+        let public_key = PublicKey::from_parts(CurveType::SECP256K1, vec![1; 64]).unwrap();
+        let _ = contract.update_spending_controls("jwt".to_string(), "alice".to_string(), "jwt".to_string(), SpendingUpdate::SecondFactor { public_key: Some(public_key) }, None);
+    }
+
+    fn session_request(key: u8) -> SessionRequest {
+        SessionRequest {
+            public_key: PublicKey::from_parts(CurveType::ED25519, vec![key; 32]).unwrap(),
+            expires_at: 1_000,
+            max_signatures: 2,
+            scope: SessionScope::default(),
+        }
+    }
+
+    #[test]
+    fn create_and_revoke_session() {
+        let (mut contract, _, _) = setup_contract();
+        let public_key = session_request(1).public_key;
+        assert!(contract.on_create_session_callback("jwt".to_string(), session_request(1), verified("alice")));
+        // A key cannot be used by two sessions
+        assert!(!contract.on_create_session_callback("jwt".to_string(), session_request(1), verified("bob")));
+
+        let sessions = contract.get_sessions("jwt".to_string(), "alice".to_string());
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].0, public_key);
+        assert_eq!(sessions[0].1.signatures, 0);
+        assert!(contract.get_sessions("jwt".to_string(), "bob".to_string()).is_empty());
+
+        // Only the user of the session can revoke it
+        assert!(!contract.on_revoke_session_callback("jwt".to_string(), public_key.clone(), verified("bob")));
+        assert!(contract.on_revoke_session_callback("jwt".to_string(), public_key.clone(), verified("alice")));
+        assert!(contract.get_session(public_key).is_none());
+        assert!(contract.get_sessions("jwt".to_string(), "alice".to_string()).is_empty());
+    }
+
+    #[test]
+    fn create_session_over_limit_fails() {
+        let (mut contract, _, _) = setup_contract();
+        for key in 0..MAX_SESSIONS_PER_IDENTITY as u8 {
+            assert!(contract.on_create_session_callback("jwt".to_string(), session_request(key), verified("alice")));
+        }
+        assert!(!contract.on_create_session_callback("jwt".to_string(), session_request(u8::MAX), verified("alice")));
+
+        // Expired sessions do not count towards the limit
+        let mut context = VMContextBuilder::new();
+        context.current_account_id(accounts(0)).predecessor_account_id(accounts(1)).block_timestamp(1_000 * 1_000_000);
+        testing_env!(context.build());
+        let mut request = session_request(u8::MAX);
+        request.expires_at = 2_000;
+        assert!(contract.on_create_session_callback("jwt".to_string(), request, verified("alice")));
+        assert_eq!(contract.get_sessions("jwt".to_string(), "alice".to_string()).len(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid session signature")]
+    fn sign_with_session_invalid_signature_fails() {
+        let (mut contract, _, _) = setup_contract();
+        contract.add_guard("jwt".to_string(), accounts(3), None, None);
+        assert!(contract.on_create_session_callback("jwt".to_string(), session_request(1), verified("alice")));
+        let _ = contract.sign_with_session(session_request(1).public_key, vec![0; 64], vec![1, 2, 3], "eddsa".to_string(), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Session does not exist, expired or ran out of signatures")]
+    fn sign_with_unknown_session_fails() {
+        let (mut contract, _, _) = setup_contract();
+        let _ = contract.sign_with_session(session_request(1).public_key, vec![0; 64], vec![1, 2, 3], "eddsa".to_string(), None);
+    }
+
+    fn two_guard_quorum() -> QuorumUpdate {
+        QuorumUpdate {
+            guard_id: "jwt".to_string(),
+            sub: "alice".to_string(),
+            quorum: Some(GuardQuorum {
+                approvers: vec![
+                    Approver { guard_id: "jwt".to_string(), sub: "alice".to_string() },
+                    Approver { guard_id: "passkey".to_string(), sub: "k1".to_string() },
+                ],
+                threshold: 2,
+                min_value: near_limit(10),
+            }),
+        }
+    }
+
+    #[test]
+    fn guard_quorum_approves_high_value_payloads() {
+        let (mut contract, _, _) = setup_contract();
+        contract.add_guard("jwt".to_string(), accounts(3), None, None);
+        // Only the user can set the first quorum of its path
+        assert!(!contract.on_update_guard_quorum_callback("passkey".to_string(), two_guard_quorum(), verified("k1")));
+        assert!(contract.on_update_guard_quorum_callback("jwt".to_string(), two_guard_quorum(), verified("alice")));
+
+        let sign_cost = SignCost { mpc_deposit: DEFAULT_MPC_DEPOSIT, fee: NearToken::from_yoctonear(0) };
+        let verify_sign = |contract: &mut FastAuth, sign_payload: Vec<u8>| {
+            contract.on_verify_sign_callback("jwt".to_string(), sign_payload, NearToken::from_yoctonear(1), sign_cost, SignatureAlgorithm::Ecdsa, HashingMode::Sha256, accounts(3), None, verified("alice"))
+        };
+        assert!(matches!(verify_sign(&mut contract, transfer_transaction("bob.near", 5)), PromiseOrValue::Promise(_)));
+        match verify_sign(&mut contract, transfer_transaction("bob.near", 10)) {
+            PromiseOrValue::Value(SignOutcome::Failed { code, .. }) => assert_eq!(code, SignErrorCode::ApprovalRequired),
+            _ => panic!("Expected a failed outcome"),
+        }
+
+        let request = SignApproval {
+            guard_id: "jwt".to_string(),
+            sub: "alice".to_string(),
+            sign_payload: transfer_transaction("bob.near", 10),
+            algorithm: "ecdsa".to_string(),
+            hashing: None,
+        };
+        let approve = |contract: &mut FastAuth, guard_id: &str, sub: &str| {
+            contract.on_approve_sign_callback(guard_id.to_string(), request.clone(), NearToken::from_yoctonear(1), sign_cost, SignatureAlgorithm::Ecdsa, HashingMode::Sha256, accounts(3), verified(sub))
+        };
+        match approve(&mut contract, "passkey", "k2") {
+            PromiseOrValue::Value(SignOutcome::Failed { code, .. }) => assert_eq!(code, SignErrorCode::ApprovalRejected),
+            _ => panic!("Expected a failed outcome"),
+        }
+        match approve(&mut contract, "jwt", "alice") {
+            PromiseOrValue::Value(SignOutcome::Failed { code, reason }) => {
+                assert_eq!(code, SignErrorCode::ApprovalPending);
+                assert_eq!(reason, "Approved by 1 of 2 guards");
+            }
+            _ => panic!("Expected a failed outcome"),
+        }
+        assert_eq!(contract.get_pending_approval(request.commitment()).unwrap().approvers.len(), 1);
+
+        assert!(matches!(approve(&mut contract, "passkey", "k1"), PromiseOrValue::Promise(_)));
+        assert!(contract.get_pending_approval(request.commitment()).is_none());
+    }
+
+    #[test]
+    fn remove_guard_quorum_requires_quorum() {
+        let (mut contract, _, _) = setup_contract();
+        assert!(contract.on_update_guard_quorum_callback("jwt".to_string(), two_guard_quorum(), verified("alice")));
+
+        let removal = QuorumUpdate { quorum: None, ..two_guard_quorum() };
+        assert!(!contract.on_update_guard_quorum_callback("jwt".to_string(), removal.clone(), verified("alice")));
+        assert!(contract.get_guard_quorum("jwt".to_string(), "alice".to_string()).is_some());
+
+        assert!(contract.on_update_guard_quorum_callback("passkey".to_string(), removal, verified("k1")));
+        assert!(contract.get_guard_quorum("jwt".to_string(), "alice".to_string()).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "Guard with ID 'jwt' does not exist")]
+    fn derived_public_key_unknown_guard_fails() {
+        let contract = new_contract();
+        let _ = contract.derived_public_key("jwt".to_string(), "user".to_string(), "ecdsa".to_string());
+    }
+
     #[test]
     fn signature_algorithm_from_str() {
         // Test valid algorithms
@@ -0,0 +1,112 @@
+use near_sdk::store::{IterableMap, LookupMap};
+use near_sdk::{near, AccountId, NearToken};
+use std::collections::HashMap;
+
+use crate::guards::guard_registry;
+use crate::fees::{SignFees, DEFAULT_MPC_DEPOSIT};
+use crate::rate_limit::RateLimits;
+use crate::{FastAuth, Prefix};
+
+/// FastAuth state layout prior to near-plugins roles, used for state migration
+#[near(serializers = [borsh])]
+pub struct FastAuthV1 {
+    guards: HashMap<String, AccountId>,
+    pub(crate) owner: AccountId,
+    mpc_address: AccountId,
+    mpc_key_version: u32,
+    mpc_domain_id: u64,
+    version: String,
+    pub(crate) pauser: AccountId,
+    pub(crate) paused: bool,
+}
+
+impl From<FastAuthV1> for FastAuth {
+    fn from(prev_state: FastAuthV1) -> Self {
+        Self {
+            guards: guard_registry(prev_state.guards),
+            owner: prev_state.owner,
+            pending_owner: None,
+            mpc_address: prev_state.mpc_address,
+            mpc_key_version: prev_state.mpc_key_version,
+            mpc_domain_id: prev_state.mpc_domain_id,
+            version: prev_state.version,
+            timelock_delay_ms: 0,
+            scheduled_operations: IterableMap::new(Prefix::ScheduledOperations),
+            next_operation_id: 0,
+            rate_limits: RateLimits::default(),
+            request_windows: LookupMap::new(Prefix::RequestWindows),
+            mpc_deposit: DEFAULT_MPC_DEPOSIT,
+            sign_fees: SignFees::default(),
+            collected_fees: NearToken::from_yoctonear(0),
+            treasury: None,
+            identity_links: LookupMap::new(Prefix::IdentityLinks),
+            linked_identities: LookupMap::new(Prefix::LinkedIdentities),
+            guard_policies: LookupMap::new(Prefix::GuardPolicies),
+            guard_spending_limits: LookupMap::new(Prefix::GuardSpendingLimits),
+            user_spending_limits: LookupMap::new(Prefix::UserSpendingLimits),
+            spending_windows: LookupMap::new(Prefix::SpendingWindows),
+            second_factors: LookupMap::new(Prefix::SecondFactors),
+            sessions: LookupMap::new(Prefix::Sessions),
+            identity_sessions: LookupMap::new(Prefix::IdentitySessions),
+            guard_quorums: LookupMap::new(Prefix::GuardQuorums),
+            pending_approvals: LookupMap::new(Prefix::PendingApprovals),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_plugins::{AccessControllable, Pausable};
+    use near_sdk::env;
+    use near_sdk::test_utils::{accounts, VMContextBuilder};
+    use near_sdk::testing_env;
+    use crate::Role;
+
+    fn write_v1_state(paused: bool) {
+        let mut context = VMContextBuilder::new();
+        context.current_account_id(accounts(0)).predecessor_account_id(accounts(0));
+        testing_env!(context.build());
+        env::state_write(&FastAuthV1 {
+            guards: HashMap::from([("jwt".to_string(), accounts(3))]),
+            owner: accounts(1),
+            mpc_address: accounts(4),
+            mpc_key_version: 0,
+            mpc_domain_id: 1,
+            version: "0.1.0".to_string(),
+            pauser: accounts(2),
+            paused,
+        });
+    }
+
+    #[test]
+    fn migrate_v1_grants_roles() {
+        write_v1_state(false);
+        let contract = FastAuth::migrate();
+        assert!(contract.acl_has_role(Role::DAO.into(), accounts(1)));
+        assert!(contract.acl_has_role(Role::PauseManager.into(), accounts(2)));
+        assert_eq!(contract.get_guard("jwt".to_string()), accounts(3));
+        assert!(!contract.pa_is_paused("ALL".to_string()));
+    }
+
+    #[test]
+    fn migrate_v1_keeps_pause() {
+        write_v1_state(true);
+        let contract = FastAuth::migrate();
+        assert!(contract.pa_is_paused("ALL".to_string()));
+        assert_eq!(contract.pa_all_paused(), Some(["ALL".to_string()].into()));
+    }
+
+    #[test]
+    fn migrate_current_layout() {
+        write_v1_state(false);
+        let mut contract = FastAuth::migrate();
+        contract.version = "0.2.0".to_string();
+        contract.guards.flush();
+        env::state_write(&contract);
+        let contract = FastAuth::migrate();
+        assert_eq!(contract.version, "0.2.0");
+        assert_eq!(contract.get_guard("jwt".to_string()), accounts(3));
+        assert!(contract.acl_has_role(Role::DAO.into(), accounts(1)));
+    }
+}
@@ -0,0 +1,108 @@
+use near_sdk::near;
+
+use crate::external_contracts::SignResponseAny;
+
+/// Reasons a signing request did not produce a signature
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[near(serializers=[json])]
+#[serde(rename_all = "snake_case")]
+pub enum SignErrorCode {
+    /// The guard call failed, e.g. the guard panicked or ran out of gas
+    GuardCallFailed,
+    /// The guard rejected the verify payload
+    VerificationRejected,
+    /// The guard returned an invalid user identifier
+    InvalidSub,
+    /// The guard was frozen while the verification was in flight
+    GuardFrozen,
+    /// The MPC contract did not return a signature
+    MpcFailed,
+    /// The user or the guard exceeded its rate limit
+    RateLimited,
+    /// The payloads would exceed the spending limits of the user and no valid second factor signature was presented
+    SpendingLimitExceeded,
+    /// The payloads need the approval of the guard quorum of the user, requested with `approve_sign`
+    ApprovalRequired,
+    /// The approval was recorded, and the request waits for the rest of the guard quorum
+    ApprovalPending,
+    /// The approval was not recorded, e.g. the identity is not an approver of the guard quorum
+    ApprovalRejected,
+}
+
+impl SignErrorCode {
+    /// Reason reported when the failure carries no more specific one
+    pub fn default_reason(&self) -> &'static str {
+        match self {
+            SignErrorCode::GuardCallFailed => "Guard verification failed",
+            SignErrorCode::VerificationRejected => "Guard verification rejected",
+            SignErrorCode::InvalidSub => "Invalid sub",
+            SignErrorCode::GuardFrozen => "Guard frozen during verification",
+            SignErrorCode::MpcFailed => "MPC signing failed",
+            SignErrorCode::RateLimited => "Rate limit exceeded",
+            SignErrorCode::SpendingLimitExceeded => "Spending limit exceeded",
+            SignErrorCode::ApprovalRequired => "Guard quorum approval required",
+            SignErrorCode::ApprovalPending => "Waiting for guard quorum approvals",
+            SignErrorCode::ApprovalRejected => "Approval rejected",
+        }
+    }
+}
+
+/// Result of a signing request
+///
+/// Serialized as `{"status":"signed","signature":...}` or
+/// `{"status":"failed","code":...,"reason":...}`.
+#[derive(Debug, Clone)]
+#[near(serializers=[json])]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum SignOutcome {
+    /// The MPC signature of the payload
+    Signed { signature: SignResponseAny },
+    /// The request failed and the deposit was refunded
+    Failed { code: SignErrorCode, reason: String },
+}
+
+impl SignOutcome {
+    /// Builds a failed outcome, falling back to the default reason of the code
+    /// # Arguments
+    /// * `code` - The error code
+    /// * `reason` - The reason reported by the guard, possibly empty
+    pub fn failed(code: SignErrorCode, reason: String) -> Self {
+        let reason = if reason.is_empty() { code.default_reason().to_string() } else { reason };
+        SignOutcome::Failed { code, reason }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::external_contracts::EdDsaSignResponse;
+    use near_sdk::serde_json::{self, json};
+
+    #[test]
+    fn failed_falls_back_to_default_reason() {
+        match SignOutcome::failed(SignErrorCode::MpcFailed, String::new()) {
+            SignOutcome::Failed { code, reason } => {
+                assert_eq!(code, SignErrorCode::MpcFailed);
+                assert_eq!(reason, "MPC signing failed");
+            }
+            SignOutcome::Signed { .. } => panic!("Expected a failed outcome"),
+        }
+    }
+
+    #[test]
+    fn outcome_json() {
+        let failed = SignOutcome::failed(SignErrorCode::VerificationRejected, "Token expired".to_string());
+        assert_eq!(
+            serde_json::to_value(&failed).unwrap(),
+            json!({ "status": "failed", "code": "verification_rejected", "reason": "Token expired" })
+        );
+
+        let signed = SignOutcome::Signed {
+            signature: SignResponseAny::EdDsa(EdDsaSignResponse { scheme: "Ed25519".to_string(), signature: vec![1, 2] }),
+        };
+        assert_eq!(
+            serde_json::to_value(&signed).unwrap(),
+            json!({ "status": "signed", "signature": { "scheme": "Ed25519", "signature": [1, 2] } })
+        );
+    }
+}
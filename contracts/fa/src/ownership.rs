@@ -0,0 +1,10 @@
+use base_jwt_guard::{Ownable, PendingOwner};
+use near_sdk::AccountId;
+
+use crate::FastAuth;
+
+impl Ownable for FastAuth {
+    fn ownership_mut(&mut self) -> (&mut AccountId, &mut Option<PendingOwner>) {
+        (&mut self.owner, &mut self.pending_owner)
+    }
+}
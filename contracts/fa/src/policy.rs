@@ -0,0 +1,187 @@
+use near_sdk::{near, AccountId, NearToken};
+
+use crate::transaction::{decode_sign_payload, total_deposit, AccessKeyPermission, Action};
+
+/// Maximum number of receivers or methods a policy can allow
+pub const MAX_POLICY_ENTRIES: usize = 100;
+
+/// The kinds of actions a NEAR transaction can contain
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[near(serializers=[borsh, json])]
+#[serde(rename_all = "snake_case")]
+pub enum ActionKind {
+    CreateAccount,
+    DeployContract,
+    FunctionCall,
+    Transfer,
+    Stake,
+    /// Adding a key with full access
+    AddFullAccessKey,
+    /// Adding a key restricted to function calls
+    AddFunctionCallKey,
+    DeleteKey,
+    DeleteAccount,
+    /// Nesting a signed delegate action, whose actions are checked too
+    Delegate,
+    DeployGlobalContract,
+    UseGlobalContract,
+}
+
+impl ActionKind {
+    /// Gets the kind of an action
+    pub fn of(action: &Action) -> Self {
+        match action {
+            Action::CreateAccount => ActionKind::CreateAccount,
+            Action::DeployContract { .. } => ActionKind::DeployContract,
+            Action::FunctionCall(_) => ActionKind::FunctionCall,
+            Action::Transfer { .. } => ActionKind::Transfer,
+            Action::Stake { .. } => ActionKind::Stake,
+            Action::AddKey { access_key, .. } => match access_key.permission {
+                AccessKeyPermission::FullAccess => ActionKind::AddFullAccessKey,
+                AccessKeyPermission::FunctionCall(_) => ActionKind::AddFunctionCallKey,
+            },
+            Action::DeleteKey { .. } => ActionKind::DeleteKey,
+            Action::DeleteAccount { .. } => ActionKind::DeleteAccount,
+            Action::Delegate(_) => ActionKind::Delegate,
+            Action::DeployGlobalContract { .. } => ActionKind::DeployGlobalContract,
+            Action::UseGlobalContract { .. } => ActionKind::UseGlobalContract,
+        }
+    }
+}
+
+/// What the payloads signed through a guard can execute. Payloads must decode as NEAR transactions or delegate actions
+#[derive(Debug, Clone, Default, PartialEq)]
+#[near(serializers=[borsh, json])]
+pub struct TransactionPolicy {
+    /// The accounts payloads can be sent to, any if none
+    pub allowed_receivers: Option<Vec<AccountId>>,
+    /// The methods function calls can call, any if none
+    pub allowed_methods: Option<Vec<String>>,
+    /// The maximum deposit a payload can transfer, attach or stake across its actions, any if none
+    pub max_deposit: Option<NearToken>,
+    /// The kinds of actions payloads cannot contain
+    pub denied_actions: Vec<ActionKind>,
+}
+
+impl TransactionPolicy {
+    /// Checks that the policy can be stored
+    /// # Returns
+    /// * An error describing why the policy is invalid
+    pub fn validate(&self) -> Result<(), String> {
+        let receivers = self.allowed_receivers.as_ref().map_or(0, Vec::len);
+        let methods = self.allowed_methods.as_ref().map_or(0, Vec::len);
+        if receivers > MAX_POLICY_ENTRIES || methods > MAX_POLICY_ENTRIES {
+            return Err(format!("Policy cannot allow more than {} receivers or methods", MAX_POLICY_ENTRIES));
+        }
+        Ok(())
+    }
+
+    /// Checks that a payload complies with the policy
+    /// # Arguments
+    /// * `sign_payload` - The payload to sign
+    /// # Returns
+    /// * An error describing the violation
+    pub fn check(&self, sign_payload: &[u8]) -> Result<(), String> {
+        let payload = decode_sign_payload(sign_payload)?;
+        self.check_actions(&payload.receiver_id, &payload.actions)?;
+        let deposit = total_deposit(&payload.actions);
+        match self.max_deposit {
+            Some(max_deposit) if deposit > max_deposit.as_yoctonear() => {
+                Err(format!("Deposit of {} yoctoNEAR exceeds the maximum of {}", deposit, max_deposit.as_yoctonear()))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks the receiver and actions of a payload, including the ones of nested delegate actions
+    /// # Returns
+    /// * An error describing the violation
+    fn check_actions(&self, receiver_id: &AccountId, actions: &[Action]) -> Result<(), String> {
+        if let Some(allowed_receivers) = &self.allowed_receivers {
+            if !allowed_receivers.contains(receiver_id) {
+                return Err(format!("Receiver {} is not allowed", receiver_id));
+            }
+        }
+
+        for action in actions {
+            let kind = ActionKind::of(action);
+            if self.denied_actions.contains(&kind) {
+                return Err(format!("Action {:?} is not allowed", kind));
+            }
+            match action {
+                Action::FunctionCall(call) => {
+                    if let Some(allowed_methods) = &self.allowed_methods {
+                        if !allowed_methods.contains(&call.method_name) {
+                            return Err(format!("Method {} is not allowed", call.method_name));
+                        }
+                    }
+                }
+                Action::Delegate(signed) => {
+                    let delegate_action = &signed.delegate_action;
+                    self.check_actions(&delegate_action.receiver_id, &delegate_action.actions)?;
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transaction::{AccessKey, FunctionCallAction, TransactionV0, TxPublicKey};
+    use near_sdk::borsh;
+
+    fn transaction(receiver_id: &str, actions: Vec<Action>) -> Vec<u8> {
+        borsh::to_vec(&TransactionV0 {
+            signer_id: "alice.near".parse().unwrap(),
+            public_key: TxPublicKey::Ed25519([1; 32]),
+            nonce: 1,
+            receiver_id: receiver_id.parse().unwrap(),
+            block_hash: [2; 32],
+            actions,
+        })
+        .unwrap()
+    }
+
+    fn function_call(method_name: &str, deposit: u128) -> Action {
+        Action::FunctionCall(FunctionCallAction { method_name: method_name.to_string(), args: vec![], gas: 0, deposit })
+    }
+
+    #[test]
+    fn check_receivers_and_methods() {
+        let policy = TransactionPolicy {
+            allowed_receivers: Some(vec!["token.near".parse().unwrap()]),
+            allowed_methods: Some(vec!["ft_transfer".to_string()]),
+            ..Default::default()
+        };
+        assert!(policy.check(&transaction("token.near", vec![function_call("ft_transfer", 1)])).is_ok());
+        assert_eq!(
+            policy.check(&transaction("other.near", vec![function_call("ft_transfer", 1)])),
+            Err("Receiver other.near is not allowed".to_string())
+        );
+        assert_eq!(
+            policy.check(&transaction("token.near", vec![function_call("storage_withdraw", 1)])),
+            Err("Method storage_withdraw is not allowed".to_string())
+        );
+    }
+
+    #[test]
+    fn check_denied_actions_and_deposit() {
+        let policy = TransactionPolicy {
+            max_deposit: Some(NearToken::from_yoctonear(10)),
+            denied_actions: vec![ActionKind::DeleteAccount, ActionKind::AddFullAccessKey],
+            ..Default::default()
+        };
+        let add_key = |permission| Action::AddKey {
+            public_key: TxPublicKey::Ed25519([3; 32]),
+            access_key: AccessKey { nonce: 0, permission },
+        };
+        assert!(policy.check(&transaction("bob.near", vec![Action::Transfer { deposit: 4 }, function_call("deposit", 6)])).is_ok());
+        assert!(policy.check(&transaction("bob.near", vec![Action::Transfer { deposit: 4 }, function_call("deposit", 7)])).is_err());
+        assert!(policy.check(&transaction("alice.near", vec![add_key(AccessKeyPermission::FullAccess)])).is_err());
+        assert!(policy.check(&transaction("alice.near", vec![Action::DeleteAccount { beneficiary_id: "bob.near".parse().unwrap() }])).is_err());
+        assert!(policy.check(&[0; 32]).is_err());
+    }
+}
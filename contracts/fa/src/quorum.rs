@@ -0,0 +1,480 @@
+use near_plugins::{pause, Pausable};
+use near_sdk::{borsh, env, near, AccountId, NearToken, Promise, PromiseError, PromiseOrValue};
+
+use crate::events::FastAuthEvent;
+use crate::external_contracts::external_guard;
+use crate::fees::SignCost;
+use crate::hashing::HashingMode;
+use crate::identity::format_identity;
+use crate::outcome::{SignErrorCode, SignOutcome};
+use crate::spending::{payload_spend, SpendingLimits};
+use crate::{FastAuth, FastAuthExt, SignatureAlgorithm};
+
+/// Maximum number of identities a guard quorum can have
+pub const MAX_QUORUM_APPROVERS: usize = 5;
+
+/// Milliseconds after the first approval of a request during which the rest of the quorum can approve it
+pub const APPROVAL_EXPIRY_MS: u64 = 24 * 60 * 60 * 1000;
+
+/// An identity of a user, verified by a guard, that can approve requests
+#[derive(Debug, Clone, PartialEq)]
+#[near(serializers=[borsh, json])]
+pub struct Approver {
+    /// The guard ID verifying the JWTs of the identity
+    pub guard_id: String,
+    /// The user identifier
+    pub sub: String,
+}
+
+/// The identities, each verified by its own guard, that must approve the high-value requests of a derivation path
+#[derive(Debug, Clone, PartialEq)]
+#[near(serializers=[borsh, json])]
+pub struct GuardQuorum {
+    /// The identities that can approve
+    pub approvers: Vec<Approver>,
+    /// The number of approvers a request needs
+    pub threshold: u32,
+    /// Payloads spending less than this much of their asset skip approval. Every payload needs approval if none,
+    /// and so do the payloads of the assets without a value and the payloads whose spend cannot be decoded
+    pub min_value: Option<SpendingLimits>,
+}
+
+impl GuardQuorum {
+    /// Checks that the quorum is well formed
+    /// # Returns
+    /// * An error describing why the quorum is invalid
+    pub fn validate(&self) -> Result<(), String> {
+        if self.approvers.is_empty() || self.approvers.len() > MAX_QUORUM_APPROVERS {
+            return Err(format!("Guard quorum must have between 1 and {} approvers", MAX_QUORUM_APPROVERS));
+        }
+        for (index, approver) in self.approvers.iter().enumerate() {
+            if approver.guard_id.is_empty() || approver.sub.is_empty() || approver.sub.contains('#') {
+                return Err(format!("Invalid approver '{}#{}'", approver.guard_id, approver.sub));
+            }
+            if self.approvers[..index].contains(approver) {
+                return Err(format!("Duplicate approver '{}#{}'", approver.guard_id, approver.sub));
+            }
+        }
+        if self.threshold == 0 || self.threshold as usize > self.approvers.len() {
+            return Err("Guard quorum threshold must be between 1 and the number of approvers".to_string());
+        }
+        Ok(())
+    }
+
+    /// Checks if a payload must be approved by the quorum
+    /// # Arguments
+    /// * `sign_payload` - The payload to sign
+    /// * `hashing` - How the payload is hashed before signing
+    pub fn requires_approval(&self, sign_payload: &[u8], hashing: HashingMode) -> bool {
+        let Some(min_value) = &self.min_value else {
+            return true;
+        };
+        match payload_spend(sign_payload, hashing) {
+            Some((asset, amount)) => min_value.limit(&asset).is_none_or(|min_value| amount >= min_value),
+            None => true,
+        }
+    }
+
+    /// Counts the approvals of the current approvers
+    /// # Arguments
+    /// * `approved` - The identities that approved a request
+    pub fn approvals(&self, approved: &[Approver]) -> u32 {
+        approved.iter().filter(|approver| self.approvers.contains(approver)).count() as u32
+    }
+}
+
+/// A signature request for the derivation path of a user, signed once its guard quorum approved it
+#[derive(Debug, Clone, PartialEq)]
+#[near(serializers=[borsh, json])]
+pub struct SignApproval {
+    /// The guard ID the user signs through
+    pub guard_id: String,
+    /// The user identifier
+    pub sub: String,
+    /// The data to sign
+    pub sign_payload: Vec<u8>,
+    /// The signature algorithm to use ("secp256k1", "ecdsa", or "eddsa")
+    pub algorithm: String,
+    /// How the payload is hashed before signing, sha256 by default
+    pub hashing: Option<String>,
+}
+
+impl SignApproval {
+    /// Computes the commitment the JWT of each approver must carry in its `fatxn` claim
+    /// # Returns
+    /// * The SHA-256 of the borsh serialized request
+    pub fn commitment(&self) -> Vec<u8> {
+        let message = borsh::to_vec(&("approve_sign", self)).expect("Failed to serialize sign approval");
+        env::sha256(&message)
+    }
+}
+
+/// A change of the guard quorum of the derivation path of a user
+#[derive(Debug, Clone, PartialEq)]
+#[near(serializers=[borsh, json])]
+pub struct QuorumUpdate {
+    /// The guard ID the user signs through
+    pub guard_id: String,
+    /// The user identifier
+    pub sub: String,
+    /// The quorum to set, none to remove it
+    pub quorum: Option<GuardQuorum>,
+}
+
+impl QuorumUpdate {
+    /// Computes the commitment the JWT of each approver must carry in its `fatxn` claim
+    /// # Returns
+    /// * The SHA-256 of the borsh serialized update
+    pub fn commitment(&self) -> Vec<u8> {
+        let message = borsh::to_vec(&("update_guard_quorum", self)).expect("Failed to serialize quorum update");
+        env::sha256(&message)
+    }
+}
+
+/// The approvals collected for a request until its quorum is met or it expires
+#[derive(Debug, Clone, PartialEq)]
+#[near(serializers=[borsh, json])]
+pub struct PendingApproval {
+    /// The derivation path the request acts on
+    pub path: String,
+    /// The identities that approved the request
+    pub approvers: Vec<Approver>,
+    /// Block timestamp in milliseconds at which the approvals expire
+    pub expires_at: u64,
+}
+
+impl PendingApproval {
+    /// Starts collecting approvals in the current block
+    pub fn new(path: String) -> Self {
+        Self {
+            path,
+            approvers: Vec::new(),
+            expires_at: env::block_timestamp_ms().saturating_add(APPROVAL_EXPIRY_MS),
+        }
+    }
+
+    /// Checks if the approvals still count
+    /// # Arguments
+    /// * `now` - The current block timestamp in milliseconds
+    pub fn is_active(&self, now: u64) -> bool {
+        now < self.expires_at
+    }
+}
+
+#[near]
+impl FastAuth {
+    /// Computes the commitment the JWT of each approver must carry in its `fatxn` claim to approve a signature
+    /// # Arguments
+    /// * `request` - The signature request
+    pub fn sign_approval_commitment(&self, request: SignApproval) -> Vec<u8> {
+        request.commitment()
+    }
+
+    /// Computes the commitment the JWT of each approver must carry in its `fatxn` claim to approve a quorum update
+    /// # Arguments
+    /// * `request` - The quorum update
+    pub fn quorum_update_commitment(&self, request: QuorumUpdate) -> Vec<u8> {
+        request.commitment()
+    }
+
+    /// Gets the guard quorum of the derivation path of a user
+    /// # Arguments
+    /// * `guard_id` - The guard ID the user signs through
+    /// * `sub` - The user identifier
+    #[pause]
+    pub fn get_guard_quorum(&self, guard_id: String, sub: String) -> Option<GuardQuorum> {
+        self.guard_quorums.get(&self.derivation_path(&guard_id, &sub)).cloned()
+    }
+
+    /// Gets the approvals collected for a request
+    /// # Arguments
+    /// * `commitment` - The commitment of the request
+    /// # Returns
+    /// * The approvals, none if the request was never approved, was executed or expired
+    #[pause]
+    pub fn get_pending_approval(&self, commitment: Vec<u8>) -> Option<PendingApproval> {
+        let now = env::block_timestamp_ms();
+        self.pending_approvals.get(&commitment).filter(|pending| pending.is_active(now)).cloned()
+    }
+
+    /// Sets or removes the guard quorum of the derivation path of a user
+    /// # Arguments
+    /// * `guard_id` - The guard ID of the approving identity
+    /// * `verify_payload` - A JWT of the approving identity, whose `fatxn` claim must be the quorum update commitment
+    /// * `request` - The user and the quorum to set
+    /// # Returns
+    /// * Promise for the verification of the JWT, resolving to whether the quorum was updated
+    /// # Notes
+    /// * Without a quorum, the JWT of the user itself sets it. With one, the update is applied once the quorum approved it
+    /// # Panics
+    /// * If a guard does not exist or is frozen, or the sub is invalid
+    /// * If the quorum is invalid
+    #[pause]
+    pub fn update_guard_quorum(&self, guard_id: String, verify_payload: String, request: QuorumUpdate) -> Promise {
+        let guard_address = self.verifying_guard(&guard_id);
+        self.verifying_guard(&request.guard_id);
+        assert!(self.verify_sub(request.sub.clone()), "Invalid sub");
+        if let Some(Err(err)) = request.quorum.as_ref().map(GuardQuorum::validate) {
+            env::panic_str(&err);
+        }
+
+        external_guard::ext(guard_address)
+            .verify(guard_id.clone(), verify_payload, request.commitment(), env::predecessor_account_id())
+            .then(Self::ext(env::current_account_id())
+                .on_update_guard_quorum_callback(guard_id, request)
+        )
+    }
+
+    /// Records the approval of a quorum update once the JWT is verified, and applies the update once approved
+    /// # Arguments
+    /// * `guard_id` - The guard ID of the approving identity
+    /// * `request` - The user and the quorum to set
+    /// * `call_result` - Verification result of the approving identity
+    /// # Returns
+    /// * Whether the quorum was updated
+    #[private]
+    pub fn on_update_guard_quorum_callback(&mut self, guard_id: String, request: QuorumUpdate, #[callback_result] call_result: Result<(bool, String, String), PromiseError>) -> bool {
+        let sub = match self.verified_user(&guard_id, call_result) {
+            Ok(sub) => sub,
+            Err(outcome) => {
+                if let SignOutcome::Failed { reason, .. } = outcome {
+                    env::log_str(&reason);
+                }
+                return false;
+            }
+        };
+        let path = self.derivation_path(&request.guard_id, &request.sub);
+        if self.guard_quorums.contains_key(&path) {
+            match self.record_approval(request.commitment(), &path, Approver { guard_id, sub }) {
+                Ok((approvals, threshold)) if approvals >= threshold => {}
+                Ok(_) => return false,
+                Err(err) => {
+                    env::log_str(&err);
+                    return false;
+                }
+            }
+        } else if guard_id != request.guard_id || sub != request.sub {
+            env::log_str("Only the user can set the first guard quorum of its derivation path");
+            return false;
+        }
+
+        FastAuthEvent::GuardQuorumChanged { path: &path, quorum: request.quorum.as_ref() }.emit();
+        match request.quorum {
+            Some(quorum) => self.guard_quorums.insert(path, quorum),
+            None => self.guard_quorums.remove(&path),
+        };
+        true
+    }
+
+    /// Approves a signature request for the derivation path of a user, signing it once the guard quorum approved it
+    /// # Arguments
+    /// * `guard_id` - The guard ID of the approving identity
+    /// * `verify_payload` - A JWT of the approving identity, whose `fatxn` claim must be the sign approval commitment
+    /// * `request` - The user and the payload to sign
+    /// # Returns
+    /// * Promise for verification then, once approved, signing, resolving to a `SignOutcome`.
+    ///   Approvals that do not meet the threshold resolve to an `approval_pending` outcome
+    /// # Panics
+    /// * If a guard does not exist or is frozen, or the sub is invalid
+    /// * If the algorithm, hashing mode or payload is not allowed
+    /// # Notes
+    /// * Requires an attached deposit for MPC costs, refunded unless the approval releases the signature
+    #[payable]
+    #[pause]
+    pub fn approve_sign(&mut self, guard_id: String, verify_payload: String, request: SignApproval) -> Promise {
+        let attached_deposit = env::attached_deposit();
+        let caller = env::predecessor_account_id();
+
+        let signature_algorithm = match SignatureAlgorithm::parse_str(&request.algorithm) {
+            Ok(alg) => alg,
+            Err(err) => env::panic_str(&err),
+        };
+        let hashing_mode = self.hashing_mode(request.hashing.clone(), &signature_algorithm, std::slice::from_ref(&request.sign_payload));
+
+        let guard_address = self.verifying_guard(&guard_id);
+        self.verifying_guard(&request.guard_id);
+        assert!(self.verify_sub(request.sub.clone()), "Invalid sub");
+        let guard_prefix = self.get_guard_prefix(request.guard_id.clone());
+        self.assert_guard_supports(&guard_prefix, &signature_algorithm);
+        self.assert_guard_policy_allows(&guard_prefix, std::slice::from_ref(&request.sign_payload));
+        let sign_cost = self.sign_cost(&signature_algorithm);
+        Self::assert_deposit_covers(attached_deposit, &sign_cost, 1);
+
+        external_guard::ext(guard_address)
+            .verify(guard_id.clone(), verify_payload, request.commitment(), env::predecessor_account_id())
+            .then(Self::ext(env::current_account_id())
+                .on_approve_sign_callback(guard_id, request, attached_deposit, sign_cost, signature_algorithm, hashing_mode, caller)
+        )
+    }
+
+    /// Records the approval of a signature request once the JWT is verified, and signs it once approved
+    /// # Arguments
+    /// * `guard_id` - The guard ID of the approving identity
+    /// * `request` - The user and the payload to sign
+    /// * `attached_deposit` - Deposit attached to the request
+    /// * `sign_cost` - MPC deposit and fee of the request
+    /// * `algorithm` - The signature algorithm to use
+    /// * `hashing` - How the payload is hashed before signing
+    /// * `caller` - The original caller to refund deposit to
+    /// * `call_result` - Verification result of the approving identity
+    /// # Returns
+    /// * Promise for MPC signing, or the failed outcome if the request is not signed yet
+    #[private]
+    pub fn on_approve_sign_callback(&mut self, guard_id: String, request: SignApproval, attached_deposit: NearToken, sign_cost: SignCost, algorithm: SignatureAlgorithm, hashing: HashingMode, caller: AccountId, #[callback_result] call_result: Result<(bool, String, String), PromiseError>) -> PromiseOrValue<SignOutcome> {
+        let path = self.derivation_path(&request.guard_id, &request.sub);
+        let (user, spend) = match self
+            .verified_user(&guard_id, call_result)
+            .and_then(|sub| match self.record_approval(request.commitment(), &path, Approver { guard_id: guard_id.clone(), sub }) {
+                Ok((approvals, threshold)) if approvals >= threshold => Ok(request.sub.clone()),
+                Ok((approvals, threshold)) => Err(SignOutcome::failed(SignErrorCode::ApprovalPending, format!("Approved by {} of {} guards", approvals, threshold))),
+                Err(err) => Err(SignOutcome::failed(SignErrorCode::ApprovalRejected, err)),
+            })
+            .and_then(|user| self.consume_sign_limits(&request.guard_id, user, std::slice::from_ref(&request.sign_payload), hashing, &request.sign_payload, None))
+        {
+            Ok((user, mut spends)) => (user, spends.pop().flatten()),
+            Err(outcome) => {
+                let _ = Promise::new(caller).transfer(attached_deposit);
+                return PromiseOrValue::Value(outcome);
+            }
+        };
+
+        FastAuthEvent::SignRequested {
+            guard_id: &request.guard_id,
+            caller: &caller,
+            algorithm: &algorithm,
+            payloads: 1,
+            deposit: attached_deposit,
+        }.emit();
+
+        PromiseOrValue::Promise(
+            self.sign_request(request.guard_id.clone(), user, request.sign_payload, sign_cost.mpc_deposit, algorithm, hashing)
+                .then(Self::ext(env::current_account_id())
+                    .on_sign_callback(request.guard_id, caller, attached_deposit, sign_cost, spend)
+            )
+        )
+    }
+
+    /// Checks that the payloads of a verified user can be signed without the approval of its guard quorum
+    /// # Arguments
+    /// * `guard_id` - The guard ID used for JWT verification
+    /// * `user` - The verified user identifier
+    /// * `sign_payloads` - The payloads to sign
+    /// * `hashing` - How the payloads are hashed before signing
+    /// # Returns
+    /// * The user, or the failed outcome of the request if a payload needs approval
+    pub(crate) fn check_guard_quorum(&self, guard_id: &str, user: String, sign_payloads: &[Vec<u8>], hashing: HashingMode) -> Result<String, SignOutcome> {
+        match self.guard_quorums.get(&self.derivation_path(guard_id, &user)) {
+            Some(quorum) if sign_payloads.iter().any(|sign_payload| quorum.requires_approval(sign_payload, hashing)) => {
+                Err(SignOutcome::failed(SignErrorCode::ApprovalRequired, String::new()))
+            }
+            _ => Ok(user),
+        }
+    }
+
+    /// Records the approval of a request by an identity of the guard quorum of a derivation path
+    /// # Arguments
+    /// * `commitment` - The commitment of the request
+    /// * `path` - The derivation path the request acts on
+    /// * `approver` - The verified approving identity
+    /// # Returns
+    /// * The approvals of the request and the threshold of the quorum, the approvals being cleared once they meet it
+    fn record_approval(&mut self, commitment: Vec<u8>, path: &str, approver: Approver) -> Result<(u32, u32), String> {
+        let Some(quorum) = self.guard_quorums.get(path).cloned() else {
+            return Err("No guard quorum is set for the derivation path".to_string());
+        };
+        let identity = format_identity(&approver.guard_id, &approver.sub);
+        if !quorum.approvers.contains(&approver) {
+            return Err(format!("Identity '{}' is not an approver of the guard quorum", identity));
+        }
+
+        let now = env::block_timestamp_ms();
+        let mut pending = self
+            .pending_approvals
+            .get(&commitment)
+            .filter(|pending| pending.is_active(now) && pending.path == path)
+            .cloned()
+            .unwrap_or_else(|| PendingApproval::new(path.to_string()));
+        if !pending.approvers.contains(&approver) {
+            pending.approvers.push(approver);
+        }
+        let approvals = quorum.approvals(&pending.approvers);
+        FastAuthEvent::ApprovalRecorded {
+            path,
+            commitment: &commitment,
+            approver: &identity,
+            approvals,
+            threshold: quorum.threshold,
+        }.emit();
+
+        if approvals >= quorum.threshold {
+            self.pending_approvals.remove(&commitment);
+        } else {
+            self.pending_approvals.insert(commitment, pending);
+        }
+        Ok((approvals, quorum.threshold))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spending::Asset;
+    use near_sdk::json_types::U128;
+    use near_sdk::NearToken;
+
+    fn approver(guard_id: &str, sub: &str) -> Approver {
+        Approver { guard_id: guard_id.to_string(), sub: sub.to_string() }
+    }
+
+    fn quorum(threshold: u32) -> GuardQuorum {
+        GuardQuorum {
+            approvers: vec![approver("jwt#google", "alice"), approver("jwt#apple", "a1"), approver("passkey", "k1")],
+            threshold,
+            min_value: None,
+        }
+    }
+
+    #[test]
+    fn validate_quorum() {
+        assert!(quorum(2).validate().is_ok());
+        assert!(quorum(0).validate().is_err());
+        assert!(quorum(4).validate().is_err());
+
+        let mut duplicated = quorum(2);
+        duplicated.approvers.push(approver("passkey", "k1"));
+        assert_eq!(duplicated.validate(), Err("Duplicate approver 'passkey#k1'".to_string()));
+        assert!(GuardQuorum { approvers: vec![], threshold: 0, min_value: None }.validate().is_err());
+    }
+
+    #[test]
+    fn count_current_approvers() {
+        let approved = vec![approver("jwt#google", "alice"), approver("jwt#google", "bob"), approver("passkey", "k1")];
+        assert_eq!(quorum(2).approvals(&approved), 2);
+    }
+
+    #[test]
+    fn approval_depends_on_value() {
+        let mut quorum = quorum(2);
+        assert!(quorum.requires_approval(&[1, 2, 3], HashingMode::Sha256));
+
+        quorum.min_value = Some(SpendingLimits { near: Some(NearToken::from_near(1)), evm: None });
+        // Payloads whose spend cannot be decoded still need approval
+        assert!(quorum.requires_approval(&[1, 2, 3], HashingMode::Sha256));
+        assert!(quorum.requires_approval(&[1, 2, 3], HashingMode::Raw));
+    }
+
+    #[test]
+    fn approval_required_for_asset_without_min_value() {
+        let mut quorum = quorum(2);
+        quorum.min_value = Some(SpendingLimits { near: Some(NearToken::from_near(1)), evm: None });
+        // Legacy EVM transaction on chain 56 sending 1 ETH
+        let mut transaction = vec![0xe7, 0x09, 0x01, 0x82, 0x52, 0x08, 0x94];
+        transaction.extend([0x11; 20]);
+        transaction.extend([0x88, 0x0d, 0xe0, 0xb6, 0xb3, 0xa7, 0x64, 0x00, 0x00, 0x80, 0x38, 0x80, 0x80]);
+        assert_eq!(payload_spend(&transaction, HashingMode::Keccak256), Some((Asset::Evm { chain_id: 56 }, 1_000_000_000_000_000_000)));
+        assert!(quorum.requires_approval(&transaction, HashingMode::Keccak256));
+
+        quorum.min_value = Some(SpendingLimits { near: None, evm: Some(U128(2_000_000_000_000_000_000)) });
+        assert!(!quorum.requires_approval(&transaction, HashingMode::Keccak256));
+    }
+}
@@ -0,0 +1,100 @@
+use near_sdk::near;
+
+/// Maximum number of requests a rate limit can allow within its window
+pub const MAX_RATE_LIMIT_REQUESTS: u32 = 100;
+
+/// A maximum number of signature requests within a sliding window
+#[derive(Debug, Clone, PartialEq)]
+#[near(serializers=[borsh, json])]
+pub struct RateLimit {
+    /// The maximum number of requests within the window
+    pub max_requests: u32,
+    /// The length of the window in milliseconds
+    pub window_ms: u64,
+}
+
+impl RateLimit {
+    /// Checks that the limit can be enforced
+    /// # Returns
+    /// * An error describing why the limit is invalid
+    pub fn validate(&self) -> Result<(), String> {
+        if self.max_requests == 0 || self.max_requests > MAX_RATE_LIMIT_REQUESTS {
+            return Err(format!("Rate limit must allow between 1 and {} requests", MAX_RATE_LIMIT_REQUESTS));
+        }
+        if self.window_ms == 0 {
+            return Err("Rate limit window cannot be empty".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// The rate limits of signature requests, none meaning unlimited
+#[derive(Debug, Clone, Default, PartialEq)]
+#[near(serializers=[borsh, json])]
+pub struct RateLimits {
+    /// Limit of the requests of a single user, identified by guard ID and sub
+    pub subject: Option<RateLimit>,
+    /// Limit of the requests verified by a single guard
+    pub guard: Option<RateLimit>,
+}
+
+/// The block timestamps in milliseconds of the requests counted against a rate limit, oldest first
+#[derive(Debug, Clone, Default)]
+#[near(serializers=[borsh])]
+pub struct RequestWindow {
+    timestamps: Vec<u64>,
+}
+
+impl RequestWindow {
+    /// Gets the number of requests that can still be made
+    /// # Arguments
+    /// * `limit` - The rate limit of the requests
+    /// * `now` - The current block timestamp in milliseconds
+    pub fn remaining(&self, limit: &RateLimit, now: u64) -> u32 {
+        let counted = self.timestamps.iter().filter(|timestamp| Self::in_window(**timestamp, limit, now)).count() as u32;
+        limit.max_requests.saturating_sub(counted)
+    }
+
+    /// Counts new requests, forgetting the ones that left the window
+    /// # Arguments
+    /// * `limit` - The rate limit of the requests
+    /// * `now` - The current block timestamp in milliseconds
+    /// * `requests` - The number of requests to count
+    pub fn record(&mut self, limit: &RateLimit, now: u64, requests: u32) {
+        self.timestamps.retain(|timestamp| Self::in_window(*timestamp, limit, now));
+        self.timestamps.extend(std::iter::repeat_n(now, requests as usize));
+    }
+
+    fn in_window(timestamp: u64, limit: &RateLimit, now: u64) -> bool {
+        timestamp.saturating_add(limit.window_ms) > now
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn window_slides() {
+        let limit = RateLimit { max_requests: 3, window_ms: 1_000 };
+        let mut window = RequestWindow::default();
+        assert_eq!(window.remaining(&limit, 0), 3);
+
+        window.record(&limit, 0, 2);
+        window.record(&limit, 500, 1);
+        assert_eq!(window.remaining(&limit, 999), 0);
+        assert_eq!(window.remaining(&limit, 1_000), 2);
+        assert_eq!(window.remaining(&limit, 1_500), 3);
+
+        window.record(&limit, 1_200, 1);
+        assert_eq!(window.timestamps, vec![500, 1_200]);
+    }
+
+    #[test]
+    fn validate_bounds() {
+        assert!(RateLimit { max_requests: 10, window_ms: 60_000 }.validate().is_ok());
+        assert!(RateLimit { max_requests: 0, window_ms: 60_000 }.validate().is_err());
+        assert!(RateLimit { max_requests: 101, window_ms: 60_000 }.validate().is_err());
+        assert!(RateLimit { max_requests: 10, window_ms: 0 }.validate().is_err());
+    }
+}
@@ -0,0 +1,548 @@
+use near_plugins::{pause, Pausable};
+use near_sdk::{borsh, env, near, CurveType, Promise, PromiseError, PublicKey};
+
+use crate::events::FastAuthEvent;
+use crate::external_contracts::external_guard;
+use crate::hashing::HashingMode;
+use crate::identity::format_identity;
+use crate::outcome::SignOutcome;
+use crate::policy::TransactionPolicy;
+use crate::{FastAuth, FastAuthExt, SignatureAlgorithm};
+
+/// Maximum milliseconds a session can last
+pub const MAX_SESSION_DURATION_MS: u64 = 7 * 24 * 60 * 60 * 1000;
+
+/// Maximum number of signatures a session can request
+pub const MAX_SESSION_SIGNATURES: u32 = 1_000;
+
+/// Maximum number of sessions an identity can have at once
+pub const MAX_SESSIONS_PER_IDENTITY: usize = 10;
+
+/// What a session can sign, on top of what the guard allows
+#[derive(Debug, Clone, Default, PartialEq)]
+#[near(serializers=[borsh, json])]
+pub struct SessionScope {
+    /// The algorithms the session can sign with, any the guard supports if none
+    pub algorithms: Option<Vec<SignatureAlgorithm>>,
+    /// What the payloads signed with the session can execute, anything the guard allows if none
+    pub policy: Option<TransactionPolicy>,
+}
+
+impl SessionScope {
+    /// Checks that a request is within the scope
+    /// # Arguments
+    /// * `algorithm` - The signature algorithm of the request
+    /// * `sign_payload` - The payload to sign
+    /// # Returns
+    /// * An error describing why the request is out of scope
+    pub fn check(&self, algorithm: &SignatureAlgorithm, sign_payload: &[u8]) -> Result<(), String> {
+        if let Some(algorithms) = &self.algorithms {
+            if !algorithms.contains(algorithm) {
+                return Err(format!("Session cannot sign with algorithm {:?}", algorithm));
+            }
+        }
+        match &self.policy {
+            Some(policy) => policy.check(sign_payload),
+            None => Ok(()),
+        }
+    }
+}
+
+/// A session key to authorize, which the JWT creating the session commits to
+#[derive(Debug, Clone, PartialEq)]
+#[near(serializers=[borsh, json])]
+pub struct SessionRequest {
+    /// The ed25519 or secp256k1 session key
+    pub public_key: PublicKey,
+    /// Block timestamp in milliseconds at which the session expires
+    pub expires_at: u64,
+    /// The number of signatures the session can request
+    pub max_signatures: u32,
+    /// What the session can sign
+    pub scope: SessionScope,
+}
+
+impl SessionRequest {
+    /// Checks that the session can be created
+    /// # Arguments
+    /// * `now` - The current block timestamp in milliseconds
+    /// # Returns
+    /// * An error describing why the session is invalid
+    pub fn validate(&self, now: u64) -> Result<(), String> {
+        if self.expires_at <= now || self.expires_at > now.saturating_add(MAX_SESSION_DURATION_MS) {
+            return Err(format!("Session must expire within {} ms", MAX_SESSION_DURATION_MS));
+        }
+        if self.max_signatures == 0 || self.max_signatures > MAX_SESSION_SIGNATURES {
+            return Err(format!("Session must allow between 1 and {} signatures", MAX_SESSION_SIGNATURES));
+        }
+        if let Some(algorithms) = &self.scope.algorithms {
+            if algorithms.is_empty() {
+                return Err("Session must allow at least one algorithm".to_string());
+            }
+        }
+        match &self.scope.policy {
+            Some(policy) => policy.validate(),
+            None => Ok(()),
+        }
+    }
+
+    /// Computes the commitment a JWT must carry in its `fatxn` claim to create the session
+    /// # Returns
+    /// * The SHA-256 of the borsh serialized request
+    pub fn commitment(&self) -> Vec<u8> {
+        let message = borsh::to_vec(&("session", self)).expect("Failed to serialize session request");
+        env::sha256(&message)
+    }
+}
+
+/// An ephemeral key signing on behalf of a user until it expires, is used up or revoked
+#[derive(Debug, Clone, PartialEq)]
+#[near(serializers=[borsh, json])]
+pub struct Session {
+    /// The guard ID the JWT creating the session was verified with
+    pub guard_id: String,
+    /// The verified user identifier
+    pub sub: String,
+    /// Block timestamp in milliseconds at which the session was created
+    pub created_at: u64,
+    /// Block timestamp in milliseconds at which the session expires
+    pub expires_at: u64,
+    /// The number of signatures the session can request
+    pub max_signatures: u32,
+    /// The number of signatures requested so far, which is also the nonce of the next request
+    pub signatures: u32,
+    /// What the session can sign
+    pub scope: SessionScope,
+}
+
+impl Session {
+    /// Creates a session for a verified user in the current block
+    pub fn new(guard_id: String, sub: String, request: SessionRequest) -> Self {
+        Self {
+            guard_id,
+            sub,
+            created_at: env::block_timestamp_ms(),
+            expires_at: request.expires_at,
+            max_signatures: request.max_signatures,
+            signatures: 0,
+            scope: request.scope,
+        }
+    }
+
+    /// Checks if the session can still sign
+    /// # Arguments
+    /// * `now` - The current block timestamp in milliseconds
+    pub fn is_active(&self, now: u64) -> bool {
+        now < self.expires_at && self.signatures < self.max_signatures
+    }
+
+    /// Computes the message the session key signs to request its next signature
+    /// # Arguments
+    /// * `public_key` - The session key
+    /// * `sign_payload` - The payload to sign
+    /// * `algorithm` - The signature algorithm of the request
+    /// * `hashing` - How the payload is hashed before signing
+    /// # Returns
+    /// * The SHA-256 of the borsh serialized contract account, session key, creation time, nonce, payload, algorithm and hashing mode
+    pub fn sign_message(&self, public_key: &PublicKey, sign_payload: &[u8], algorithm: &SignatureAlgorithm, hashing: HashingMode) -> Vec<u8> {
+        let message = borsh::to_vec(&(env::current_account_id(), public_key, self.created_at, self.signatures, sign_payload, algorithm, hashing))
+            .expect("Failed to serialize session message");
+        env::sha256(&message)
+    }
+}
+
+/// Computes the commitment a JWT must carry in its `fatxn` claim to revoke a session
+/// # Arguments
+/// * `public_key` - The session key
+/// # Returns
+/// * The SHA-256 of the borsh serialized session key
+pub fn revoke_commitment(public_key: &PublicKey) -> Vec<u8> {
+    let message = borsh::to_vec(&("revoke_session", public_key)).expect("Failed to serialize session key");
+    env::sha256(&message)
+}
+
+/// Verifies a signature of a session key
+/// # Arguments
+/// * `public_key` - The session key
+/// * `message` - The signed 32 bytes message
+/// * `signature` - The 64 bytes ed25519 signature, or the 65 bytes secp256k1 signature followed by its recovery ID
+pub fn verify_session_signature(public_key: &PublicKey, message: &[u8], signature: &[u8]) -> bool {
+    let key = &public_key.as_bytes()[1..];
+    match public_key.curve_type() {
+        CurveType::ED25519 => match (<&[u8; 64]>::try_from(signature), <&[u8; 32]>::try_from(key)) {
+            (Ok(signature), Ok(key)) => env::ed25519_verify(signature, message, key),
+            _ => false,
+        },
+        CurveType::SECP256K1 => match signature {
+            [signature @ .., v] if signature.len() == 64 => {
+                let v = if *v >= 27 { *v - 27 } else { *v };
+                env::ecrecover(message, signature, v, true).is_some_and(|recovered| recovered.as_slice() == key)
+            }
+            _ => false,
+        },
+    }
+}
+
+#[near]
+impl FastAuth {
+    /// Computes the commitment the JWT of a user must carry in its `fatxn` claim to create a session
+    /// # Arguments
+    /// * `session` - The session to create
+    pub fn session_commitment(&self, session: SessionRequest) -> Vec<u8> {
+        session.commitment()
+    }
+
+    /// Computes the commitment the JWT of a user must carry in its `fatxn` claim to revoke a session
+    /// # Arguments
+    /// * `public_key` - The session key
+    pub fn session_revoke_commitment(&self, public_key: PublicKey) -> Vec<u8> {
+        revoke_commitment(&public_key)
+    }
+
+    /// Computes the message the session key must sign to request the signature of a payload
+    /// # Arguments
+    /// * `public_key` - The session key
+    /// * `sign_payload` - The payload to sign
+    /// * `algorithm` - The signature algorithm to use ("secp256k1", "ecdsa", or "eddsa")
+    /// * `hashing` - How the payload is hashed before signing ("sha256", "keccak256", "prehashed" or "raw"), sha256 by default
+    /// # Returns
+    /// * The message for the next signature of the session
+    /// # Panics
+    /// * If the session does not exist, expired or ran out of signatures
+    /// * If the algorithm or hashing mode is not supported, or not valid for the payload
+    #[pause]
+    pub fn session_sign_message(&self, public_key: PublicKey, sign_payload: Vec<u8>, algorithm: String, hashing: Option<String>) -> Vec<u8> {
+        let signature_algorithm = match SignatureAlgorithm::parse_str(&algorithm) {
+            Ok(alg) => alg,
+            Err(err) => env::panic_str(&err),
+        };
+        let hashing_mode = self.hashing_mode(hashing, &signature_algorithm, std::slice::from_ref(&sign_payload));
+        self.active_session(&public_key).sign_message(&public_key, &sign_payload, &signature_algorithm, hashing_mode)
+    }
+
+    /// Creates a session, authorizing a key to request signatures for the user without a JWT
+    /// # Arguments
+    /// * `guard_id` - The guard ID for JWT verification
+    /// * `verify_payload` - A JWT of the user, whose `fatxn` claim must be the session commitment
+    /// * `session` - The session key, expiry, signature count limit and scope
+    /// # Returns
+    /// * Promise for the verification of the JWT, resolving to whether the session was created
+    /// # Panics
+    /// * If the guard does not exist or is frozen
+    /// * If the session expires in the past or after 7 days, allows no signature or more than 1000, or its policy is invalid
+    /// * If the key is already used by a session
+    #[pause]
+    pub fn create_session(&self, guard_id: String, verify_payload: String, session: SessionRequest) -> Promise {
+        let guard_address = self.verifying_guard(&guard_id);
+        if let Err(err) = self.session_error(&session) {
+            env::panic_str(&err);
+        }
+        let commitment = session.commitment();
+
+        external_guard::ext(guard_address)
+            .verify(guard_id.clone(), verify_payload, commitment, env::predecessor_account_id())
+            .then(Self::ext(env::current_account_id())
+                .on_create_session_callback(guard_id, session)
+        )
+    }
+
+    /// Creates the session once the JWT is verified
+    /// # Arguments
+    /// * `guard_id` - The guard ID used for JWT verification
+    /// * `session` - The session to create
+    /// * `call_result` - Verification result containing success and user ID
+    /// # Returns
+    /// * Whether the session was created
+    #[private]
+    pub fn on_create_session_callback(&mut self, guard_id: String, session: SessionRequest, #[callback_result] call_result: Result<(bool, String, String), PromiseError>) -> bool {
+        let sub = match self.verified_user(&guard_id, call_result) {
+            Ok(sub) => sub,
+            Err(outcome) => {
+                if let SignOutcome::Failed { reason, .. } = outcome {
+                    env::log_str(&reason);
+                }
+                return false;
+            }
+        };
+        let identity = format_identity(&guard_id, &sub);
+        self.remove_inactive_sessions(&identity);
+        let session_error = self.session_error(&session).err().or_else(|| {
+            let sessions = self.identity_sessions.get(&identity).map_or(0, Vec::len);
+            (sessions >= MAX_SESSIONS_PER_IDENTITY).then(|| format!("Cannot have more than {} sessions", MAX_SESSIONS_PER_IDENTITY))
+        });
+        if let Some(err) = session_error {
+            env::log_str(&err);
+            return false;
+        }
+
+        let public_key = session.public_key.clone();
+        FastAuthEvent::SessionCreated {
+            identity: &identity,
+            public_key: &public_key,
+            expires_at: session.expires_at,
+            max_signatures: session.max_signatures,
+        }.emit();
+        self.sessions.insert(public_key.clone(), Session::new(guard_id, sub, session));
+        self.identity_sessions.entry(identity).or_default().push(public_key);
+        true
+    }
+
+    /// Revokes a session
+    /// # Arguments
+    /// * `guard_id` - The guard ID the session was created with
+    /// * `verify_payload` - A JWT of the user of the session, whose `fatxn` claim must be the revoke commitment
+    /// * `public_key` - The session key
+    /// # Returns
+    /// * Promise for the verification of the JWT, resolving to whether the session was revoked
+    /// # Panics
+    /// * If the guard does not exist or is frozen
+    /// * If the session does not exist or was created with another guard ID
+    #[pause]
+    pub fn revoke_session(&self, guard_id: String, verify_payload: String, public_key: PublicKey) -> Promise {
+        let guard_address = self.verifying_guard(&guard_id);
+        match self.sessions.get(&public_key) {
+            Some(session) if session.guard_id == guard_id => {}
+            Some(_) => env::panic_str(&format!("Session was not created with guard '{}'", guard_id)),
+            None => env::panic_str("Session does not exist"),
+        }
+
+        external_guard::ext(guard_address)
+            .verify(guard_id.clone(), verify_payload, revoke_commitment(&public_key), env::predecessor_account_id())
+            .then(Self::ext(env::current_account_id())
+                .on_revoke_session_callback(guard_id, public_key)
+        )
+    }
+
+    /// Revokes the session once the JWT is verified
+    /// # Arguments
+    /// * `guard_id` - The guard ID used for JWT verification
+    /// * `public_key` - The session key
+    /// * `call_result` - Verification result of the user of the session
+    /// # Returns
+    /// * Whether the session was revoked
+    #[private]
+    pub fn on_revoke_session_callback(&mut self, guard_id: String, public_key: PublicKey, #[callback_result] call_result: Result<(bool, String, String), PromiseError>) -> bool {
+        let Some(session) = self.sessions.get(&public_key).filter(|session| session.guard_id == guard_id) else {
+            env::log_str("Session does not exist");
+            return false;
+        };
+        let sub = session.sub.clone();
+        if !self.verified_identity(&guard_id, &sub, call_result) {
+            return false;
+        }
+
+        let identity = format_identity(&guard_id, &sub);
+        self.sessions.remove(&public_key);
+        if let Some(public_keys) = self.identity_sessions.get_mut(&identity) {
+            public_keys.retain(|session_key| *session_key != public_key);
+        }
+        FastAuthEvent::SessionRevoked { identity: &identity, public_key: &public_key }.emit();
+        true
+    }
+
+    /// Signs a payload on behalf of the user of a session, authenticated by the session key instead of a JWT
+    /// # Arguments
+    /// * `public_key` - The session key
+    /// * `signature` - Signature by the session key of `session_sign_message(public_key, sign_payload, algorithm, hashing)`
+    /// * `sign_payload` - The data to sign
+    /// * `algorithm` - The signature algorithm to use ("secp256k1", "ecdsa", or "eddsa")
+    /// * `hashing` - How the payload is hashed before signing ("sha256", "keccak256", "prehashed" or "raw"), sha256 by default
+    /// # Returns
+    /// * Promise for MPC signing, resolving to a `SignOutcome`
+    /// # Panics
+    /// * If the session does not exist, expired or ran out of signatures, or the signature is invalid
+    /// * If the request is out of the scope of the session, or not allowed by the guard
+    /// * If the user exceeds its rate or spending limits
+    /// # Notes
+    /// * Requires an attached deposit for MPC costs
+    #[payable]
+    #[pause]
+    pub fn sign_with_session(&mut self, public_key: PublicKey, signature: Vec<u8>, sign_payload: Vec<u8>, algorithm: String, hashing: Option<String>) -> Promise {
+        let attached_deposit = env::attached_deposit();
+        let caller = env::predecessor_account_id();
+
+        let signature_algorithm = match SignatureAlgorithm::parse_str(&algorithm) {
+            Ok(alg) => alg,
+            Err(err) => env::panic_str(&err),
+        };
+        let hashing_mode = self.hashing_mode(hashing, &signature_algorithm, std::slice::from_ref(&sign_payload));
+
+        let mut session = self.active_session(&public_key).clone();
+        let message = session.sign_message(&public_key, &sign_payload, &signature_algorithm, hashing_mode);
+        assert!(verify_session_signature(&public_key, &message, &signature), "Invalid session signature");
+        if let Err(err) = session.scope.check(&signature_algorithm, &sign_payload) {
+            env::panic_str(&err);
+        }
+
+        let guard_id = session.guard_id.clone();
+        let guard_prefix = self.get_guard_prefix(guard_id.clone());
+        self.verifying_guard(&guard_id);
+        self.assert_guard_supports(&guard_prefix, &signature_algorithm);
+        self.assert_guard_policy_allows(&guard_prefix, std::slice::from_ref(&sign_payload));
+        let sign_cost = self.sign_cost(&signature_algorithm);
+        Self::assert_deposit_covers(attached_deposit, &sign_cost, 1);
+
+        session.signatures += 1;
+        let sub = session.sub.clone();
+        self.sessions.insert(public_key, session);
+        let (user, spend) = match self
+            .check_guard_quorum(&guard_id, sub, std::slice::from_ref(&sign_payload), hashing_mode)
+            .and_then(|user| self.consume_sign_limits(&guard_id, user, std::slice::from_ref(&sign_payload), hashing_mode, &sign_payload, None))
+        {
+            Ok((user, mut spends)) => (user, spends.pop().flatten()),
+            Err(SignOutcome::Failed { reason, .. }) => env::panic_str(&reason),
+            Err(SignOutcome::Signed { .. }) => unreachable!(),
+        };
+
+        FastAuthEvent::SignRequested {
+            guard_id: &guard_id,
+            caller: &caller,
+            algorithm: &signature_algorithm,
+            payloads: 1,
+            deposit: attached_deposit,
+        }.emit();
+
+        self.sign_request(guard_id.clone(), user, sign_payload, sign_cost.mpc_deposit, signature_algorithm, hashing_mode)
+            .then(Self::ext(env::current_account_id())
+                .on_sign_callback(guard_id, caller, attached_deposit, sign_cost, spend)
+        )
+    }
+
+    /// Gets a session
+    /// # Arguments
+    /// * `public_key` - The session key
+    /// # Returns
+    /// * The session, none if it does not exist or was revoked
+    #[pause]
+    pub fn get_session(&self, public_key: PublicKey) -> Option<Session> {
+        self.sessions.get(&public_key).cloned()
+    }
+
+    /// Gets the active sessions of a user
+    /// # Arguments
+    /// * `guard_id` - The guard ID the sessions were created with
+    /// * `sub` - The user identifier
+    /// # Returns
+    /// * The session keys and their sessions, in creation order
+    #[pause]
+    pub fn get_sessions(&self, guard_id: String, sub: String) -> Vec<(PublicKey, Session)> {
+        let now = env::block_timestamp_ms();
+        self.identity_sessions
+            .get(&format_identity(&guard_id, &sub))
+            .map(|public_keys| {
+                public_keys
+                    .iter()
+                    .filter_map(|public_key| {
+                        self.sessions
+                            .get(public_key)
+                            .filter(|session| session.is_active(now))
+                            .map(|session| (public_key.clone(), session.clone()))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Gets a session that can still sign
+    /// # Panics
+    /// * If the session does not exist, expired or ran out of signatures
+    fn active_session(&self, public_key: &PublicKey) -> &Session {
+        let now = env::block_timestamp_ms();
+        self.sessions
+            .get(public_key)
+            .filter(|session| session.is_active(now))
+            .unwrap_or_else(|| env::panic_str("Session does not exist, expired or ran out of signatures"))
+    }
+
+    /// Checks if a session can be created
+    /// # Returns
+    /// * Why the session cannot be created
+    fn session_error(&self, session: &SessionRequest) -> Result<(), String> {
+        session.validate(env::block_timestamp_ms())?;
+        if self.sessions.contains_key(&session.public_key) {
+            return Err("Session key is already in use".to_string());
+        }
+        Ok(())
+    }
+
+    /// Removes the sessions of an identity that expired or ran out of signatures
+    /// # Arguments
+    /// * `identity` - The identity, `guard_id#sub`
+    fn remove_inactive_sessions(&mut self, identity: &str) {
+        let Some(public_keys) = self.identity_sessions.get(identity).cloned() else {
+            return;
+        };
+        let now = env::block_timestamp_ms();
+        let (active, inactive): (Vec<PublicKey>, Vec<PublicKey>) = public_keys
+            .into_iter()
+            .partition(|public_key| self.sessions.get(public_key).is_some_and(|session| session.is_active(now)));
+        for public_key in inactive {
+            self.sessions.remove(&public_key);
+        }
+        if active.is_empty() {
+            self.identity_sessions.remove(identity);
+        } else {
+            self.identity_sessions.insert(identity.to_string(), active);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::NearToken;
+
+    fn request(expires_at: u64, max_signatures: u32) -> SessionRequest {
+        SessionRequest {
+            public_key: PublicKey::from_parts(CurveType::ED25519, vec![1; 32]).unwrap(),
+            expires_at,
+            max_signatures,
+            scope: SessionScope::default(),
+        }
+    }
+
+    #[test]
+    fn validate_request() {
+        assert!(request(1_000, 1).validate(0).is_ok());
+        assert!(request(MAX_SESSION_DURATION_MS, MAX_SESSION_SIGNATURES).validate(0).is_ok());
+        assert!(request(0, 1).validate(0).is_err());
+        assert!(request(MAX_SESSION_DURATION_MS + 1, 1).validate(0).is_err());
+        assert!(request(1_000, 0).validate(0).is_err());
+        assert!(request(1_000, MAX_SESSION_SIGNATURES + 1).validate(0).is_err());
+    }
+
+    #[test]
+    fn check_scope() {
+        let scope = SessionScope {
+            algorithms: Some(vec![SignatureAlgorithm::Eddsa]),
+            policy: Some(TransactionPolicy { max_deposit: Some(NearToken::from_yoctonear(1)), ..Default::default() }),
+        };
+        assert_eq!(
+            scope.check(&SignatureAlgorithm::Ecdsa, &[]),
+            Err("Session cannot sign with algorithm Ecdsa".to_string())
+        );
+        assert!(scope.check(&SignatureAlgorithm::Eddsa, &[1, 2, 3]).is_err());
+        assert!(SessionScope::default().check(&SignatureAlgorithm::Ecdsa, &[1, 2, 3]).is_ok());
+    }
+
+    #[test]
+    fn sign_message_binds_algorithm_and_hashing() {
+        let session = Session::new("jwt".to_string(), "alice".to_string(), request(1_000, 2));
+        let public_key = request(1_000, 2).public_key;
+        let message = session.sign_message(&public_key, &[1; 32], &SignatureAlgorithm::Ecdsa, HashingMode::Sha256);
+
+        assert_eq!(message, session.sign_message(&public_key, &[1; 32], &SignatureAlgorithm::Ecdsa, HashingMode::Sha256));
+        assert_ne!(message, session.sign_message(&public_key, &[1; 32], &SignatureAlgorithm::Eddsa, HashingMode::Sha256));
+        assert_ne!(message, session.sign_message(&public_key, &[1; 32], &SignatureAlgorithm::Ecdsa, HashingMode::Raw));
+    }
+
+    #[test]
+    fn session_expires_or_runs_out() {
+        let mut session = Session::new("jwt".to_string(), "alice".to_string(), request(1_000, 2));
+        assert!(session.is_active(999));
+        assert!(!session.is_active(1_000));
+
+        session.signatures = 2;
+        assert!(!session.is_active(0));
+    }
+}
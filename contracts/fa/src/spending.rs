@@ -0,0 +1,275 @@
+use near_sdk::json_types::U128;
+use near_sdk::{borsh, env, near, CurveType, NearToken, PublicKey};
+
+use crate::evm::decode_evm_value;
+use crate::hashing::HashingMode;
+use crate::transaction::{decode_sign_payload, total_deposit};
+
+/// Length in milliseconds of the rolling window spending is limited over
+pub const SPENDING_WINDOW_MS: u64 = 24 * 60 * 60 * 1000;
+
+/// Milliseconds between registering a second factor and it allowing spending over the limits
+pub const SECOND_FACTOR_ACTIVATION_MS: u64 = 24 * 60 * 60 * 1000;
+
+/// An asset whose spending is tracked
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Asset {
+    /// NEAR, spent by the deposits of NEAR transactions and delegate actions
+    Near,
+    /// The native token of an EVM chain, spent by the value of EVM transactions
+    Evm { chain_id: u64 },
+}
+
+impl Asset {
+    /// Parse asset from string, "near" or "evm:<chain_id>"
+    pub fn parse_str(asset: &str) -> Result<Self, String> {
+        let asset = asset.to_lowercase();
+        if asset == "near" {
+            return Ok(Asset::Near);
+        }
+        asset
+            .strip_prefix("evm:")
+            .and_then(|chain_id| chain_id.parse().ok())
+            .map(|chain_id| Asset::Evm { chain_id })
+            .ok_or_else(|| format!("Unsupported asset: {}. Supported assets are: near, evm:<chain_id>", asset))
+    }
+
+    /// Formats the asset as parsed by `parse_str`
+    pub fn key(&self) -> String {
+        match self {
+            Asset::Near => "near".to_string(),
+            Asset::Evm { chain_id } => format!("evm:{}", chain_id),
+        }
+    }
+}
+
+/// Gets what a payload spends
+/// # Arguments
+/// * `sign_payload` - The payload to sign
+/// * `hashing` - How the payload is hashed, sha256 for NEAR transactions and keccak256 for EVM transactions
+/// # Returns
+/// * The asset and amount spent, none if the payload is not a transaction of the hashing mode
+pub fn payload_spend(sign_payload: &[u8], hashing: HashingMode) -> Option<(Asset, u128)> {
+    match hashing {
+        HashingMode::Sha256 => decode_sign_payload(sign_payload)
+            .ok()
+            .map(|payload| (Asset::Near, total_deposit(&payload.actions))),
+        HashingMode::Keccak256 => decode_evm_value(sign_payload)
+            .ok()
+            .map(|evm_value| (Asset::Evm { chain_id: evm_value.chain_id }, evm_value.value)),
+        HashingMode::Prehashed | HashingMode::Raw => None,
+    }
+}
+
+/// Limits of what can be spent within the rolling window, none meaning unlimited
+#[derive(Debug, Clone, Default, PartialEq)]
+#[near(serializers=[borsh, json])]
+pub struct SpendingLimits {
+    /// Limit of the NEAR spent
+    pub near: Option<NearToken>,
+    /// Limit of the wei spent on each EVM chain
+    pub evm: Option<U128>,
+}
+
+impl SpendingLimits {
+    /// Gets the limit of an asset
+    pub fn limit(&self, asset: &Asset) -> Option<u128> {
+        match asset {
+            Asset::Near => self.near.map(|near| near.as_yoctonear()),
+            Asset::Evm { .. } => self.evm.map(|evm| evm.0),
+        }
+    }
+
+    /// Checks if any asset is limited
+    pub fn is_limited(&self) -> bool {
+        self.near.is_some() || self.evm.is_some()
+    }
+
+    /// Combines two limits, keeping the tightest of each asset
+    pub fn tightest(&self, other: &SpendingLimits) -> SpendingLimits {
+        SpendingLimits {
+            near: Self::min(self.near, other.near),
+            evm: Self::min(self.evm, other.evm),
+        }
+    }
+
+    /// Checks if the limits allow more than others for any asset
+    pub fn is_looser_than(&self, other: &SpendingLimits) -> bool {
+        let looser = |limit: Option<u128>, other: Option<u128>| match (limit, other) {
+            (_, None) => false,
+            (None, Some(_)) => true,
+            (Some(limit), Some(other)) => limit > other,
+        };
+        looser(self.limit(&Asset::Near), other.limit(&Asset::Near))
+            || looser(self.evm.map(|evm| evm.0), other.evm.map(|evm| evm.0))
+    }
+
+    fn min<T: PartialOrd>(limit: Option<T>, other: Option<T>) -> Option<T> {
+        match (limit, other) {
+            (Some(limit), Some(other)) => Some(if other < limit { other } else { limit }),
+            (limit, other) => limit.or(other),
+        }
+    }
+}
+
+/// The amounts spent within the window, with the block timestamps in milliseconds they were spent at, oldest first
+#[derive(Debug, Clone, Default)]
+#[near(serializers=[borsh])]
+pub struct SpendingWindow {
+    spends: Vec<(u64, u128)>,
+}
+
+impl SpendingWindow {
+    /// Gets the amount spent within the window
+    /// # Arguments
+    /// * `now` - The current block timestamp in milliseconds
+    pub fn spent(&self, now: u64) -> u128 {
+        self.spends
+            .iter()
+            .filter(|(timestamp, _)| Self::in_window(*timestamp, now))
+            .fold(0, |spent, (_, amount)| spent.saturating_add(*amount))
+    }
+
+    /// Records a spend, forgetting the ones that left the window
+    /// # Arguments
+    /// * `now` - The current block timestamp in milliseconds
+    /// * `amount` - The amount spent
+    pub fn record(&mut self, now: u64, amount: u128) {
+        self.spends.retain(|(timestamp, _)| Self::in_window(*timestamp, now));
+        self.spends.push((now, amount));
+    }
+
+    /// Releases a recorded spend, if it is still in the window
+    /// # Arguments
+    /// * `spent_at` - The block timestamp in milliseconds the amount was recorded at
+    /// * `amount` - The amount recorded
+    pub fn release(&mut self, spent_at: u64, amount: u128) {
+        if let Some(index) = self.spends.iter().position(|spend| *spend == (spent_at, amount)) {
+            self.spends.remove(index);
+        }
+    }
+
+    fn in_window(timestamp: u64, now: u64) -> bool {
+        timestamp.saturating_add(SPENDING_WINDOW_MS) > now
+    }
+}
+
+/// An amount counted against a spending window for a payload, released if MPC fails to sign it
+#[derive(Debug, Clone, PartialEq)]
+#[near(serializers=[borsh, json])]
+pub struct RecordedSpend {
+    /// The key of the spending window, `path@asset`
+    pub key: String,
+    /// The amount counted
+    pub amount: U128,
+    /// The block timestamp in milliseconds the amount was counted at
+    pub spent_at: u64,
+}
+
+/// A change of the spending controls of a user, authorized by a JWT of the user
+#[derive(Debug, Clone, PartialEq)]
+#[near(serializers=[borsh, json])]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SpendingUpdate {
+    /// Set the spending limits chosen by the user, none to remove them
+    Limits { limits: Option<SpendingLimits> },
+    /// Set the ed25519 key whose signatures allow spending over the limits, none to remove it
+    SecondFactor { public_key: Option<PublicKey> },
+}
+
+impl SpendingUpdate {
+    /// Computes the commitment a JWT must carry in its `fatxn` claim to authorize the update
+    /// # Arguments
+    /// * `identity` - The identity of the user, `guard_id#sub`
+    /// # Returns
+    /// * The SHA-256 of the borsh serialized identity and update
+    pub fn commitment(&self, identity: &str) -> Vec<u8> {
+        let message = borsh::to_vec(&(identity, self)).expect("Failed to serialize spending update");
+        env::sha256(&message)
+    }
+}
+
+/// An ed25519 key whose signatures allow spending over the limits
+#[derive(Debug, Clone, PartialEq)]
+#[near(serializers=[borsh, json])]
+pub struct SecondFactor {
+    /// The ed25519 key
+    pub public_key: PublicKey,
+    /// Block timestamp in milliseconds from which the key is active
+    pub active_at: u64,
+}
+
+impl SecondFactor {
+    /// Registers a key in the current block, active once the activation delay elapsed
+    pub fn new(public_key: PublicKey) -> Self {
+        Self {
+            public_key,
+            active_at: env::block_timestamp_ms().saturating_add(SECOND_FACTOR_ACTIVATION_MS),
+        }
+    }
+
+    /// Checks if the key can sign
+    /// # Arguments
+    /// * `now` - The current block timestamp in milliseconds
+    pub fn is_active(&self, now: u64) -> bool {
+        now >= self.active_at
+    }
+}
+
+/// Checks that a second factor key can be registered
+pub fn validate_second_factor(public_key: &PublicKey) -> Result<(), String> {
+    if public_key.curve_type() != CurveType::ED25519 {
+        return Err("Second factor must be an ed25519 key".to_string());
+    }
+    Ok(())
+}
+
+/// Verifies a second factor signature
+/// # Arguments
+/// * `public_key` - The ed25519 second factor key
+/// * `message` - The signed message, the commitment the JWT of the request carries
+/// * `signature` - The ed25519 signature
+pub fn verify_second_factor(public_key: &PublicKey, message: &[u8], signature: &[u8]) -> bool {
+    match (<&[u8; 64]>::try_from(signature), <&[u8; 32]>::try_from(&public_key.as_bytes()[1..])) {
+        (Ok(signature), Ok(public_key)) => env::ed25519_verify(signature, message, public_key),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn window_rolls_over() {
+        let mut window = SpendingWindow::default();
+        window.record(0, 10);
+        window.record(SPENDING_WINDOW_MS / 2, 5);
+        assert_eq!(window.spent(SPENDING_WINDOW_MS - 1), 15);
+        assert_eq!(window.spent(SPENDING_WINDOW_MS), 5);
+
+        window.record(SPENDING_WINDOW_MS, 1);
+        assert_eq!(window.spends, vec![(SPENDING_WINDOW_MS / 2, 5), (SPENDING_WINDOW_MS, 1)]);
+    }
+
+    #[test]
+    fn combine_limits() {
+        let guard = SpendingLimits { near: Some(NearToken::from_near(10)), evm: None };
+        let user = SpendingLimits { near: Some(NearToken::from_near(1)), evm: Some(U128(5)) };
+        assert_eq!(guard.tightest(&user), user);
+        assert!(guard.is_looser_than(&user));
+        assert!(!user.is_looser_than(&guard));
+        assert!(!SpendingLimits::default().is_looser_than(&SpendingLimits::default()));
+        assert!(user.is_limited());
+        assert!(!SpendingLimits::default().is_limited());
+    }
+
+    #[test]
+    fn parse_asset() {
+        assert_eq!(Asset::parse_str("NEAR").unwrap(), Asset::Near);
+        assert_eq!(Asset::parse_str("evm:8453").unwrap(), Asset::Evm { chain_id: 8453 });
+        assert_eq!(Asset::Evm { chain_id: 1 }.key(), "evm:1");
+        assert!(Asset::parse_str("evm:").is_err());
+        assert!(Asset::parse_str("btc").is_err());
+    }
+}
@@ -0,0 +1,68 @@
+use near_sdk::{env, near, AccountId, NearToken};
+
+use crate::guards::GuardInfo;
+use crate::policy::TransactionPolicy;
+use crate::spending::SpendingLimits;
+
+/// Maximum delay in milliseconds between scheduling an operation and executing it
+pub const MAX_TIMELOCK_DELAY_MS: u64 = 30 * 24 * 60 * 60 * 1000;
+
+/// An administrative operation that takes effect once the timelock delay elapsed
+#[derive(Debug, Clone, PartialEq)]
+#[near(serializers=[borsh, json])]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AdminOperation {
+    /// Set the MPC contract address
+    SetMpcAddress { mpc_address: AccountId },
+    /// Set the MPC key version
+    SetMpcKeyVersion { mpc_key_version: u32 },
+    /// Set the MPC domain ID
+    SetMpcDomainId { mpc_domain_id: u64 },
+    /// Register a guard, replacing the guard registered under the same ID
+    AddGuard { guard_id: String, guard_info: GuardInfo },
+    /// Remove a guard
+    RemoveGuard { guard_id: String },
+    /// Set the timelock delay of the operations scheduled afterwards
+    SetTimelockDelay { delay_ms: u64 },
+    /// Set the deposit forwarded to MPC with each signing request
+    SetMpcDeposit { mpc_deposit: NearToken },
+    /// Set or remove the transaction policy of a guard
+    SetGuardPolicy { guard_id: String, policy: Option<TransactionPolicy> },
+    /// Set or remove the spending limits of the users of a guard
+    SetGuardSpendingLimits { guard_id: String, limits: Option<SpendingLimits> },
+}
+
+/// An administrative operation waiting for its timelock delay to elapse
+#[derive(Debug, Clone, PartialEq)]
+#[near(serializers=[borsh, json])]
+pub struct ScheduledOperation {
+    /// The operation to execute
+    pub operation: AdminOperation,
+    /// The account that scheduled the operation
+    pub scheduled_by: AccountId,
+    /// Block timestamp in milliseconds at which the operation was scheduled
+    pub scheduled_at: u64,
+    /// Block timestamp in milliseconds from which the operation can be executed
+    pub executable_at: u64,
+}
+
+impl ScheduledOperation {
+    /// Schedules an operation on behalf of the predecessor in the current block
+    /// # Arguments
+    /// * `operation` - The operation to execute
+    /// * `delay_ms` - Milliseconds before the operation can be executed
+    pub fn new(operation: AdminOperation, delay_ms: u64) -> Self {
+        let scheduled_at = env::block_timestamp_ms();
+        Self {
+            operation,
+            scheduled_by: env::predecessor_account_id(),
+            scheduled_at,
+            executable_at: scheduled_at.saturating_add(delay_ms),
+        }
+    }
+
+    /// Checks if the timelock delay of the operation elapsed
+    pub fn is_ready(&self) -> bool {
+        env::block_timestamp_ms() >= self.executable_at
+    }
+}
@@ -0,0 +1,229 @@
+use near_sdk::{borsh, near, AccountId};
+
+/// NEP-461 discriminant prefixing a delegate action, as signed for meta transactions
+pub const DELEGATE_ACTION_PREFIX: u32 = (1 << 30) + 366;
+
+/// Tag of a versioned transaction. Unversioned transactions start with the length of the signer ID instead, which is never 1
+const TRANSACTION_V1_TAG: u8 = 1;
+
+/// Public key of a NEAR access key
+#[derive(Debug, Clone, PartialEq)]
+#[near(serializers=[borsh])]
+pub enum TxPublicKey {
+    Ed25519([u8; 32]),
+    Secp256k1([u8; 64]),
+}
+
+/// Signature of a NEAR delegate action
+#[derive(Debug, Clone, PartialEq)]
+#[near(serializers=[borsh])]
+pub enum TxSignature {
+    Ed25519([u8; 64]),
+    Secp256k1([u8; 65]),
+}
+
+/// A function call action
+#[derive(Debug, Clone, PartialEq)]
+#[near(serializers=[borsh])]
+pub struct FunctionCallAction {
+    pub method_name: String,
+    pub args: Vec<u8>,
+    pub gas: u64,
+    pub deposit: u128,
+}
+
+/// A function call access key permission
+#[derive(Debug, Clone, PartialEq)]
+#[near(serializers=[borsh])]
+pub struct FunctionCallPermission {
+    pub allowance: Option<u128>,
+    pub receiver_id: String,
+    pub method_names: Vec<String>,
+}
+
+/// The permission of an access key
+#[derive(Debug, Clone, PartialEq)]
+#[near(serializers=[borsh])]
+pub enum AccessKeyPermission {
+    FunctionCall(FunctionCallPermission),
+    FullAccess,
+}
+
+/// An access key added to an account
+#[derive(Debug, Clone, PartialEq)]
+#[near(serializers=[borsh])]
+pub struct AccessKey {
+    pub nonce: u64,
+    pub permission: AccessKeyPermission,
+}
+
+/// How a global contract is identified once deployed
+#[derive(Debug, Clone, PartialEq)]
+#[near(serializers=[borsh])]
+pub enum GlobalContractDeployMode {
+    CodeHash,
+    AccountId,
+}
+
+/// A global contract to use
+#[derive(Debug, Clone, PartialEq)]
+#[near(serializers=[borsh])]
+pub enum GlobalContractIdentifier {
+    CodeHash([u8; 32]),
+    AccountId(AccountId),
+}
+
+/// An action of a NEAR transaction, in protocol order
+#[derive(Debug, Clone, PartialEq)]
+#[near(serializers=[borsh])]
+pub enum Action {
+    CreateAccount,
+    DeployContract { code: Vec<u8> },
+    FunctionCall(FunctionCallAction),
+    Transfer { deposit: u128 },
+    Stake { stake: u128, public_key: TxPublicKey },
+    AddKey { public_key: TxPublicKey, access_key: AccessKey },
+    DeleteKey { public_key: TxPublicKey },
+    DeleteAccount { beneficiary_id: AccountId },
+    Delegate(Box<SignedDelegateAction>),
+    DeployGlobalContract { code: Vec<u8>, deploy_mode: GlobalContractDeployMode },
+    UseGlobalContract { contract_identifier: GlobalContractIdentifier },
+}
+
+impl Action {
+    /// Gets the yoctoNEAR the action transfers, attaches or stakes, including the ones of a nested delegate action
+    pub fn deposit(&self) -> u128 {
+        match self {
+            Action::FunctionCall(call) => call.deposit,
+            Action::Transfer { deposit } => *deposit,
+            Action::Stake { stake, .. } => *stake,
+            Action::Delegate(signed) => total_deposit(&signed.delegate_action.actions),
+            _ => 0,
+        }
+    }
+}
+
+/// Gets the yoctoNEAR a list of actions transfers, attaches or stakes
+pub fn total_deposit(actions: &[Action]) -> u128 {
+    actions.iter().fold(0, |total, action| total.saturating_add(action.deposit()))
+}
+
+/// The actions a relayer submits on behalf of the sender of a meta transaction
+#[derive(Debug, Clone, PartialEq)]
+#[near(serializers=[borsh])]
+pub struct DelegateAction {
+    pub sender_id: AccountId,
+    pub receiver_id: AccountId,
+    pub actions: Vec<Action>,
+    pub nonce: u64,
+    pub max_block_height: u64,
+    pub public_key: TxPublicKey,
+}
+
+/// A delegate action signed by its sender
+#[derive(Debug, Clone, PartialEq)]
+#[near(serializers=[borsh])]
+pub struct SignedDelegateAction {
+    pub delegate_action: DelegateAction,
+    pub signature: TxSignature,
+}
+
+/// An unversioned NEAR transaction
+#[derive(Debug, Clone, PartialEq)]
+#[near(serializers=[borsh])]
+pub struct TransactionV0 {
+    pub signer_id: AccountId,
+    pub public_key: TxPublicKey,
+    pub nonce: u64,
+    pub receiver_id: AccountId,
+    pub block_hash: [u8; 32],
+    pub actions: Vec<Action>,
+}
+
+/// A NEAR transaction with a priority fee
+#[derive(Debug, Clone, PartialEq)]
+#[near(serializers=[borsh])]
+pub struct TransactionV1 {
+    pub signer_id: AccountId,
+    pub public_key: TxPublicKey,
+    pub nonce: u64,
+    pub receiver_id: AccountId,
+    pub block_hash: [u8; 32],
+    pub actions: Vec<Action>,
+    pub priority_fee: u64,
+}
+
+/// What a sign payload asks to execute
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodedPayload {
+    /// The account receiving the actions
+    pub receiver_id: AccountId,
+    /// The actions to execute
+    pub actions: Vec<Action>,
+}
+
+/// Decodes a sign payload as a borsh serialized NEAR transaction, or a delegate action prefixed by its NEP-461 discriminant
+/// # Arguments
+/// * `sign_payload` - The payload to sign
+/// # Returns
+/// * The receiver and actions of the payload, or an error if it is neither
+pub fn decode_sign_payload(sign_payload: &[u8]) -> Result<DecodedPayload, String> {
+    let not_a_transaction = |_| "Sign payload is not a NEAR transaction or delegate action".to_string();
+
+    if let Some(delegate_action) = sign_payload.strip_prefix(&DELEGATE_ACTION_PREFIX.to_le_bytes()) {
+        let delegate_action: DelegateAction = borsh::from_slice(delegate_action).map_err(not_a_transaction)?;
+        return Ok(DecodedPayload { receiver_id: delegate_action.receiver_id, actions: delegate_action.actions });
+    }
+    if let Some(transaction) = sign_payload.strip_prefix(&[TRANSACTION_V1_TAG]) {
+        let transaction: TransactionV1 = borsh::from_slice(transaction).map_err(not_a_transaction)?;
+        return Ok(DecodedPayload { receiver_id: transaction.receiver_id, actions: transaction.actions });
+    }
+    let transaction: TransactionV0 = borsh::from_slice(sign_payload).map_err(not_a_transaction)?;
+    Ok(DecodedPayload { receiver_id: transaction.receiver_id, actions: transaction.actions })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn account(account_id: &str) -> AccountId {
+        account_id.parse().unwrap()
+    }
+
+    #[test]
+    fn decode_transaction() {
+        let transaction = TransactionV0 {
+            signer_id: account("alice.near"),
+            public_key: TxPublicKey::Ed25519([1; 32]),
+            nonce: 1,
+            receiver_id: account("token.near"),
+            block_hash: [2; 32],
+            actions: vec![Action::Transfer { deposit: 10 }],
+        };
+        let decoded = decode_sign_payload(&borsh::to_vec(&transaction).unwrap()).unwrap();
+        assert_eq!(decoded.receiver_id, account("token.near"));
+        assert_eq!(decoded.actions, vec![Action::Transfer { deposit: 10 }]);
+    }
+
+    #[test]
+    fn decode_delegate_action() {
+        let delegate_action = DelegateAction {
+            sender_id: account("alice.near"),
+            receiver_id: account("token.near"),
+            actions: vec![Action::DeleteAccount { beneficiary_id: account("bob.near") }],
+            nonce: 1,
+            max_block_height: 100,
+            public_key: TxPublicKey::Ed25519([1; 32]),
+        };
+        let payload = [DELEGATE_ACTION_PREFIX.to_le_bytes().to_vec(), borsh::to_vec(&delegate_action).unwrap()].concat();
+        let decoded = decode_sign_payload(&payload).unwrap();
+        assert_eq!(decoded.receiver_id, account("token.near"));
+        assert_eq!(decoded.actions, delegate_action.actions);
+    }
+
+    #[test]
+    fn decode_other_payload_fails() {
+        assert!(decode_sign_payload(&[1, 2, 3]).is_err());
+        assert!(decode_sign_payload(&[0; 32]).is_err());
+    }
+}
@@ -104,15 +104,34 @@ async fn test_owner() -> Result<(), Box<dyn std::error::Error>> {
         .await?;
     assert_eq!(owner_outcome.json::<String>()?, owner.id().to_string());
 
-    // Test changing the owner
+    // Test proposing a new owner
     let new_owner = sandbox.dev_create_account().await?;
-    let change_owner_outcome = owner.call(contract.id(), "change_owner")
+    let propose_owner_outcome = owner.call(contract.id(), "propose_owner")
         .args_json(json!({
             "new_owner": new_owner.id()
         }))
         .transact()
         .await?;
-    assert!(change_owner_outcome.is_success());
+    assert!(propose_owner_outcome.is_success());
+
+    // The owner does not change until the proposed owner accepts
+    let pending_owner = contract
+        .call("pending_owner")
+        .view()
+        .await?
+        .json::<serde_json::Value>()?;
+    assert_eq!(pending_owner["account_id"], new_owner.id().to_string());
+
+    // Only the proposed owner can accept
+    let owner_accept_outcome = owner.call(contract.id(), "accept_ownership")
+        .transact()
+        .await?;
+    assert!(!owner_accept_outcome.is_success());
+
+    let accept_outcome = new_owner.call(contract.id(), "accept_ownership")
+        .transact()
+        .await?;
+    assert!(accept_outcome.is_success());
 
     // Test getting the new owner
     let new_owner_outcome = contract
@@ -130,6 +149,16 @@ async fn test_verify() -> Result<(), Box<dyn std::error::Error>> {
 
     let sandbox = near_workspaces::sandbox().await?;
     let contract = sandbox.dev_deploy(&contract_wasm).await?;
+
+    // Initialize contract with the contract account as owner
+    let _ = contract.call("init")
+        .args_json(json!({
+            "init_guards": {},
+            "owner": contract.id(),
+            "pauser": contract.id()
+        }))
+        .transact()
+        .await?;
     
     // Deploy a mock guard contract
     let mock_guard = sandbox.dev_deploy(include_bytes!("../../target/wasm32-unknown-unknown/release/external_guard.wasm")).await?;
@@ -183,6 +212,16 @@ async fn test_sign() -> Result<(), Box<dyn std::error::Error>> {
     let sandbox = near_workspaces::sandbox().await?;
     let contract = sandbox.dev_deploy(&contract_wasm).await?;
 
+    // Initialize contract with the contract account as owner
+    let _ = contract.call("init")
+        .args_json(json!({
+            "init_guards": {},
+            "owner": contract.id(),
+            "pauser": contract.id()
+        }))
+        .transact()
+        .await?;
+
     // Deploy a mock guard contract
     let mock_guard = sandbox.dev_deploy(include_bytes!("../../target/wasm32-unknown-unknown/release/external_guard.wasm")).await?;
     
@@ -219,6 +258,7 @@ async fn test_sign() -> Result<(), Box<dyn std::error::Error>> {
             "sign_payload": vec![1, 2, 3],
             "algorithm": "ecdsa"
         }))
+        .deposit(near_sdk::NearToken::from_yoctonear(1))
         .transact()
         .await?;
 
@@ -237,6 +277,16 @@ async fn test_mpc() -> Result<(), Box<dyn std::error::Error>> {
     let sandbox = near_workspaces::sandbox().await?;
     let contract = sandbox.dev_deploy(&contract_wasm).await?;
 
+    // Initialize contract with the contract account as owner
+    let _ = contract.call("init")
+        .args_json(json!({
+            "init_guards": {},
+            "owner": contract.id(),
+            "pauser": contract.id()
+        }))
+        .transact()
+        .await?;
+
     // Test initial values
     let mpc_address = contract
         .call("mpc_address")
@@ -332,20 +382,23 @@ async fn test_pause() -> Result<(), Box<dyn std::error::Error>> {
 
     // Test initial paused state
     let paused_state = contract
-        .call("paused")
+        .call("pa_is_paused")
+        .args_json(json!({ "key": "ALL" }))
         .view()
         .await?
         .json::<bool>()?;
     assert_eq!(paused_state, false);
 
     // Test pausing the contract (should work - owner is set as pauser)
-    let pause_outcome = owner.call(contract.id(), "pause")
+    let pause_outcome = owner.call(contract.id(), "pa_pause_feature")
+        .args_json(json!({ "key": "ALL" }))
         .transact()
         .await?;
     assert!(pause_outcome.is_success());
 
-    // Test unpausing the contract (should work - owner can unpause)
-    let unpause_outcome = owner.call(contract.id(), "unpause")
+    // Test unpausing the contract (should work - owner has the DAO role)
+    let unpause_outcome = owner.call(contract.id(), "pa_unpause_feature")
+        .args_json(json!({ "key": "ALL" }))
         .transact()
         .await?;
     assert!(unpause_outcome.is_success());
@@ -371,38 +424,43 @@ async fn test_pauser_functionality() -> Result<(), Box<dyn std::error::Error>> {
         .transact()
         .await?;
 
-    // Test setting pauser (should work - owner can set pauser)
-    let set_pauser_outcome = owner.call(contract.id(), "set_pauser")
+    // Grant the PauseManager role (should work - owner is super admin)
+    let grant_pauser_outcome = owner.call(contract.id(), "acl_grant_role")
         .args_json(json!({
-            "pauser": pauser.id()
+            "role": "PauseManager",
+            "account_id": pauser.id()
         }))
         .transact()
         .await?;
-    assert!(set_pauser_outcome.is_success());
+    assert!(grant_pauser_outcome.is_success());
 
     // Test pausing with the new pauser (should work)
-    let pause_outcome = pauser.call(contract.id(), "pause")
+    let pause_outcome = pauser.call(contract.id(), "pa_pause_feature")
+        .args_json(json!({ "key": "ALL" }))
         .transact()
         .await?;
     assert!(pause_outcome.is_success());
 
     // Verify contract is paused
     let paused_state = contract
-        .call("paused")
+        .call("pa_is_paused")
+        .args_json(json!({ "key": "ALL" }))
         .view()
         .await?
         .json::<bool>()?;
     assert_eq!(paused_state, true);
 
     // Test unpausing with owner (should work)
-    let unpause_outcome = owner.call(contract.id(), "unpause")
+    let unpause_outcome = owner.call(contract.id(), "pa_unpause_feature")
+        .args_json(json!({ "key": "ALL" }))
         .transact()
         .await?;
     assert!(unpause_outcome.is_success());
 
     // Verify contract is unpaused
     let paused_state = contract
-        .call("paused")
+        .call("pa_is_paused")
+        .args_json(json!({ "key": "ALL" }))
         .view()
         .await?
         .json::<bool>()?;
@@ -429,7 +487,8 @@ async fn test_paused_operations_blocked() -> Result<(), Box<dyn std::error::Erro
         .await?;
 
     // Pause the contract (owner is set as pauser)
-    let pause_outcome = owner.call(contract.id(), "pause")
+    let pause_outcome = owner.call(contract.id(), "pa_pause_feature")
+        .args_json(json!({ "key": "ALL" }))
         .transact()
         .await?;
     assert!(pause_outcome.is_success());
@@ -454,15 +513,15 @@ async fn test_paused_operations_blocked() -> Result<(), Box<dyn std::error::Erro
         .await?;
     assert!(!remove_guard_outcome.is_success());
 
-    // Test change_owner
+    // Test propose_owner
     let new_owner = sandbox.dev_create_account().await?;
-    let change_owner_outcome = owner.call(contract.id(), "change_owner")
+    let propose_owner_outcome = owner.call(contract.id(), "propose_owner")
         .args_json(json!({
             "new_owner": new_owner.id()
         }))
         .transact()
         .await?;
-    assert!(!change_owner_outcome.is_success());
+    assert!(!propose_owner_outcome.is_success());
 
     // Test set_mpc_address
     let set_mpc_outcome = owner.call(contract.id(), "set_mpc_address")
@@ -494,7 +553,8 @@ async fn test_paused_operations_blocked() -> Result<(), Box<dyn std::error::Erro
     // Test view functions that should still work when paused
     // Test paused (should work - this is the only view function that works when paused)
     let _paused_result = contract
-        .call("paused")
+        .call("pa_is_paused")
+        .args_json(json!({ "key": "ALL" }))
         .view()
         .await?;
 
@@ -527,62 +587,64 @@ async fn test_pause_unpause_authorization() -> Result<(), Box<dyn std::error::Er
         .transact()
         .await?;
 
-    // Set pauser to a different account (owner can set pauser)
-    let set_pauser_outcome = owner.call(contract.id(), "set_pauser")
+    // Grant the PauseManager role to a different account (owner is super admin)
+    let grant_pauser_outcome = owner.call(contract.id(), "acl_grant_role")
         .args_json(json!({
-            "pauser": pauser.id()
+            "role": "PauseManager",
+            "account_id": pauser.id()
         }))
         .transact()
         .await?;
-    assert!(set_pauser_outcome.is_success());
-
-    // Test that owner cannot pause (only pauser can)
-    let owner_pause_outcome = owner.call(contract.id(), "pause")
-        .transact()
-        .await?;
-    assert!(!owner_pause_outcome.is_success());
+    assert!(grant_pauser_outcome.is_success());
 
     // Test that unauthorized user cannot pause
-    let unauthorized_pause_outcome = unauthorized_user.call(contract.id(), "pause")
+    let unauthorized_pause_outcome = unauthorized_user.call(contract.id(), "pa_pause_feature")
+        .args_json(json!({ "key": "ALL" }))
         .transact()
         .await?;
     assert!(!unauthorized_pause_outcome.is_success());
 
     // Test that pauser can pause
-    let pauser_pause_outcome = pauser.call(contract.id(), "pause")
+    let pauser_pause_outcome = pauser.call(contract.id(), "pa_pause_feature")
+        .args_json(json!({ "key": "ALL" }))
         .transact()
         .await?;
     assert!(pauser_pause_outcome.is_success());
 
     // Verify contract is paused
     let paused_state = contract
-        .call("paused")
+        .call("pa_is_paused")
+        .args_json(json!({ "key": "ALL" }))
         .view()
         .await?
         .json::<bool>()?;
     assert_eq!(paused_state, true);
 
-    // Test that pauser cannot unpause (only owner can)
-    let pauser_unpause_outcome = pauser.call(contract.id(), "unpause")
+    // Test that pauser cannot unpause (only DAO can)
+    let pauser_unpause_outcome = pauser.call(contract.id(), "pa_unpause_feature")
+        .args_json(json!({ "key": "ALL" }))
         .transact()
         .await?;
     assert!(!pauser_unpause_outcome.is_success());
 
     // Test that unauthorized user cannot unpause
-    let unauthorized_unpause_outcome = unauthorized_user.call(contract.id(), "unpause")
+    let unauthorized_unpause_outcome = unauthorized_user.call(contract.id(), "pa_unpause_feature")
+        .args_json(json!({ "key": "ALL" }))
         .transact()
         .await?;
     assert!(!unauthorized_unpause_outcome.is_success());
 
     // Test that owner can unpause
-    let owner_unpause_outcome = owner.call(contract.id(), "unpause")
+    let owner_unpause_outcome = owner.call(contract.id(), "pa_unpause_feature")
+        .args_json(json!({ "key": "ALL" }))
         .transact()
         .await?;
     assert!(owner_unpause_outcome.is_success());
 
     // Verify contract is unpaused
     let paused_state = contract
-        .call("paused")
+        .call("pa_is_paused")
+        .args_json(json!({ "key": "ALL" }))
         .view()
         .await?
         .json::<bool>()?;
@@ -610,35 +672,40 @@ async fn test_paused_state_management() -> Result<(), Box<dyn std::error::Error>
 
     // Test initial paused state
     let initial_paused_state = contract
-        .call("paused")
+        .call("pa_is_paused")
+        .args_json(json!({ "key": "ALL" }))
         .view()
         .await?
         .json::<bool>()?;
     assert_eq!(initial_paused_state, false);
 
     // Pause the contract (owner is set as pauser)
-    let pause_outcome = owner.call(contract.id(), "pause")
+    let pause_outcome = owner.call(contract.id(), "pa_pause_feature")
+        .args_json(json!({ "key": "ALL" }))
         .transact()
         .await?;
     assert!(pause_outcome.is_success());
 
     // Test paused state after pausing
     let paused_state = contract
-        .call("paused")
+        .call("pa_is_paused")
+        .args_json(json!({ "key": "ALL" }))
         .view()
         .await?
         .json::<bool>()?;
     assert_eq!(paused_state, true);
 
     // Unpause the contract
-    let unpause_outcome = owner.call(contract.id(), "unpause")
+    let unpause_outcome = owner.call(contract.id(), "pa_unpause_feature")
+        .args_json(json!({ "key": "ALL" }))
         .transact()
         .await?;
     assert!(unpause_outcome.is_success());
 
     // Test paused state after unpausing
     let unpaused_state = contract
-        .call("paused")
+        .call("pa_is_paused")
+        .args_json(json!({ "key": "ALL" }))
         .view()
         .await?
         .json::<bool>()?;
@@ -678,7 +745,8 @@ async fn test_verify_and_sign_blocked_when_paused() -> Result<(), Box<dyn std::e
     assert!(add_outcome.is_success());
 
     // Pause the contract (owner is set as pauser)
-    let pause_outcome = owner.call(contract.id(), "pause")
+    let pause_outcome = owner.call(contract.id(), "pa_pause_feature")
+        .args_json(json!({ "key": "ALL" }))
         .transact()
         .await?;
     assert!(pause_outcome.is_success());
@@ -704,6 +772,7 @@ async fn test_verify_and_sign_blocked_when_paused() -> Result<(), Box<dyn std::e
             "sign_payload": vec![1, 2, 3],
             "algorithm": "ecdsa"
         }))
+        .deposit(near_sdk::NearToken::from_yoctonear(1))
         .transact()
         .await?;
     assert!(!sign_outcome.is_success());
@@ -717,6 +786,7 @@ async fn test_verify_and_sign_blocked_when_paused() -> Result<(), Box<dyn std::e
             "sign_payload": vec![1, 2, 3],
             "algorithm": "secp256k1"
         }))
+        .deposit(near_sdk::NearToken::from_yoctonear(1))
         .transact()
         .await?;
     assert!(!sign_legacy_outcome.is_success());
@@ -730,6 +800,7 @@ async fn test_verify_and_sign_blocked_when_paused() -> Result<(), Box<dyn std::e
             "sign_payload": vec![1, 2, 3],
             "algorithm": "eddsa"
         }))
+        .deposit(near_sdk::NearToken::from_yoctonear(1))
         .transact()
         .await?;
     assert!(!sign_eddsa_outcome.is_success());
@@ -873,7 +944,8 @@ async fn test_mpc_domain_id_blocked_when_paused() -> Result<(), Box<dyn std::err
         .await?;
 
     // Pause the contract
-    let pause_outcome = owner.call(contract.id(), "pause")
+    let pause_outcome = owner.call(contract.id(), "pa_pause_feature")
+        .args_json(json!({ "key": "ALL" }))
         .transact()
         .await?;
     assert!(pause_outcome.is_success());
@@ -895,7 +967,8 @@ async fn test_mpc_domain_id_blocked_when_paused() -> Result<(), Box<dyn std::err
     assert!(domain_id_result.is_err());
 
     // Unpause and verify operations work again
-    let unpause_outcome = owner.call(contract.id(), "unpause")
+    let unpause_outcome = owner.call(contract.id(), "pa_unpause_feature")
+        .args_json(json!({ "key": "ALL" }))
         .transact()
         .await?;
     assert!(unpause_outcome.is_success());
@@ -980,6 +1053,7 @@ async fn test_sign_legacy() -> Result<(), Box<dyn std::error::Error>> {
             "sign_payload": vec![1, 2, 3],
             "algorithm": "secp256k1"
         }))
+        .deposit(near_sdk::NearToken::from_yoctonear(1))
         .transact()
         .await?;
 
@@ -1052,6 +1126,7 @@ async fn test_sign_v2() -> Result<(), Box<dyn std::error::Error>> {
             "sign_payload": vec![1, 2, 3],
             "algorithm": "ecdsa"
         }))
+        .deposit(near_sdk::NearToken::from_yoctonear(1))
         .transact()
         .await?;
 
@@ -1250,6 +1325,7 @@ async fn test_sign_eddsa_algorithm() -> Result<(), Box<dyn std::error::Error>> {
             "sign_payload": vec![1, 2, 3],
             "algorithm": "eddsa"
         }))
+        .deposit(near_sdk::NearToken::from_yoctonear(1))
         .transact()
         .await?;
 
@@ -1301,6 +1377,7 @@ async fn test_sign_invalid_algorithm() -> Result<(), Box<dyn std::error::Error>>
             "sign_payload": vec![1, 2, 3],
             "algorithm": "invalid_algorithm"
         }))
+        .deposit(near_sdk::NearToken::from_yoctonear(1))
         .transact()
         .await?;
 
@@ -1315,10 +1392,959 @@ async fn test_sign_invalid_algorithm() -> Result<(), Box<dyn std::error::Error>>
             "sign_payload": vec![1, 2, 3],
             "algorithm": "ECDSA"
         }))
+        .deposit(near_sdk::NearToken::from_yoctonear(1))
         .transact()
         .await?;
 
     assert!(sign_outcome_upper.is_success());
 
     Ok(())
-}
\ No newline at end of file
+}
+
+#[tokio::test]
+async fn test_sign_batch() -> Result<(), Box<dyn std::error::Error>> {
+    let contract_wasm = near_workspaces::compile_project("./").await?;
+    let sandbox = near_workspaces::sandbox().await?;
+    let owner = sandbox.dev_create_account().await?;
+    let contract = sandbox.dev_deploy(&contract_wasm).await?;
+
+    // Initialize contract with owner
+    let _ = contract.call("init")
+        .args_json(json!({
+            "init_guards": {},
+            "owner": owner.id(),
+            "pauser": owner.id()
+        }))
+        .transact()
+        .await?;
+
+    // Deploy a mock guard contract
+    let mock_guard = sandbox.dev_deploy(include_bytes!("../../target/wasm32-unknown-unknown/release/external_guard.wasm")).await?;
+
+    // Deploy a mock mpc contract
+    let mock_mpc = sandbox.dev_deploy(include_bytes!("../../target/wasm32-unknown-unknown/release/mpc.wasm")).await?;
+
+    // Add the mock guard to the contract
+    let add_outcome = owner.call(contract.id(), "add_guard")
+        .args_json(json!({
+            "guard_id": "jwt",
+            "guard_address": mock_guard.id()
+        }))
+        .transact()
+        .await?;
+    assert!(add_outcome.is_success());
+
+    // Set the MPC address
+    let set_mpc_outcome = owner.call(contract.id(), "set_mpc_address")
+        .args_json(json!({
+            "mpc_address": mock_mpc.id()
+        }))
+        .transact()
+        .await?;
+    assert!(set_mpc_outcome.is_success());
+
+    // The batch commitment is the Merkle root of the payloads
+    let commitment = contract
+        .call("batch_commitment")
+        .args_json(json!({
+            "sign_payloads": [[1, 2, 3], [4, 5, 6]]
+        }))
+        .view()
+        .await?
+        .json::<Vec<u8>>()?;
+    assert_eq!(commitment.len(), 32);
+
+    // Call sign_batch without enough deposit for every payload (should fail)
+    let sign_outcome = contract
+        .call("sign_batch")
+        .args_json(json!({
+            "guard_id": "jwt#mock",
+            "verify_payload": "test_payload",
+            "sign_payloads": [[1, 2, 3], [4, 5, 6]],
+            "algorithm": "ecdsa"
+        }))
+        .deposit(near_sdk::NearToken::from_yoctonear(1))
+        .transact()
+        .await?;
+    assert!(!sign_outcome.is_success());
+
+    // Call sign_batch with an empty batch (should fail)
+    let sign_outcome = contract
+        .call("sign_batch")
+        .args_json(json!({
+            "guard_id": "jwt#mock",
+            "verify_payload": "test_payload",
+            "sign_payloads": [],
+            "algorithm": "ecdsa"
+        }))
+        .deposit(near_sdk::NearToken::from_yoctonear(2))
+        .transact()
+        .await?;
+    assert!(!sign_outcome.is_success());
+
+    // Call sign_batch with two payloads
+    let sign_outcome = contract
+        .call("sign_batch")
+        .args_json(json!({
+            "guard_id": "jwt#mock",
+            "verify_payload": "test_payload",
+            "sign_payloads": [[1, 2, 3], [4, 5, 6]],
+            "algorithm": "ecdsa"
+        }))
+        .deposit(near_sdk::NearToken::from_yoctonear(2))
+        .transact()
+        .await?;
+    assert!(sign_outcome.is_success());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_derived_addresses() -> Result<(), Box<dyn std::error::Error>> {
+    let contract_wasm = near_workspaces::compile_project("./").await?;
+    let sandbox = near_workspaces::sandbox().await?;
+    let owner = sandbox.dev_create_account().await?;
+    let contract = sandbox.dev_deploy(&contract_wasm).await?;
+
+    // Initialize contract with owner
+    let _ = contract.call("init")
+        .args_json(json!({
+            "init_guards": {},
+            "owner": owner.id(),
+            "pauser": owner.id()
+        }))
+        .transact()
+        .await?;
+
+    // Deploy a mock guard contract
+    let mock_guard = sandbox.dev_deploy(include_bytes!("../../target/wasm32-unknown-unknown/release/external_guard.wasm")).await?;
+
+    // Deploy a mock mpc contract
+    let mock_mpc = sandbox.dev_deploy(include_bytes!("../../target/wasm32-unknown-unknown/release/mpc.wasm")).await?;
+
+    // Add the mock guard to the contract
+    let add_outcome = owner.call(contract.id(), "add_guard")
+        .args_json(json!({
+            "guard_id": "jwt",
+            "guard_address": mock_guard.id()
+        }))
+        .transact()
+        .await?;
+    assert!(add_outcome.is_success());
+
+    // Set the MPC address
+    let set_mpc_outcome = owner.call(contract.id(), "set_mpc_address")
+        .args_json(json!({
+            "mpc_address": mock_mpc.id()
+        }))
+        .transact()
+        .await?;
+    assert!(set_mpc_outcome.is_success());
+
+    // The mock MPC derives the secp256k1 generator point for every path
+    let derived_outcome = owner.call(contract.id(), "derived_addresses")
+        .args_json(json!({
+            "guard_id": "jwt",
+            "sub": "user",
+            "algorithm": "ecdsa"
+        }))
+        .transact()
+        .await?;
+    assert!(derived_outcome.is_success());
+    let addresses = derived_outcome.json::<serde_json::Value>()?;
+    assert_eq!(addresses["evm_address"], "0x7E5F4552091A69125d5DfCb7b8C2659029395Bdf");
+    assert_eq!(addresses["bitcoin_address"], "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4");
+    assert!(addresses["near_implicit_account"].is_null());
+
+    // Unknown guards are rejected
+    let unknown_guard_outcome = owner.call(contract.id(), "derived_public_key")
+        .args_json(json!({
+            "guard_id": "unknown",
+            "sub": "user",
+            "algorithm": "ecdsa"
+        }))
+        .transact()
+        .await?;
+    assert!(unknown_guard_outcome.is_failure());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_freeze_guard() -> Result<(), Box<dyn std::error::Error>> {
+    let contract_wasm = near_workspaces::compile_project("./").await?;
+    let sandbox = near_workspaces::sandbox().await?;
+    let owner = sandbox.dev_create_account().await?;
+    let pauser = sandbox.dev_create_account().await?;
+    let contract = sandbox.dev_deploy(&contract_wasm).await?;
+
+    // Initialize contract with owner and pauser
+    let _ = contract.call("init")
+        .args_json(json!({
+            "init_guards": {},
+            "owner": owner.id(),
+            "pauser": pauser.id()
+        }))
+        .transact()
+        .await?;
+
+    // Deploy a mock guard contract
+    let mock_guard = sandbox.dev_deploy(include_bytes!("../../target/wasm32-unknown-unknown/release/external_guard.wasm")).await?;
+
+    // Add the mock guard to the contract
+    let add_outcome = owner.call(contract.id(), "add_guard")
+        .args_json(json!({
+            "guard_id": "jwt",
+            "guard_address": mock_guard.id()
+        }))
+        .transact()
+        .await?;
+    assert!(add_outcome.is_success());
+
+    // Freeze the guard with the pauser
+    let freeze_outcome = pauser.call(contract.id(), "freeze_guard")
+        .args_json(json!({
+            "guard_id": "jwt"
+        }))
+        .transact()
+        .await?;
+    assert!(freeze_outcome.is_success());
+
+    let frozen_guards = contract
+        .call("frozen_guards")
+        .view()
+        .await?
+        .json::<Vec<String>>()?;
+    assert_eq!(frozen_guards, vec!["jwt".to_string()]);
+
+    // Verify is rejected while the guard is frozen
+    let verify_outcome = contract
+        .call("verify")
+        .args_json(json!({
+            "guard_id": "jwt#mock",
+            "verify_payload": "test_payload",
+            "sign_payload": vec![1, 2, 3]
+        }))
+        .transact()
+        .await?;
+    assert!(!verify_outcome.is_success());
+
+    // The pauser cannot unfreeze the guard
+    let pauser_unfreeze_outcome = pauser.call(contract.id(), "unfreeze_guard")
+        .args_json(json!({
+            "guard_id": "jwt"
+        }))
+        .transact()
+        .await?;
+    assert!(!pauser_unfreeze_outcome.is_success());
+
+    // The owner can unfreeze the guard
+    let owner_unfreeze_outcome = owner.call(contract.id(), "unfreeze_guard")
+        .args_json(json!({
+            "guard_id": "jwt"
+        }))
+        .transact()
+        .await?;
+    assert!(owner_unfreeze_outcome.is_success());
+
+    // Verify works again
+    let verify_outcome = contract
+        .call("verify")
+        .args_json(json!({
+            "guard_id": "jwt#mock",
+            "verify_payload": "test_payload",
+            "sign_payload": vec![1, 2, 3]
+        }))
+        .transact()
+        .await?;
+    assert!(verify_outcome.is_success());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_guard_registry() -> Result<(), Box<dyn std::error::Error>> {
+    let contract_wasm = near_workspaces::compile_project("./").await?;
+    let sandbox = near_workspaces::sandbox().await?;
+    let owner = sandbox.dev_create_account().await?;
+    let contract = sandbox.dev_deploy(&contract_wasm).await?;
+
+    // Initialize contract with one guard
+    let _ = contract.call("init")
+        .args_json(json!({
+            "init_guards": { "jwt": "jwt.fast-auth.near" },
+            "owner": owner.id(),
+            "pauser": owner.id()
+        }))
+        .transact()
+        .await?;
+
+    // Add a guard with metadata
+    let add_outcome = owner.call(contract.id(), "add_guard")
+        .args_json(json!({
+            "guard_id": "custom",
+            "guard_address": "custom.fast-auth.near",
+            "description": "Custom issuer",
+            "supported_algorithms": ["eddsa"]
+        }))
+        .transact()
+        .await?;
+    assert!(add_outcome.is_success());
+
+    // The guard info carries the metadata
+    let guard_info = contract
+        .call("get_guard_info")
+        .args_json(json!({
+            "guard_id": "custom"
+        }))
+        .view()
+        .await?
+        .json::<serde_json::Value>()?;
+    assert_eq!(guard_info["account_id"], "custom.fast-auth.near");
+    assert_eq!(guard_info["description"], "Custom issuer");
+    assert_eq!(guard_info["added_by"], owner.id().to_string());
+    assert_eq!(guard_info["supported_algorithms"], json!(["Eddsa"]));
+
+    // Guards are listed in registration order
+    let guards = contract
+        .call("get_guards")
+        .args_json(json!({
+            "from_index": 1,
+            "limit": 10
+        }))
+        .view()
+        .await?
+        .json::<Vec<(String, serde_json::Value)>>()?;
+    assert_eq!(guards.len(), 1);
+    assert_eq!(guards[0].0, "custom");
+
+    let count = contract
+        .call("get_guards_count")
+        .view()
+        .await?
+        .json::<u32>()?;
+    assert_eq!(count, 2);
+
+    // Signing with an algorithm the guard does not support fails
+    let sign_outcome = contract
+        .call("sign")
+        .args_json(json!({
+            "guard_id": "custom",
+            "verify_payload": "test_payload",
+            "sign_payload": vec![1, 2, 3],
+            "algorithm": "ecdsa"
+        }))
+        .deposit(near_sdk::NearToken::from_yoctonear(1))
+        .transact()
+        .await?;
+    assert!(!sign_outcome.is_success());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_timelocked_operations() -> Result<(), Box<dyn std::error::Error>> {
+    let contract_wasm = near_workspaces::compile_project("./").await?;
+    let sandbox = near_workspaces::sandbox().await?;
+    let owner = sandbox.dev_create_account().await?;
+    let guardian = sandbox.dev_create_account().await?;
+    let contract = sandbox.dev_deploy(&contract_wasm).await?;
+
+    let _ = contract.call("init")
+        .args_json(json!({
+            "init_guards": {},
+            "owner": owner.id(),
+            "pauser": owner.id()
+        }))
+        .transact()
+        .await?;
+
+    let grant_outcome = owner.call(contract.id(), "acl_grant_role")
+        .args_json(json!({
+            "role": "Guardian",
+            "account_id": guardian.id()
+        }))
+        .transact()
+        .await?;
+    assert!(grant_outcome.is_success());
+
+    // Without a delay the timelock delay itself takes effect immediately
+    let delay_outcome = owner.call(contract.id(), "set_timelock_delay")
+        .args_json(json!({
+            "delay_ms": 3_600_000
+        }))
+        .transact()
+        .await?;
+    assert!(delay_outcome.is_success());
+    let delay = contract.call("timelock_delay").view().await?.json::<u64>()?;
+    assert_eq!(delay, 3_600_000);
+
+    // Changing the MPC address is now only scheduled
+    let operation_id = owner.call(contract.id(), "set_mpc_address")
+        .args_json(json!({
+            "mpc_address": "mpc.fast-auth.near"
+        }))
+        .transact()
+        .await?
+        .json::<Option<u64>>()?
+        .expect("Operation should be scheduled");
+    let mpc_address = contract.call("mpc_address").view().await?.json::<String>()?;
+    assert_eq!(mpc_address, contract.id().to_string());
+
+    let operations = contract
+        .call("get_scheduled_operations")
+        .args_json(json!({}))
+        .view()
+        .await?
+        .json::<Vec<(u64, serde_json::Value)>>()?;
+    assert_eq!(operations.len(), 1);
+    assert_eq!(operations[0].1["operation"], json!({ "type": "set_mpc_address", "mpc_address": "mpc.fast-auth.near" }));
+
+    // It cannot be executed before the delay elapsed
+    let execute_outcome = owner.call(contract.id(), "execute_operation")
+        .args_json(json!({
+            "operation_id": operation_id
+        }))
+        .transact()
+        .await?;
+    assert!(!execute_outcome.is_success());
+
+    // The guardian cancels it
+    let cancel_outcome = guardian.call(contract.id(), "cancel_operation")
+        .args_json(json!({
+            "operation_id": operation_id
+        }))
+        .transact()
+        .await?;
+    assert!(cancel_outcome.is_success());
+
+    let operation = contract
+        .call("get_scheduled_operation")
+        .args_json(json!({
+            "operation_id": operation_id
+        }))
+        .view()
+        .await?
+        .json::<Option<serde_json::Value>>()?;
+    assert!(operation.is_none());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_fees() -> Result<(), Box<dyn std::error::Error>> {
+    let contract_wasm = near_workspaces::compile_project("./").await?;
+    let sandbox = near_workspaces::sandbox().await?;
+    let owner = sandbox.dev_create_account().await?;
+    let treasury = sandbox.dev_create_account().await?;
+    let contract = sandbox.dev_deploy(&contract_wasm).await?;
+
+    let _ = contract.call("init")
+        .args_json(json!({
+            "init_guards": {},
+            "owner": owner.id(),
+            "pauser": owner.id()
+        }))
+        .transact()
+        .await?;
+
+    // Set a fee for ecdsa
+    let fee_outcome = owner.call(contract.id(), "set_sign_fee")
+        .args_json(json!({
+            "algorithm": "ecdsa",
+            "fee": "1000"
+        }))
+        .transact()
+        .await?;
+    assert!(fee_outcome.is_success());
+
+    // The quote covers the MPC deposit and the fee of every payload
+    let quote = contract
+        .call("quote_sign_cost")
+        .args_json(json!({
+            "algorithm": "ecdsa",
+            "payloads": 2
+        }))
+        .view()
+        .await?
+        .json::<String>()?;
+    assert_eq!(quote, "2002");
+
+    let quote = contract
+        .call("quote_sign_cost")
+        .args_json(json!({
+            "algorithm": "eddsa"
+        }))
+        .view()
+        .await?
+        .json::<String>()?;
+    assert_eq!(quote, "1");
+
+    // Withdrawing requires a treasury
+    let withdraw_outcome = owner.call(contract.id(), "withdraw_fees")
+        .args_json(json!({}))
+        .transact()
+        .await?;
+    assert!(!withdraw_outcome.is_success());
+
+    let treasury_outcome = owner.call(contract.id(), "set_treasury")
+        .args_json(json!({
+            "treasury": treasury.id()
+        }))
+        .transact()
+        .await?;
+    assert!(treasury_outcome.is_success());
+
+    // Nothing was collected yet
+    let withdraw_outcome = treasury.call(contract.id(), "withdraw_fees")
+        .args_json(json!({}))
+        .transact()
+        .await?;
+    assert!(!withdraw_outcome.is_success());
+
+    let collected = contract.call("collected_fees").view().await?.json::<String>()?;
+    assert_eq!(collected, "0");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_identity_links() -> Result<(), Box<dyn std::error::Error>> {
+    let contract_wasm = near_workspaces::compile_project("./").await?;
+    let sandbox = near_workspaces::sandbox().await?;
+    let owner = sandbox.dev_create_account().await?;
+    let user = sandbox.dev_create_account().await?;
+    let contract = sandbox.dev_deploy(&contract_wasm).await?;
+
+    let _ = contract.call("init")
+        .args_json(json!({
+            "init_guards": {},
+            "owner": owner.id(),
+            "pauser": owner.id()
+        }))
+        .transact()
+        .await?;
+
+    // Deploy a mock guard contract
+    let mock_guard = sandbox.dev_deploy(include_bytes!("../../target/wasm32-unknown-unknown/release/external_guard.wasm")).await?;
+    let add_outcome = owner.call(contract.id(), "add_guard")
+        .args_json(json!({
+            "guard_id": "jwt",
+            "guard_address": mock_guard.id()
+        }))
+        .transact()
+        .await?;
+    assert!(add_outcome.is_success());
+
+    // Unlinked identities sign with their own path
+    let path = contract
+        .call("get_derivation_path")
+        .args_json(json!({
+            "guard_id": "jwt#apple",
+            "sub": "alice"
+        }))
+        .view()
+        .await?
+        .json::<String>()?;
+    assert_eq!(path, "jwt#apple#alice");
+
+    // Linking through an unknown guard is rejected
+    let link_outcome = user.call(contract.id(), "link_identity")
+        .args_json(json!({
+            "guard_id": "jwt#google",
+            "sub": "alice",
+            "verify_payload": "jwt",
+            "linked_guard_id": "custom",
+            "linked_sub": "alice",
+            "linked_verify_payload": "jwt"
+        }))
+        .transact()
+        .await?;
+    assert!(!link_outcome.is_success());
+
+    // Linking an identity to itself is rejected
+    let link_outcome = user.call(contract.id(), "link_identity")
+        .args_json(json!({
+            "guard_id": "jwt#google",
+            "sub": "alice",
+            "verify_payload": "jwt",
+            "linked_guard_id": "jwt#google",
+            "linked_sub": "alice",
+            "linked_verify_payload": "jwt"
+        }))
+        .transact()
+        .await?;
+    assert!(!link_outcome.is_success());
+
+    // Unlinking requires a link
+    let unlink_outcome = user.call(contract.id(), "unlink_identity")
+        .args_json(json!({
+            "guard_id": "jwt#apple",
+            "sub": "alice",
+            "verify_payload": "jwt",
+            "linked_identity": "jwt#apple#alice"
+        }))
+        .transact()
+        .await?;
+    assert!(!unlink_outcome.is_success());
+
+    let linked_identities = contract
+        .call("get_linked_identities")
+        .args_json(json!({
+            "identity": "jwt#google#alice"
+        }))
+        .view()
+        .await?
+        .json::<Vec<serde_json::Value>>()?;
+    assert!(linked_identities.is_empty());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_guard_policy() -> Result<(), Box<dyn std::error::Error>> {
+    let contract_wasm = near_workspaces::compile_project("./").await?;
+    let sandbox = near_workspaces::sandbox().await?;
+    let owner = sandbox.dev_create_account().await?;
+    let user = sandbox.dev_create_account().await?;
+    let contract = sandbox.dev_deploy(&contract_wasm).await?;
+
+    let _ = contract.call("init")
+        .args_json(json!({
+            "init_guards": {},
+            "owner": owner.id(),
+            "pauser": owner.id()
+        }))
+        .transact()
+        .await?;
+
+    // Deploy a mock guard contract
+    let mock_guard = sandbox.dev_deploy(include_bytes!("../../target/wasm32-unknown-unknown/release/external_guard.wasm")).await?;
+    let add_outcome = owner.call(contract.id(), "add_guard")
+        .args_json(json!({
+            "guard_id": "jwt",
+            "guard_address": mock_guard.id()
+        }))
+        .transact()
+        .await?;
+    assert!(add_outcome.is_success());
+
+    // Restrict the guard to transfers of up to 1 NEAR
+    let policy_outcome = owner.call(contract.id(), "set_guard_policy")
+        .args_json(json!({
+            "guard_id": "jwt",
+            "policy": {
+                "max_deposit": "1000000000000000000000000",
+                "denied_actions": ["delete_account", "add_full_access_key", "deploy_contract"]
+            }
+        }))
+        .transact()
+        .await?;
+    assert!(policy_outcome.is_success());
+
+    let policy = contract
+        .call("get_guard_policy")
+        .args_json(json!({
+            "guard_id": "jwt#issuer"
+        }))
+        .view()
+        .await?
+        .json::<Option<serde_json::Value>>()?;
+    assert_eq!(policy.unwrap()["denied_actions"][0], "delete_account");
+
+    // Payloads that are not NEAR transactions are rejected before the guard is called
+    let violation = contract
+        .call("check_guard_policy")
+        .args_json(json!({
+            "guard_id": "jwt#issuer",
+            "sign_payload": [1, 2, 3]
+        }))
+        .view()
+        .await?
+        .json::<Option<String>>()?;
+    assert_eq!(violation, Some("Sign payload is not a NEAR transaction or delegate action".to_string()));
+
+    let sign_outcome = user.call(contract.id(), "sign")
+        .args_json(json!({
+            "guard_id": "jwt#issuer",
+            "verify_payload": "jwt",
+            "sign_payload": [1, 2, 3],
+            "algorithm": "ecdsa"
+        }))
+        .deposit(near_sdk::NearToken::from_yoctonear(1))
+        .transact()
+        .await?;
+    assert!(!sign_outcome.is_success());
+
+    // Policies can only be set for registered guards
+    let policy_outcome = owner.call(contract.id(), "set_guard_policy")
+        .args_json(json!({
+            "guard_id": "custom",
+            "policy": null
+        }))
+        .transact()
+        .await?;
+    assert!(!policy_outcome.is_success());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_spending_limits() -> Result<(), Box<dyn std::error::Error>> {
+    let contract_wasm = near_workspaces::compile_project("./").await?;
+    let sandbox = near_workspaces::sandbox().await?;
+    let owner = sandbox.dev_create_account().await?;
+    let user = sandbox.dev_create_account().await?;
+    let contract = sandbox.dev_deploy(&contract_wasm).await?;
+
+    let _ = contract.call("init")
+        .args_json(json!({
+            "init_guards": {},
+            "owner": owner.id(),
+            "pauser": owner.id()
+        }))
+        .transact()
+        .await?;
+
+    // Deploy a mock guard contract
+    let mock_guard = sandbox.dev_deploy(include_bytes!("../../target/wasm32-unknown-unknown/release/external_guard.wasm")).await?;
+    let add_outcome = owner.call(contract.id(), "add_guard")
+        .args_json(json!({
+            "guard_id": "jwt",
+            "guard_address": mock_guard.id()
+        }))
+        .transact()
+        .await?;
+    assert!(add_outcome.is_success());
+
+    // Limit the users of the guard to 1 NEAR and 0.1 ETH a day
+    let limits_outcome = owner.call(contract.id(), "set_guard_spending_limits")
+        .args_json(json!({
+            "guard_id": "jwt",
+            "limits": {
+                "near": "1000000000000000000000000",
+                "evm": "100000000000000000"
+            }
+        }))
+        .transact()
+        .await?;
+    assert!(limits_outcome.is_success());
+
+    let limits = contract
+        .call("get_spending_limits")
+        .args_json(json!({
+            "guard_id": "jwt#issuer",
+            "sub": "alice"
+        }))
+        .view()
+        .await?
+        .json::<serde_json::Value>()?;
+    assert_eq!(limits["near"], "1000000000000000000000000");
+    assert_eq!(limits["evm"], "100000000000000000");
+
+    let spent = contract
+        .call("get_spent")
+        .args_json(json!({
+            "guard_id": "jwt#issuer",
+            "sub": "alice",
+            "asset": "evm:1"
+        }))
+        .view()
+        .await?
+        .json::<String>()?;
+    assert_eq!(spent, "0");
+
+    // Only guard managers and the DAO can set guard limits
+    let limits_outcome = user.call(contract.id(), "set_guard_spending_limits")
+        .args_json(json!({
+            "guard_id": "jwt",
+            "limits": null
+        }))
+        .transact()
+        .await?;
+    assert!(!limits_outcome.is_success());
+
+    // Second factors must be ed25519 keys
+    let update_outcome = user.call(contract.id(), "update_spending_controls")
+        .args_json(json!({
+            "guard_id": "jwt#issuer",
+            "sub": "alice",
+            "verify_payload": "jwt",
+            "update": {
+                "type": "second_factor",
+                "public_key": "secp256k1:qMoRgcoXai4mBPsdbHi1wfyxF9TdbPCF4qSDQTRP3TfescSRoUdSx6nmeQoN3aiwGzwMyGXAb1gUjBTv5AY8DXj"
+            }
+        }))
+        .transact()
+        .await?;
+    assert!(!update_outcome.is_success());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_session_keys() -> Result<(), Box<dyn std::error::Error>> {
+    let contract_wasm = near_workspaces::compile_project("./").await?;
+    let sandbox = near_workspaces::sandbox().await?;
+    let owner = sandbox.dev_create_account().await?;
+    let user = sandbox.dev_create_account().await?;
+    let contract = sandbox.dev_deploy(&contract_wasm).await?;
+
+    let _ = contract.call("init")
+        .args_json(json!({
+            "init_guards": {},
+            "owner": owner.id(),
+            "pauser": owner.id()
+        }))
+        .transact()
+        .await?;
+
+    // Deploy a mock guard contract
+    let mock_guard = sandbox.dev_deploy(include_bytes!("../../target/wasm32-unknown-unknown/release/external_guard.wasm")).await?;
+    let add_outcome = owner.call(contract.id(), "add_guard")
+        .args_json(json!({
+            "guard_id": "jwt",
+            "guard_address": mock_guard.id()
+        }))
+        .transact()
+        .await?;
+    assert!(add_outcome.is_success());
+
+    let session_key = "ed25519:6E8sCci9badyRkXb3JoRpBj5p8C6Tw41ELDZoiihKEtp";
+
+    // Sessions cannot last more than 7 days
+    let create_outcome = user.call(contract.id(), "create_session")
+        .args_json(json!({
+            "guard_id": "jwt#issuer",
+            "verify_payload": "jwt",
+            "session": {
+                "public_key": session_key,
+                "expires_at": u64::MAX,
+                "max_signatures": 10,
+                "scope": {}
+            }
+        }))
+        .max_gas()
+        .transact()
+        .await?;
+    assert!(!create_outcome.is_success());
+
+    let session = contract
+        .call("get_session")
+        .args_json(json!({
+            "public_key": session_key
+        }))
+        .view()
+        .await?
+        .json::<serde_json::Value>()?;
+    assert!(session.is_null());
+
+    // Signing with a session that does not exist fails
+    let sign_outcome = user.call(contract.id(), "sign_with_session")
+        .args_json(json!({
+            "public_key": session_key,
+            "signature": vec![0u8; 64],
+            "sign_payload": vec![1u8, 2, 3],
+            "algorithm": "eddsa"
+        }))
+        .deposit(near_sdk::NearToken::from_near(1))
+        .max_gas()
+        .transact()
+        .await?;
+    assert!(!sign_outcome.is_success());
+
+    let revoke_commitment = contract
+        .call("session_revoke_commitment")
+        .args_json(json!({
+            "public_key": session_key
+        }))
+        .view()
+        .await?
+        .json::<Vec<u8>>()?;
+    assert_eq!(revoke_commitment.len(), 32);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_guard_quorum() -> Result<(), Box<dyn std::error::Error>> {
+    let contract_wasm = near_workspaces::compile_project("./").await?;
+    let sandbox = near_workspaces::sandbox().await?;
+    let owner = sandbox.dev_create_account().await?;
+    let user = sandbox.dev_create_account().await?;
+    let contract = sandbox.dev_deploy(&contract_wasm).await?;
+
+    let _ = contract.call("init")
+        .args_json(json!({
+            "init_guards": {},
+            "owner": owner.id(),
+            "pauser": owner.id()
+        }))
+        .transact()
+        .await?;
+
+    // Deploy a mock guard contract
+    let mock_guard = sandbox.dev_deploy(include_bytes!("../../target/wasm32-unknown-unknown/release/external_guard.wasm")).await?;
+    let add_outcome = owner.call(contract.id(), "add_guard")
+        .args_json(json!({
+            "guard_id": "jwt",
+            "guard_address": mock_guard.id()
+        }))
+        .transact()
+        .await?;
+    assert!(add_outcome.is_success());
+
+    // The threshold cannot exceed the number of approvers
+    let update_outcome = user.call(contract.id(), "update_guard_quorum")
+        .args_json(json!({
+            "guard_id": "jwt#issuer",
+            "verify_payload": "jwt",
+            "request": {
+                "guard_id": "jwt#issuer",
+                "sub": "alice",
+                "quorum": {
+                    "approvers": [
+                        { "guard_id": "jwt#issuer", "sub": "alice" },
+                        { "guard_id": "jwt#passkey", "sub": "k1" }
+                    ],
+                    "threshold": 3,
+                    "min_value": null
+                }
+            }
+        }))
+        .max_gas()
+        .transact()
+        .await?;
+    assert!(!update_outcome.is_success());
+
+    let quorum = contract
+        .call("get_guard_quorum")
+        .args_json(json!({
+            "guard_id": "jwt#issuer",
+            "sub": "alice"
+        }))
+        .view()
+        .await?
+        .json::<serde_json::Value>()?;
+    assert!(quorum.is_null());
+
+    // Approvals must cover the sign cost
+    let approve_outcome = user.call(contract.id(), "approve_sign")
+        .args_json(json!({
+            "guard_id": "jwt#issuer",
+            "verify_payload": "jwt",
+            "request": {
+                "guard_id": "jwt#issuer",
+                "sub": "alice",
+                "sign_payload": vec![1u8, 2, 3],
+                "algorithm": "eddsa",
+                "hashing": null
+            }
+        }))
+        .max_gas()
+        .transact()
+        .await?;
+    assert!(!approve_outcome.is_success());
+
+    Ok(())
+}
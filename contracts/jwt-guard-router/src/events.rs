@@ -0,0 +1,24 @@
+use near_sdk::{near, AccountId};
+
+/// NEP-297 events emitted by JwtGuardRouter
+#[near(event_json(standard = "fast-auth"))]
+pub enum JwtGuardRouterEvent<'a> {
+    /// A guard was registered by its owner
+    #[event_version("1.0.0")]
+    GuardAdded { guard_name: &'a str, guard_account: &'a AccountId, owner: &'a AccountId },
+    /// A guard was pointed to another guard account
+    #[event_version("1.0.0")]
+    GuardUpdated { guard_name: &'a str, guard_account: &'a AccountId },
+    /// The fallback and shadow guards of a guard were set, none meaning removed
+    #[event_version("1.0.0")]
+    GuardRouteChanged { guard_name: &'a str, fallback: Option<&'a AccountId>, shadow: Option<&'a AccountId> },
+    /// The call to a guard failed and its fallback guard verifies the request
+    #[event_version("1.0.0")]
+    FallbackUsed { guard_name: &'a str, fallback_account: &'a AccountId },
+    /// The result of a shadow guard was compared to the result of its guard
+    #[event_version("1.0.0")]
+    ShadowCompared { guard_name: &'a str, shadow_account: &'a AccountId, matched: bool },
+    /// A guard was removed
+    #[event_version("1.0.0")]
+    GuardRemoved { guard_name: &'a str },
+}
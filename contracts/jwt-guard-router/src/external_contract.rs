@@ -4,4 +4,5 @@ use near_sdk::{ext_contract, AccountId};
 #[ext_contract(jwt_guard)]
 pub trait JwtGuard {
     fn verify(&self, issuer: String, jwt: String, sign_payload: Vec<u8>, predecessor: AccountId) -> (bool, String);
+    fn verify_without_consuming(&self, issuer: String, jwt: String, sign_payload: Vec<u8>, predecessor: AccountId) -> (bool, String);
 }
@@ -1,30 +1,62 @@
 // Find all our documentation at https://docs.near.org
-use near_sdk::{near, AccountId, env, NearToken, Promise, PromiseError, Gas  };
-use near_sdk::store::LookupMap;
+use near_sdk::{near, AccountId, env, NearToken, Promise, PromiseError, PromiseOrValue, Gas  };
+use near_sdk::borsh::BorshDeserialize;
+use near_sdk::store::{LookupMap, LookupSet};
 use crate::external_contract::jwt_guard;
+use crate::events::JwtGuardRouterEvent;
+use base_jwt_guard::{Ownable, PendingOwner};
+use crate::registration::GuardRegistration;
+use crate::route::{FallbackRequest, GuardRoute};
 
+pub mod events;
 pub mod external_contract;
+pub mod ownership;
+pub mod registration;
+pub mod route;
 
 pub const GUARD_NAME_MAX_BYTES_LENGTH: u128 = 2048;
 pub const MAX_ACCOUNT_BYTES_LENGTH: u128 = 64;
+pub const DEPOSIT_BYTES_LENGTH: u128 = 16;
+/// Key prefix, name length and storage record overhead of a removed guard name
+pub const REMOVED_GUARD_OVERHEAD_BYTES_LENGTH: u128 = 45;
 // NOTE: 1 NEAR
 pub const CONTINGENCY_DEPOSIT: u128 = 1_000_000_000_000_000_000_000_000;
 
 const MIGRATION_TGAS: u64 = 10;
 
 const MAP_KEY: &[u8] = b"g";
+const REGISTRATIONS_KEY: &[u8] = b"r";
+const ROUTES_KEY: &[u8] = b"f";
+const REMOVED_GUARDS_KEY: &[u8] = b"t";
+const STATE_KEY: &[u8] = b"STATE";
 
 /// Contract that manages JWT guard accounts
 /// 
 /// This contract maintains a mapping of guard names to their corresponding account IDs
-/// and provides functionality to add, remove and query guards. Only the contract owner
-/// can remove guards.
+/// and provides functionality to add, remove and query guards. Anyone can register a guard
+/// by paying its storage deposit, and only its registrant or the contract owner can update
+/// or remove it.
 #[near(contract_state)]
 pub struct JwtGuardRouter {
     /// Mapping of guard names to their account IDs
     guards: LookupMap<String, AccountId>,
     /// Account ID of the contract owner
     owner: AccountId,
+    /// Ownership transfer awaiting acceptance
+    pending_owner: Option<PendingOwner>,
+    /// Registrant and storage deposit of each guard, none for the guards added before self-service registration
+    registrations: LookupMap<String, GuardRegistration>,
+    /// Fallback and shadow guards of each guard that has any
+    routes: LookupMap<String, GuardRoute>,
+    /// Names of the removed guards, which cannot be registered again
+    removed_guards: LookupSet<String>,
+}
+
+/// JwtGuardRouter state layout prior to two-step ownership transfers, used for state migration
+#[near(serializers = [borsh])]
+pub struct JwtGuardRouterV1 {
+    guards: LookupMap<String, AccountId>,
+    owner: AccountId,
 }
 
 /// Default implementation initializes contract with empty guards and sets owner
@@ -34,6 +66,10 @@ impl Default for JwtGuardRouter {
         Self {
             guards: LookupMap::new(MAP_KEY),
             owner: env::current_account_id(),
+            pending_owner: None,
+            registrations: LookupMap::new(REGISTRATIONS_KEY),
+            routes: LookupMap::new(ROUTES_KEY),
+            removed_guards: LookupSet::new(REMOVED_GUARDS_KEY),
         }
     }
 }
@@ -51,6 +87,10 @@ impl JwtGuardRouter {
         Self {
             guards: LookupMap::new(MAP_KEY),
             owner,
+            pending_owner: None,
+            registrations: LookupMap::new(REGISTRATIONS_KEY),
+            routes: LookupMap::new(ROUTES_KEY),
+            removed_guards: LookupSet::new(REMOVED_GUARDS_KEY),
         }
     }
 
@@ -84,16 +124,24 @@ impl JwtGuardRouter {
     #[init(ignore_state)]
     pub fn migrate() -> Self {
         env::log_str("migrate");
-        if env::state_exists() {
-            env::log_str("state exists: migrating state");
-            let prev_state = env::state_read::<Self>().expect("Error: No previous state");
-            Self {
-                guards: prev_state.guards,
-                owner: prev_state.owner,
-            }
-        } else {
+        let Some(state) = env::storage_read(STATE_KEY) else {
             env::log_str("state does not exist: initializing default state");
-            Self::default()
+            return Self::default();
+        };
+
+        env::log_str("state exists: migrating state");
+        if let Ok(prev_state) = Self::try_from_slice(&state) {
+            return prev_state;
+        }
+
+        let prev_state = JwtGuardRouterV1::try_from_slice(&state).expect("Error: No previous state");
+        Self {
+            guards: prev_state.guards,
+            owner: prev_state.owner,
+            pending_owner: None,
+            registrations: LookupMap::new(REGISTRATIONS_KEY),
+            routes: LookupMap::new(ROUTES_KEY),
+            removed_guards: LookupSet::new(REMOVED_GUARDS_KEY),
         }
     }
 
@@ -107,35 +155,61 @@ impl JwtGuardRouter {
         self.owner.clone()
     }
 
-    /// Changes the owner of the contract to a new account
+    /// Returns the ownership transfer awaiting acceptance, if any
+    pub fn pending_owner(&self) -> Option<PendingOwner> {
+        self.pending_owner.clone()
+    }
+
+    /// Proposes a new owner, who takes over once they accept the ownership
     /// 
     /// # Arguments
-    /// * `new_owner` - The account ID that will become the new owner
+    /// * `new_owner` - The account ID proposed as the new owner
+    /// * `expires_in_ms` - Milliseconds after which the proposal can no longer be accepted, never if none
     /// 
     /// # Panics
-    /// Panics if the caller is not the current owner
-    pub fn change_owner(&mut self, new_owner: AccountId) {
+    /// Panics if the caller is not the current owner or proposes themselves
+    pub fn propose_owner(&mut self, new_owner: AccountId, expires_in_ms: Option<u64>) {
         self.only_owner();
-        self.owner = new_owner;
+        self.internal_propose_owner(new_owner, expires_in_ms);
     }
 
-    /// Adds a new guard to the contract
+    /// Cancels the ownership transfer awaiting acceptance
+    /// 
+    /// # Panics
+    /// Panics if the caller is not the current owner or there is no pending transfer
+    pub fn cancel_ownership_transfer(&mut self) {
+        self.only_owner();
+        self.internal_cancel_ownership_transfer();
+    }
+
+    /// Accepts the ownership transfer, making the caller the owner
+    /// 
+    /// # Panics
+    /// Panics if there is no pending transfer, the caller is not the proposed owner or the transfer expired
+    pub fn accept_ownership(&mut self) {
+        self.internal_accept_ownership();
+    }
+
+    /// Registers a new guard, making the caller its owner
     /// 
     /// # Arguments
     /// * `guard_name` - Name identifier for the guard
     /// * `guard_account` - Account ID of the guard
     /// 
     /// # Note
-    /// Requires attached deposit to cover storage costs
+    /// Requires attached deposit to cover storage costs, see `registration_deposit`.
+    /// Any surplus is refunded, and the deposit minus the storage cost of keeping the name
+    /// once removed is refunded when the guard is removed
     #[payable]
     pub fn add_guard(&mut self, guard_name: String, guard_account: AccountId) {
-        self.only_owner();
+        assert!(!guard_name.is_empty(), "Guard name cannot be empty");
         assert!(!guard_name.contains('#'), "Guard name cannot contain '#' character");
         assert!(guard_name.len() as u128 <= GUARD_NAME_MAX_BYTES_LENGTH, "Guard name is too long");
-        assert!(guard_account.as_str().len() as u128 <= MAX_ACCOUNT_BYTES_LENGTH, "Guard account is too long");
-        let required_deposit = env::storage_byte_cost().checked_mul(GUARD_NAME_MAX_BYTES_LENGTH + MAX_ACCOUNT_BYTES_LENGTH).unwrap().checked_add(NearToken::from_yoctonear(CONTINGENCY_DEPOSIT)).unwrap();
+        Self::assert_guard_account_length(&guard_account);
+        let required_deposit = self.registration_deposit();
+        let attached_deposit = env::attached_deposit();
         assert!(
-            env::attached_deposit() >= required_deposit,
+            attached_deposit >= required_deposit,
             "Insufficient deposit. Required: {}",
             required_deposit
         );
@@ -145,8 +219,53 @@ impl JwtGuardRouter {
             "Guard with name {} already exists",
             guard_name
         );
+        assert!(
+            !self.removed_guards.contains(&guard_name),
+            "Guard with name {} was removed and cannot be registered again",
+            guard_name
+        );
 
+        let owner = env::predecessor_account_id();
+        let deposit = required_deposit.saturating_sub(Self::removed_guard_cost());
         self.guards.insert(guard_name.clone(), guard_account.clone());
+        self.registrations.insert(guard_name.clone(), GuardRegistration { owner: owner.clone(), deposit });
+        JwtGuardRouterEvent::GuardAdded { guard_name: &guard_name, guard_account: &guard_account, owner: &owner }.emit();
+
+        let surplus = attached_deposit.saturating_sub(required_deposit);
+        if !surplus.is_zero() {
+            let _ = Promise::new(owner).transfer(surplus);
+        }
+    }
+
+    /// Returns the deposit `add_guard` requires
+    /// 
+    /// # Note
+    /// Computed from the maximum size of a guard, its registration and its route, plus a contingency amount,
+    /// all refunded when the guard is removed, and the storage cost of keeping its name once removed, which is not
+    pub fn registration_deposit(&self) -> NearToken {
+        env::storage_byte_cost()
+            .checked_mul(3 * GUARD_NAME_MAX_BYTES_LENGTH + 4 * MAX_ACCOUNT_BYTES_LENGTH + DEPOSIT_BYTES_LENGTH)
+            .unwrap()
+            .checked_add(NearToken::from_yoctonear(CONTINGENCY_DEPOSIT))
+            .unwrap()
+            .checked_add(Self::removed_guard_cost())
+            .unwrap()
+    }
+
+    /// Points a registered guard to another guard account
+    /// 
+    /// # Arguments
+    /// * `guard_name` - Name of the guard to update
+    /// * `guard_account` - Account ID of the new guard
+    /// 
+    /// # Note
+    /// Only callable by the guard owner or the contract owner
+    pub fn update_guard(&mut self, guard_name: String, guard_account: AccountId) {
+        self.assert_guard_manager(&guard_name);
+        Self::assert_guard_account_length(&guard_account);
+
+        self.guards.insert(guard_name.clone(), guard_account.clone());
+        JwtGuardRouterEvent::GuardUpdated { guard_name: &guard_name, guard_account: &guard_account }.emit();
     }
 
     /// Retrieves guard account ID by name
@@ -162,24 +281,113 @@ impl JwtGuardRouter {
         })
     }
 
+    /// Sets the fallback and shadow guards of a guard
+    /// 
+    /// # Arguments
+    /// * `guard_name` - Name of the guard
+    /// * `fallback` - Account ID of the guard verifying requests when the call to the guard fails, none to remove it
+    /// * `shadow` - Account ID of the guard receiving a copy of each request, whose result is only compared, none to remove it
+    /// 
+    /// # Note
+    /// Only callable by the guard owner or the contract owner
+    pub fn set_guard_route(&mut self, guard_name: String, fallback: Option<AccountId>, shadow: Option<AccountId>) {
+        self.assert_guard_manager(&guard_name);
+        fallback.iter().chain(shadow.iter()).for_each(Self::assert_guard_account_length);
+
+        JwtGuardRouterEvent::GuardRouteChanged { guard_name: &guard_name, fallback: fallback.as_ref(), shadow: shadow.as_ref() }.emit();
+        let route = GuardRoute { fallback, shadow };
+        if route == GuardRoute::default() {
+            self.routes.remove(&guard_name);
+        } else {
+            self.routes.insert(guard_name, route);
+        }
+    }
+
+    /// Retrieves the fallback and shadow guards of a guard
+    /// 
+    /// # Arguments
+    /// * `guard_name` - Name of the guard to look up
+    pub fn get_guard_route(&self, guard_name: String) -> GuardRoute {
+        self.routes.get(&guard_name).cloned().unwrap_or_default()
+    }
+
+    /// Retrieves the registration of a guard
+    /// 
+    /// # Arguments
+    /// * `guard_name` - Name of the guard to look up
+    /// 
+    /// # Returns
+    /// * The owner and deposit of the guard, none if it does not exist or was added before self-service registration
+    pub fn get_guard_registration(&self, guard_name: String) -> Option<GuardRegistration> {
+        self.registrations.get(&guard_name).cloned()
+    }
+
     /// Removes a guard from the contract
     /// 
     /// # Arguments
     /// * `guard_name` - Name of the guard to remove
     /// 
     /// # Note
-    /// Only callable by contract owner
-    /// Returns storage deposit to the guard account
+    /// Only callable by the guard owner or the contract owner
+    /// The name is kept so that no other account can register it and take over the wallets derived from it,
+    /// its storage being paid at registration
+    /// Returns the storage deposit of the registration to the guard owner
     pub fn remove_guard(&mut self, guard_name: String) {
-        self.only_owner();
+        self.assert_guard_manager(&guard_name);
+
+        self.guards.remove(&guard_name).unwrap();
+        self.routes.remove(&guard_name);
+        self.removed_guards.insert(guard_name.clone());
+        JwtGuardRouterEvent::GuardRemoved { guard_name: &guard_name }.emit();
+
+        if let Some(registration) = self.registrations.remove(&guard_name) {
+            if !registration.deposit.is_zero() {
+                let _ = Promise::new(registration.owner).transfer(registration.deposit);
+            }
+        }
+    }
 
+    /// Checks whether a guard name was removed, and so cannot be registered again
+    /// 
+    /// # Arguments
+    /// * `guard_name` - Name of the guard
+    pub fn is_guard_removed(&self, guard_name: String) -> bool {
+        self.removed_guards.contains(&guard_name)
+    }
+
+    /// Checks that a guard exists and the caller can manage it, panics if not
+    /// 
+    /// # Arguments
+    /// * `guard_name` - Name of the guard
+    /// 
+    /// # Panics
+    /// * If the guard does not exist
+    /// * If the caller is neither the guard owner nor the contract owner
+    fn assert_guard_manager(&self, guard_name: &str) {
         assert!(
-            self.guards.contains_key(&guard_name),
+            self.guards.contains_key(guard_name),
             "Guard with name {} does not exist",
             guard_name
         );
 
-        self.guards.remove(&guard_name).unwrap();
+        let caller = env::predecessor_account_id();
+        let is_guard_owner = self.registrations.get(guard_name).is_some_and(|registration| registration.owner == caller);
+        assert!(
+            is_guard_owner || caller == self.owner,
+            "Only the guard owner or the contract owner can call this function"
+        );
+    }
+
+    /// Returns the storage cost of keeping the longest guard name once removed
+    fn removed_guard_cost() -> NearToken {
+        env::storage_byte_cost()
+            .checked_mul(GUARD_NAME_MAX_BYTES_LENGTH + REMOVED_GUARD_OVERHEAD_BYTES_LENGTH)
+            .unwrap()
+    }
+
+    /// Checks that a guard account fits the storage paid for, panics if not
+    fn assert_guard_account_length(guard_account: &AccountId) {
+        assert!(guard_account.as_str().len() as u128 <= MAX_ACCOUNT_BYTES_LENGTH, "Guard account is too long");
     }
 
     /// Validates that a guard name follows the expected format of "jwt#GUARD_NAME"
@@ -211,13 +419,34 @@ impl JwtGuardRouter {
     /// * `jwt` - The JWT token to verify as a string
     /// * `sign_payload` - The payload to be signed by the MPC
     /// # Returns
+    /// * Promise resolving to the verification result of the guard, or of its fallback guard if the call to the guard fails
+    /// # Note
+    /// The shadow guard of the route, if any, verifies the same request with `verify_without_consuming`, so
+    /// that it never consumes the token, and its result is only compared to the guard's. Its call runs in
+    /// parallel with the guard's and shares the gas attached to `verify`
     pub fn verify(&self, guard_id: String, verify_payload: String, sign_payload: Vec<u8>, predecessor: AccountId) -> Promise {
         let (_, guard_name) = self.assert_guard_name_format(guard_id.clone());
         let guard_account = self.get_guard(guard_name.clone());
-
-        jwt_guard::ext(guard_account)
-            .verify(guard_name.clone(), verify_payload, sign_payload, predecessor)
-            .then(Self::ext(env::current_account_id()).on_verify_callback(guard_name))
+        let route = self.get_guard_route(guard_name.clone());
+
+        let verification = jwt_guard::ext(guard_account)
+            .verify(guard_name.clone(), verify_payload.clone(), sign_payload.clone(), predecessor.clone());
+        let fallback = route.fallback.map(|guard_account| FallbackRequest {
+            guard_account,
+            verify_payload: verify_payload.clone(),
+            sign_payload: sign_payload.clone(),
+            predecessor: predecessor.clone(),
+        });
+
+        match (route.shadow, fallback) {
+            (Some(shadow), fallback) => verification
+                .and(jwt_guard::ext(shadow.clone()).verify_without_consuming(guard_name.clone(), verify_payload, sign_payload, predecessor))
+                .then(Self::ext(env::current_account_id()).on_verify_shadow_callback(guard_name, shadow, fallback)),
+            (None, Some(fallback)) => verification
+                .then(Self::ext(env::current_account_id()).on_verify_fallback_callback(guard_name, fallback)),
+            (None, None) => verification
+                .then(Self::ext(env::current_account_id()).on_verify_callback(guard_name)),
+        }
     }
 
     /// Callback that processes the verification result
@@ -225,18 +454,65 @@ impl JwtGuardRouter {
     /// * `guard_name` - Name of the guard that was used for verification
     /// * `call_result` - Result from the guard verification containing:
     ///   * Boolean indicating verification success
-    ///   * String containing the user identifier
+    ///   * String containing the user identifier, or the rejection reason
     /// # Returns
+    /// * Tuple of the verification success, the user identifier or rejection reason, and the guard name
     pub fn on_verify_callback(&mut self, guard_name: String, #[callback_result] call_result: Result<(bool, String), PromiseError>) -> (bool, String, String) {
         if call_result.is_err() {
             env::panic_str(&format!("Error verifying JWT: {:?}", call_result.err().unwrap()));
         }
 
-        let (valid, sub) = call_result.unwrap();
+        let (valid, sub_or_reason) = call_result.unwrap();
         if valid {
-            (true, sub, guard_name)
+            (true, sub_or_reason, guard_name)
         } else {
-            (false, "".to_string(), "".to_string())
+            // Forward the rejection reason of the guard
+            (false, sub_or_reason, "".to_string())
+        }
+    }
+
+    /// Callback that processes the verification result, retrying on the fallback guard if the call to the guard failed
+    /// # Arguments
+    /// * `guard_name` - Name of the guard that was used for verification
+    /// * `fallback` - The request to retry on the fallback guard
+    /// * `call_result` - Result from the guard verification
+    /// # Returns
+    /// * The verification result, or a promise resolving to the one of the fallback guard
+    #[private]
+    pub fn on_verify_fallback_callback(&mut self, guard_name: String, fallback: FallbackRequest, #[callback_result] call_result: Result<(bool, String), PromiseError>) -> PromiseOrValue<(bool, String, String)> {
+        match call_result {
+            Ok(result) => PromiseOrValue::Value(self.on_verify_callback(guard_name, Ok(result))),
+            Err(_) => {
+                JwtGuardRouterEvent::FallbackUsed { guard_name: &guard_name, fallback_account: &fallback.guard_account }.emit();
+                PromiseOrValue::Promise(
+                    jwt_guard::ext(fallback.guard_account)
+                        .verify(guard_name.clone(), fallback.verify_payload, fallback.sign_payload, fallback.predecessor)
+                        .then(Self::ext(env::current_account_id()).on_verify_callback(guard_name)),
+                )
+            }
+        }
+    }
+
+    /// Callback that compares the verification results of the guard and its shadow guard, then processes the guard's
+    /// # Arguments
+    /// * `guard_name` - Name of the guard that was used for verification
+    /// * `shadow_account` - Account ID of the shadow guard
+    /// * `fallback` - The request to retry on the fallback guard, if the route has one
+    /// * `call_result` - Result from the guard verification
+    /// * `shadow_result` - Result from the shadow guard verification, which never affects the returned result
+    /// # Returns
+    /// * The verification result of the guard, or a promise resolving to the one of the fallback guard
+    #[private]
+    pub fn on_verify_shadow_callback(&mut self, guard_name: String, shadow_account: AccountId, fallback: Option<FallbackRequest>, #[callback_result] call_result: Result<(bool, String), PromiseError>, #[callback_result] shadow_result: Result<(bool, String), PromiseError>) -> PromiseOrValue<(bool, String, String)> {
+        let matched = call_result.as_ref().ok() == shadow_result.as_ref().ok();
+        if !matched {
+            env::log_str(&format!("Shadow guard result {:?} differs from guard result {:?}", shadow_result, call_result));
+        }
+        JwtGuardRouterEvent::ShadowCompared { guard_name: &guard_name, shadow_account: &shadow_account, matched }.emit();
+
+        match fallback {
+            Some(fallback) => self.on_verify_fallback_callback(guard_name, fallback, call_result),
+            None => PromiseOrValue::Value(self.on_verify_callback(guard_name, call_result)),
         }
     }
 }
@@ -269,6 +545,10 @@ mod tests {
         let contract = JwtGuardRouter {
             guards: LookupMap::new(MAP_KEY),
             owner: owner.clone(),
+            pending_owner: None,
+            registrations: LookupMap::new(REGISTRATIONS_KEY),
+            routes: LookupMap::new(ROUTES_KEY),
+            removed_guards: LookupSet::new(REMOVED_GUARDS_KEY),
         };
         
         assert_eq!(contract.owner(), owner);
@@ -289,6 +569,10 @@ mod tests {
         let mut contract = JwtGuardRouter {
             guards: LookupMap::new(MAP_KEY),
             owner: owner.clone(),
+            pending_owner: None,
+            registrations: LookupMap::new(REGISTRATIONS_KEY),
+            routes: LookupMap::new(ROUTES_KEY),
+            removed_guards: LookupSet::new(REMOVED_GUARDS_KEY),
         };
 
         // Insert the guard
@@ -307,6 +591,10 @@ mod tests {
         let contract = JwtGuardRouter {
             guards: LookupMap::new(MAP_KEY),
             owner: owner.clone(),
+            pending_owner: None,
+            registrations: LookupMap::new(REGISTRATIONS_KEY),
+            routes: LookupMap::new(ROUTES_KEY),
+            removed_guards: LookupSet::new(REMOVED_GUARDS_KEY),
         };
 
         contract.get_guard("non_existent".to_string());
@@ -324,6 +612,10 @@ mod tests {
         let mut contract = JwtGuardRouter {
             guards: LookupMap::new(MAP_KEY),
             owner: owner.clone(),
+            pending_owner: None,
+            registrations: LookupMap::new(REGISTRATIONS_KEY),
+            routes: LookupMap::new(ROUTES_KEY),
+            removed_guards: LookupSet::new(REMOVED_GUARDS_KEY),
         };
 
         // Add the guard that will be used for verification
@@ -336,6 +628,38 @@ mod tests {
         contract.verify("jwt#my-guard.com".to_string(), jwt, sign_payload, "predecessor".parse().unwrap());
     }
 
+    #[test]
+    fn test_accept_ownership_emits_event() {
+        let owner = accounts(1);
+        let new_owner = accounts(2);
+        testing_env!(get_context(owner.clone()).build());
+
+        let mut contract = JwtGuardRouter {
+            guards: LookupMap::new(MAP_KEY),
+            owner: owner.clone(),
+            pending_owner: None,
+            registrations: LookupMap::new(REGISTRATIONS_KEY),
+            routes: LookupMap::new(ROUTES_KEY),
+            removed_guards: LookupSet::new(REMOVED_GUARDS_KEY),
+        };
+
+        contract.propose_owner(new_owner.clone(), None);
+        assert_eq!(contract.owner(), owner);
+
+        testing_env!(get_context(new_owner.clone()).build());
+        contract.accept_ownership();
+
+        assert_eq!(contract.owner(), new_owner);
+        assert!(contract.pending_owner().is_none());
+        assert_eq!(
+            near_sdk::test_utils::get_logs(),
+            vec![format!(
+                r#"EVENT_JSON:{{"standard":"fast-auth","version":"1.0.0","event":"owner_changed","data":{{"old_owner":"{}","new_owner":"{}"}}}}"#,
+                owner, new_owner
+            )]
+        );
+    }
+
     #[test]
     fn test_update_contract_owner_success() {
         let owner = accounts(1);
@@ -345,6 +669,10 @@ mod tests {
         let contract = JwtGuardRouter {
             guards: LookupMap::new(MAP_KEY),
             owner: owner.clone(),
+            pending_owner: None,
+            registrations: LookupMap::new(REGISTRATIONS_KEY),
+            routes: LookupMap::new(ROUTES_KEY),
+            removed_guards: LookupSet::new(REMOVED_GUARDS_KEY),
         };
 
         // This should not panic for owner (input handling is tested in integration tests)
@@ -362,10 +690,238 @@ mod tests {
         let contract = JwtGuardRouter {
             guards: LookupMap::new(MAP_KEY),
             owner: owner.clone(),
+            pending_owner: None,
+            registrations: LookupMap::new(REGISTRATIONS_KEY),
+            routes: LookupMap::new(ROUTES_KEY),
+            removed_guards: LookupSet::new(REMOVED_GUARDS_KEY),
         };
 
         // This should panic because non-owner is calling
         contract.update_contract();
     }
 
+    #[test]
+    #[should_panic(expected = "Only the pending owner can accept the ownership")]
+    fn test_accept_ownership_other_account_fails() {
+        let owner = accounts(1);
+        testing_env!(get_context(owner.clone()).build());
+
+        let mut contract = JwtGuardRouter {
+            guards: LookupMap::new(MAP_KEY),
+            owner: owner.clone(),
+            pending_owner: None,
+            registrations: LookupMap::new(REGISTRATIONS_KEY),
+            routes: LookupMap::new(ROUTES_KEY),
+            removed_guards: LookupSet::new(REMOVED_GUARDS_KEY),
+        };
+        contract.propose_owner(accounts(2), None);
+
+        testing_env!(get_context(accounts(3)).build());
+        contract.accept_ownership();
+    }
+
+    #[test]
+    #[should_panic(expected = "No pending ownership transfer")]
+    fn test_cancel_ownership_transfer() {
+        let owner = accounts(1);
+        testing_env!(get_context(owner.clone()).build());
+
+        let mut contract = JwtGuardRouter {
+            guards: LookupMap::new(MAP_KEY),
+            owner: owner.clone(),
+            pending_owner: None,
+            registrations: LookupMap::new(REGISTRATIONS_KEY),
+            routes: LookupMap::new(ROUTES_KEY),
+            removed_guards: LookupSet::new(REMOVED_GUARDS_KEY),
+        };
+        contract.propose_owner(accounts(2), None);
+        contract.cancel_ownership_transfer();
+
+        testing_env!(get_context(accounts(2)).build());
+        contract.accept_ownership();
+    }
+
+    fn register_guard(contract: &mut JwtGuardRouter, registrant: AccountId) {
+        let mut context = get_context(registrant);
+        context.attached_deposit(NearToken::from_near(2));
+        testing_env!(context.build());
+        contract.add_guard("my-issuer".to_string(), "guard.near".parse().unwrap());
+    }
+
+    #[test]
+    fn test_add_guard_records_owner_and_deposit() {
+        let owner = accounts(1);
+        testing_env!(get_context(owner.clone()).build());
+
+        let mut contract = JwtGuardRouter::init(owner);
+        register_guard(&mut contract, accounts(2));
+
+        assert_eq!(contract.get_guard("my-issuer".to_string()), "guard.near".parse::<AccountId>().unwrap());
+        assert_eq!(
+            contract.get_guard_registration("my-issuer".to_string()),
+            Some(GuardRegistration {
+                owner: accounts(2),
+                deposit: contract.registration_deposit().saturating_sub(JwtGuardRouter::removed_guard_cost()),
+            })
+        );
+    }
+
+    #[test]
+    fn test_guard_owner_updates_and_removes_guard() {
+        let owner = accounts(1);
+        testing_env!(get_context(owner.clone()).build());
+
+        let mut contract = JwtGuardRouter::init(owner);
+        register_guard(&mut contract, accounts(2));
+
+        testing_env!(get_context(accounts(2)).build());
+        contract.update_guard("my-issuer".to_string(), "guard-v2.near".parse().unwrap());
+        assert_eq!(contract.get_guard("my-issuer".to_string()), "guard-v2.near".parse::<AccountId>().unwrap());
+
+        contract.remove_guard("my-issuer".to_string());
+        assert!(contract.get_guard_registration("my-issuer".to_string()).is_none());
+        assert!(!contract.guards.contains_key("my-issuer"));
+    }
+
+    #[test]
+    #[should_panic(expected = "Guard with name my-issuer was removed and cannot be registered again")]
+    fn test_register_removed_guard_fails() {
+        let owner = accounts(1);
+        testing_env!(get_context(owner.clone()).build());
+
+        let mut contract = JwtGuardRouter::init(owner);
+        register_guard(&mut contract, accounts(2));
+
+        testing_env!(get_context(accounts(2)).build());
+        contract.remove_guard("my-issuer".to_string());
+        assert!(contract.is_guard_removed("my-issuer".to_string()));
+
+        // Another account cannot take over the name, and with it the wallets of its users
+        register_guard(&mut contract, accounts(3));
+    }
+
+    #[test]
+    #[should_panic(expected = "Only the guard owner or the contract owner can call this function")]
+    fn test_update_guard_other_account_fails() {
+        let owner = accounts(1);
+        testing_env!(get_context(owner.clone()).build());
+
+        let mut contract = JwtGuardRouter::init(owner);
+        register_guard(&mut contract, accounts(2));
+
+        testing_env!(get_context(accounts(3)).build());
+        contract.update_guard("my-issuer".to_string(), "attacker.near".parse().unwrap());
+    }
+
+    #[test]
+    fn test_contract_owner_removes_guard() {
+        let owner = accounts(1);
+        testing_env!(get_context(owner.clone()).build());
+
+        let mut contract = JwtGuardRouter::init(owner.clone());
+        register_guard(&mut contract, accounts(2));
+
+        testing_env!(get_context(owner).build());
+        contract.remove_guard("my-issuer".to_string());
+        assert!(!contract.guards.contains_key("my-issuer"));
+    }
+
+    fn fallback_request() -> FallbackRequest {
+        FallbackRequest {
+            guard_account: "fallback.near".parse().unwrap(),
+            verify_payload: "test.jwt.token".to_string(),
+            sign_payload: vec![1, 2, 3],
+            predecessor: "predecessor".parse().unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_set_guard_route() {
+        let owner = accounts(1);
+        testing_env!(get_context(owner.clone()).build());
+
+        let mut contract = JwtGuardRouter::init(owner);
+        register_guard(&mut contract, accounts(2));
+
+        testing_env!(get_context(accounts(2)).build());
+        contract.set_guard_route("my-issuer".to_string(), Some("fallback.near".parse().unwrap()), Some("shadow.near".parse().unwrap()));
+        assert_eq!(contract.get_guard_route("my-issuer".to_string()).shadow, Some("shadow.near".parse().unwrap()));
+
+        // Routes with shadow and fallback guards still verify through the guard
+        let _ = contract.verify("jwt#my-issuer".to_string(), "test.jwt.token".to_string(), vec![1, 2, 3], "predecessor".parse().unwrap());
+
+        contract.set_guard_route("my-issuer".to_string(), None, None);
+        assert_eq!(contract.get_guard_route("my-issuer".to_string()), GuardRoute::default());
+    }
+
+    #[test]
+    #[should_panic(expected = "Only the guard owner or the contract owner can call this function")]
+    fn test_set_guard_route_other_account_fails() {
+        let owner = accounts(1);
+        testing_env!(get_context(owner.clone()).build());
+
+        let mut contract = JwtGuardRouter::init(owner);
+        register_guard(&mut contract, accounts(2));
+
+        testing_env!(get_context(accounts(3)).build());
+        contract.set_guard_route("my-issuer".to_string(), None, Some("shadow.near".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_fallback_used_when_guard_call_fails() {
+        let owner = accounts(1);
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = JwtGuardRouter::init(owner);
+
+        let verified = contract.on_verify_fallback_callback("auth0".to_string(), fallback_request(), Ok((true, "sub".to_string())));
+        assert!(matches!(verified, PromiseOrValue::Value((true, _, _))));
+        assert!(near_sdk::test_utils::get_logs().is_empty());
+
+        let retried = contract.on_verify_fallback_callback("auth0".to_string(), fallback_request(), Err(PromiseError::Failed));
+        assert!(matches!(retried, PromiseOrValue::Promise(_)));
+        assert_eq!(
+            near_sdk::test_utils::get_logs(),
+            vec![r#"EVENT_JSON:{"standard":"fast-auth","version":"1.0.0","event":"fallback_used","data":{"guard_name":"auth0","fallback_account":"fallback.near"}}"#]
+        );
+    }
+
+    #[test]
+    fn test_shadow_result_does_not_affect_verification() {
+        let owner = accounts(1);
+        testing_env!(get_context(owner.clone()).build());
+        let mut contract = JwtGuardRouter::init(owner);
+
+        let verified = contract.on_verify_shadow_callback("auth0".to_string(), "shadow.near".parse().unwrap(), None, Ok((true, "sub".to_string())), Err(PromiseError::Failed));
+        match verified {
+            PromiseOrValue::Value(result) => assert_eq!(result, (true, "sub".to_string(), "auth0".to_string())),
+            PromiseOrValue::Promise(_) => panic!("Expected a verification result"),
+        }
+        assert_eq!(
+            near_sdk::test_utils::get_logs().last().unwrap(),
+            r#"EVENT_JSON:{"standard":"fast-auth","version":"1.0.0","event":"shadow_compared","data":{"guard_name":"auth0","shadow_account":"shadow.near","matched":false}}"#
+        );
+    }
+
+    #[test]
+    fn test_on_verify_callback_forwards_rejection_reason() {
+        let owner = accounts(1);
+        let context = get_context(owner.clone());
+        testing_env!(context.build());
+
+        let mut contract = JwtGuardRouter {
+            guards: LookupMap::new(MAP_KEY),
+            owner: owner.clone(),
+            pending_owner: None,
+            registrations: LookupMap::new(REGISTRATIONS_KEY),
+            routes: LookupMap::new(ROUTES_KEY),
+            removed_guards: LookupSet::new(REMOVED_GUARDS_KEY),
+        };
+
+        let rejected = contract.on_verify_callback("auth0".to_string(), Ok((false, "Token expired".to_string())));
+        assert_eq!(rejected, (false, "Token expired".to_string(), "".to_string()));
+
+        let verified = contract.on_verify_callback("auth0".to_string(), Ok((true, "sub".to_string())));
+        assert_eq!(verified, (true, "sub".to_string(), "auth0".to_string()));
+    }
+
 }
@@ -0,0 +1,11 @@
+use near_sdk::{near, AccountId, NearToken};
+
+/// The account that registered a guard, and the refundable storage deposit it paid
+#[derive(Debug, Clone, PartialEq)]
+#[near(serializers=[borsh, json])]
+pub struct GuardRegistration {
+    /// The account that registered the guard, which can update or remove it
+    pub owner: AccountId,
+    /// The storage deposit, refunded to the owner when the guard is removed
+    pub deposit: NearToken,
+}
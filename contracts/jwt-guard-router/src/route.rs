@@ -0,0 +1,25 @@
+use near_sdk::{near, AccountId};
+
+/// The guards that back up and shadow the guard of a route
+#[derive(Debug, Clone, Default, PartialEq)]
+#[near(serializers=[borsh, json])]
+pub struct GuardRoute {
+    /// Guard account verifying the request when the call to the primary guard fails
+    pub fallback: Option<AccountId>,
+    /// Guard account receiving the same request without consuming its token, whose result is only compared to the primary one
+    pub shadow: Option<AccountId>,
+}
+
+/// A verification request to retry on the fallback guard
+#[derive(Debug, Clone, PartialEq)]
+#[near(serializers=[json])]
+pub struct FallbackRequest {
+    /// Account ID of the fallback guard
+    pub guard_account: AccountId,
+    /// The JWT to verify
+    pub verify_payload: String,
+    /// The payload to be signed by the MPC
+    pub sign_payload: Vec<u8>,
+    /// The original caller
+    pub predecessor: AccountId,
+}
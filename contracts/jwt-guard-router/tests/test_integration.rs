@@ -156,3 +156,73 @@ async fn test_remove_guard() -> Result<(), Box<dyn std::error::Error>> {
 
     Ok(())
 }
+
+#[tokio::test]
+async fn test_self_service_registration() -> Result<(), Box<dyn std::error::Error>> {
+    let contract_wasm = near_workspaces::compile_project("./").await?;
+
+    let sandbox = near_workspaces::sandbox().await?;
+    let owner = sandbox.dev_create_account().await?;
+    let registrant = sandbox.dev_create_account().await?;
+    let not_owner = sandbox.dev_create_account().await?;
+    let contract = sandbox.dev_deploy(&contract_wasm).await?;
+
+    // Initialize contract with owner
+    let _ = contract.call("init")
+        .args_json(json!({
+            "owner": owner.id(),
+        }))
+        .transact()
+        .await?;
+
+    // Anyone can register a guard by attaching the deposit
+    let outcome = registrant.call(contract.id(), "add_guard")
+        .args_json(json!({
+            "guard_name": "my-issuer",
+            "guard_account": "jwt.fast-auth.near"
+        }))
+        .deposit(NearToken::from_yoctonear(REQUIRED_DEPOSIT))
+        .transact()
+        .await?;
+
+    assert!(outcome.is_success());
+
+    let registration = contract.call("get_guard_registration")
+        .args_json(json!({
+            "guard_name": "my-issuer"
+        }))
+        .view()
+        .await?
+        .json::<serde_json::Value>()?;
+
+    assert_eq!(registration["owner"], registrant.id().to_string());
+    let deposit: u128 = registration["deposit"].as_str().unwrap().parse()?;
+
+    // Only the guard owner or the contract owner can update the guard
+    let outcome = not_owner.call(contract.id(), "update_guard")
+        .args_json(json!({
+            "guard_name": "my-issuer",
+            "guard_account": "attacker.near"
+        }))
+        .transact()
+        .await?;
+
+    assert!(!outcome.is_success());
+
+    // Removing the guard refunds the deposit to its owner. The contract owner removes it so that
+    // the guard owner pays no gas and its balance grows by the refund alone
+    let balance_before = registrant.view_account().await?.balance;
+    let outcome = owner.call(contract.id(), "remove_guard")
+        .args_json(json!({
+            "guard_name": "my-issuer"
+        }))
+        .transact()
+        .await?;
+
+    assert!(outcome.is_success());
+
+    let balance_after = registrant.view_account().await?.balance;
+    assert_eq!(balance_after, balance_before.saturating_add(NearToken::from_yoctonear(deposit)));
+
+    Ok(())
+}
@@ -0,0 +1,18 @@
+use near_sdk::{near, AccountId};
+
+/// NEP-297 events emitted by JWT guards
+#[near(event_json(standard = "fast-auth"))]
+pub enum JwtGuardEvent<'a> {
+    /// The public keys used to verify tokens were replaced
+    #[event_version("1.0.0")]
+    PublicKeysRotated { public_keys: u32 },
+    /// The Ed25519 public keys used to verify EdDSA tokens were replaced
+    #[event_version("1.0.0")]
+    OkpPublicKeysRotated { public_keys: u32 },
+    /// Replay protection was enabled or disabled
+    #[event_version("1.0.0")]
+    ReplayProtectionUpdated { enabled: bool },
+    /// The accounts whose verification requests consume tokens were replaced
+    #[event_version("1.0.0")]
+    TokenConsumersUpdated { consumers: &'a [AccountId] },
+}
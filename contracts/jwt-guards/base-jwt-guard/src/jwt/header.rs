@@ -0,0 +1,170 @@
+use near_sdk::{near, serde_json};
+use serde::{Deserialize, Serialize};
+use crate::jwt::codec::decode_base64_bytes;
+
+/// JWS signature algorithms a guard can accept
+#[near(serializers = [json, borsh])]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JwtAlgorithm {
+    /// RSASSA-PKCS1-v1_5 using SHA-256
+    RS256,
+    /// RSASSA-PKCS1-v1_5 using SHA-384
+    RS384,
+    /// RSASSA-PKCS1-v1_5 using SHA-512
+    RS512,
+    /// RSASSA-PSS using SHA-256 and MGF1 with SHA-256
+    PS256,
+    /// RSASSA-PSS using SHA-384 and MGF1 with SHA-384
+    PS384,
+    /// RSASSA-PSS using SHA-512 and MGF1 with SHA-512
+    PS512,
+    /// ECDSA using P-256 and SHA-256
+    ES256,
+    /// Edwards-curve signatures using Ed25519
+    EdDSA,
+}
+
+impl JwtAlgorithm {
+    /// Parses the `alg` header parameter
+    /// # Arguments
+    /// * `alg` - The algorithm name, as registered in RFC 7518
+    /// # Returns
+    /// * The algorithm, or none if it is not supported
+    pub fn from_name(alg: &str) -> Option<Self> {
+        match alg {
+            "RS256" => Some(Self::RS256),
+            "RS384" => Some(Self::RS384),
+            "RS512" => Some(Self::RS512),
+            "PS256" => Some(Self::PS256),
+            "PS384" => Some(Self::PS384),
+            "PS512" => Some(Self::PS512),
+            "ES256" => Some(Self::ES256),
+            "EdDSA" => Some(Self::EdDSA),
+            _ => None,
+        }
+    }
+
+    /// Whether the algorithm verifies signatures with the RSA public keys
+    pub fn is_rsa(&self) -> bool {
+        matches!(self, Self::RS256 | Self::RS384 | Self::RS512 | Self::PS256 | Self::PS384 | Self::PS512)
+    }
+}
+
+/// Header parameters registered in RFC 7515, which cannot be listed as critical
+const REGISTERED_PARAMETERS: &[&str] = &["alg", "jku", "jwk", "kid", "x5u", "x5c", "x5t", "x5t#S256", "typ", "cty", "crit"];
+
+/// Critical header parameters the guards understand
+const SUPPORTED_CRITICAL_PARAMETERS: &[&str] = &[];
+
+/// The JOSE header of a JWT
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct JwtHeader {
+    /// The algorithm the token is signed with
+    pub alg: String,
+    /// The media type of the token
+    pub typ: Option<String>,
+    /// The identifier of the key the token is signed with
+    pub kid: Option<String>,
+    /// The extension parameters the verifier must understand
+    pub crit: Option<Vec<String>>,
+}
+
+impl JwtHeader {
+    /// Decodes the base64url encoded header of a JWT
+    /// # Arguments
+    /// * `header` - The first segment of the JWT
+    /// # Returns
+    /// * The parsed header, or an error describing why it is invalid
+    pub fn decode(header: &str) -> Result<Self, String> {
+        serde_json::from_slice(&decode_base64_bytes(header.to_string())).map_err(|_| "Invalid JWT header".to_string())
+    }
+
+    /// Gets the algorithm the token is signed with
+    /// # Returns
+    /// * The algorithm, or none if it is not supported
+    pub fn algorithm(&self) -> Option<JwtAlgorithm> {
+        JwtAlgorithm::from_name(&self.alg)
+    }
+
+    /// Checks the header against the algorithms a guard accepts
+    /// # Arguments
+    /// * `allowed_algorithms` - The algorithms the guard accepts
+    /// # Returns
+    /// * The algorithm the token is signed with, or an error describing why the token is rejected
+    pub fn validate(&self, allowed_algorithms: &[JwtAlgorithm]) -> Result<JwtAlgorithm, String> {
+        let algorithm = self.algorithm()
+            .filter(|algorithm| allowed_algorithms.contains(algorithm))
+            .ok_or_else(|| format!("Unsupported algorithm '{}'", self.alg))?;
+
+        if let Some(crit) = &self.crit {
+            if crit.is_empty() {
+                return Err("Empty critical header parameters".to_string());
+            }
+            if let Some(parameter) = crit.iter().find(|parameter|
+                REGISTERED_PARAMETERS.contains(&parameter.as_str()) || !SUPPORTED_CRITICAL_PARAMETERS.contains(&parameter.as_str())
+            ) {
+                return Err(format!("Unsupported critical header parameter '{parameter}'"));
+            }
+        }
+
+        Ok(algorithm)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::base64::{self, Engine};
+
+    fn encode(header: &str) -> String {
+        base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(header)
+    }
+
+    #[test]
+    fn decode_header() {
+        let header = JwtHeader::decode(&encode(r#"{"alg":"RS256","typ":"JWT","kid":"key-1"}"#)).unwrap();
+        assert_eq!(header.alg, "RS256");
+        assert_eq!(header.typ.as_deref(), Some("JWT"));
+        assert_eq!(header.kid.as_deref(), Some("key-1"));
+        assert_eq!(header.crit, None);
+    }
+
+    #[test]
+    fn decode_invalid_header_fails() {
+        assert_eq!(JwtHeader::decode("not-a-header"), Err("Invalid JWT header".to_string()));
+        assert_eq!(JwtHeader::decode(&encode(r#"{"typ":"JWT"}"#)), Err("Invalid JWT header".to_string()));
+    }
+
+    #[test]
+    fn validate_algorithm() {
+        let header = JwtHeader::decode(&encode(r#"{"alg":"RS256"}"#)).unwrap();
+        assert_eq!(header.validate(&[JwtAlgorithm::RS256]), Ok(JwtAlgorithm::RS256));
+        assert_eq!(header.validate(&[]), Err("Unsupported algorithm 'RS256'".to_string()));
+
+        let header = JwtHeader::decode(&encode(r#"{"alg":"ES256"}"#)).unwrap();
+        assert_eq!(header.validate(&[JwtAlgorithm::RS256, JwtAlgorithm::ES256]), Ok(JwtAlgorithm::ES256));
+        assert_eq!(header.validate(&[JwtAlgorithm::RS256]), Err("Unsupported algorithm 'ES256'".to_string()));
+
+        let header = JwtHeader::decode(&encode(r#"{"alg":"PS512"}"#)).unwrap();
+        assert_eq!(header.validate(&[JwtAlgorithm::RS256, JwtAlgorithm::PS512]), Ok(JwtAlgorithm::PS512));
+        assert_eq!(header.validate(&[JwtAlgorithm::RS256]), Err("Unsupported algorithm 'PS512'".to_string()));
+
+        let header = JwtHeader::decode(&encode(r#"{"alg":"EdDSA"}"#)).unwrap();
+        assert_eq!(header.validate(&[JwtAlgorithm::EdDSA]), Ok(JwtAlgorithm::EdDSA));
+
+        let header = JwtHeader::decode(&encode(r#"{"alg":"none"}"#)).unwrap();
+        assert_eq!(header.validate(&[JwtAlgorithm::RS256]), Err("Unsupported algorithm 'none'".to_string()));
+    }
+
+    #[test]
+    fn validate_critical_parameters() {
+        let header = JwtHeader::decode(&encode(r#"{"alg":"RS256","crit":["exp"],"exp":1}"#)).unwrap();
+        assert_eq!(header.validate(&[JwtAlgorithm::RS256]), Err("Unsupported critical header parameter 'exp'".to_string()));
+
+        let header = JwtHeader::decode(&encode(r#"{"alg":"RS256","crit":["kid"]}"#)).unwrap();
+        assert!(header.validate(&[JwtAlgorithm::RS256]).is_err());
+
+        let header = JwtHeader::decode(&encode(r#"{"alg":"RS256","crit":[]}"#)).unwrap();
+        assert_eq!(header.validate(&[JwtAlgorithm::RS256]), Err("Empty critical header parameters".to_string()));
+    }
+}
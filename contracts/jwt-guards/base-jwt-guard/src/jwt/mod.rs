@@ -1 +1,2 @@
-pub mod codec;
\ No newline at end of file
+pub mod codec;
+pub mod header;
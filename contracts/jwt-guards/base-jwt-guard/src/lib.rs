@@ -1,7 +1,16 @@
 pub mod rsa;
+pub mod ec;
+pub mod okp;
 pub mod jwt;
 pub mod utils;
+pub mod replay;
+pub mod events;
+pub mod ownership;
 mod core;
 
 pub use core::*;
 pub use utils::*;
+pub use jwt::header::*;
+pub use replay::registry::*;
+pub use events::*;
+pub use ownership::*;
@@ -0,0 +1,101 @@
+use near_sdk::{env, near, AccountId};
+
+/// An ownership transfer awaiting acceptance by the proposed owner
+#[derive(Debug, Clone, PartialEq)]
+#[near(serializers=[borsh, json])]
+pub struct PendingOwner {
+    /// The proposed owner
+    pub account_id: AccountId,
+    /// Block timestamp in milliseconds after which the transfer can no longer be accepted
+    pub expires_at: Option<u64>,
+}
+
+impl PendingOwner {
+    /// Creates a transfer proposed in the current block
+    /// # Arguments
+    /// * `account_id` - The proposed owner
+    /// * `expires_in_ms` - Milliseconds after which the transfer can no longer be accepted, never if none
+    pub fn new(account_id: AccountId, expires_in_ms: Option<u64>) -> Self {
+        Self {
+            account_id,
+            expires_at: expires_in_ms.map(|expires_in_ms| env::block_timestamp_ms().saturating_add(expires_in_ms)),
+        }
+    }
+
+    /// Checks that the transfer can be accepted by an account
+    /// # Arguments
+    /// * `account_id` - The account accepting the transfer
+    /// # Panics
+    /// * If the account is not the proposed owner or the transfer expired
+    pub fn assert_acceptable_by(&self, account_id: &AccountId) {
+        assert!(account_id == &self.account_id, "Only the pending owner can accept the ownership");
+        if let Some(expires_at) = self.expires_at {
+            assert!(env::block_timestamp_ms() <= expires_at, "Ownership transfer expired");
+        }
+    }
+}
+
+/// NEP-297 events of the two-step ownership transfer
+#[near(event_json(standard = "fast-auth"))]
+pub enum OwnershipEvent<'a> {
+    /// The contract owner changed
+    #[event_version("1.0.0")]
+    OwnerChanged { old_owner: &'a AccountId, new_owner: &'a AccountId },
+    /// The owner proposed an ownership transfer. `expires_at` is a block timestamp in milliseconds
+    #[event_version("1.0.0")]
+    OwnershipTransferProposed { owner: &'a AccountId, pending_owner: &'a AccountId, expires_at: Option<u64> },
+    /// The owner cancelled the pending ownership transfer
+    #[event_version("1.0.0")]
+    OwnershipTransferCancelled { owner: &'a AccountId, pending_owner: &'a AccountId },
+}
+
+/// Two-step ownership transfer, where the proposed owner must accept the ownership before taking over
+///
+/// Callers check that the predecessor may propose or cancel a transfer before calling the flow
+pub trait Ownable {
+    /// Gets the mutable owner and ownership transfer awaiting acceptance of the contract
+    fn ownership_mut(&mut self) -> (&mut AccountId, &mut Option<PendingOwner>);
+
+    /// Proposes a new owner, replacing any transfer awaiting acceptance
+    /// # Arguments
+    /// * `new_owner` - The proposed owner
+    /// * `expires_in_ms` - Milliseconds after which the proposal can no longer be accepted, never if none
+    /// # Panics
+    /// * If the proposed owner is the current owner
+    fn internal_propose_owner(&mut self, new_owner: AccountId, expires_in_ms: Option<u64>) {
+        let (owner, pending_owner) = self.ownership_mut();
+        assert!(&new_owner != owner, "New owner must differ from the current owner");
+
+        let proposed = PendingOwner::new(new_owner, expires_in_ms);
+        OwnershipEvent::OwnershipTransferProposed {
+            owner,
+            pending_owner: &proposed.account_id,
+            expires_at: proposed.expires_at,
+        }.emit();
+        *pending_owner = Some(proposed);
+    }
+
+    /// Cancels the ownership transfer awaiting acceptance
+    /// # Panics
+    /// * If there is no pending transfer
+    fn internal_cancel_ownership_transfer(&mut self) {
+        let (owner, pending_owner) = self.ownership_mut();
+        let cancelled = pending_owner.take().unwrap_or_else(|| env::panic_str("No pending ownership transfer"));
+        OwnershipEvent::OwnershipTransferCancelled { owner, pending_owner: &cancelled.account_id }.emit();
+    }
+
+    /// Accepts the ownership transfer, making the predecessor the owner
+    /// # Returns
+    /// * The previous owner
+    /// # Panics
+    /// * If there is no pending transfer
+    /// * If the predecessor is not the proposed owner or the transfer expired
+    fn internal_accept_ownership(&mut self) -> AccountId {
+        let (owner, pending_owner) = self.ownership_mut();
+        let accepted = pending_owner.take().unwrap_or_else(|| env::panic_str("No pending ownership transfer"));
+        accepted.assert_acceptable_by(&env::predecessor_account_id());
+        let old_owner = std::mem::replace(owner, accepted.account_id);
+        OwnershipEvent::OwnerChanged { old_owner: &old_owner, new_owner: owner }.emit();
+        old_owner
+    }
+}
@@ -0,0 +1,267 @@
+use near_sdk::store::IterableMap;
+use near_sdk::{env, near, AccountId, IntoStorageKey, NearToken};
+
+/// Number of consumed entries inspected for expiration on every consumption
+const AUTO_PRUNE_LIMIT: u32 = 3;
+
+/// Registry of consumed JWTs used to reject replayed tokens
+///
+/// Each entry maps the SHA-256 hash of the token identifier (the `jti` claim, or the signing
+/// input `header.payload` when no `jti` is present) to the token expiration. Entries are garbage-collected
+/// once their `exp` has passed, since an expired token is rejected by the claims check anyway.
+#[near(serializers = [borsh])]
+pub struct ReplayProtection {
+    /// Whether consumed tokens are recorded and replays rejected
+    enabled: bool,
+    /// Mapping of token hashes to their expiration (seconds)
+    consumed: IterableMap<Vec<u8>, u64>,
+    /// Index where the next garbage collection pass starts
+    prune_cursor: u32,
+    /// Storage bytes currently used by the consumed entries
+    storage_usage: u64,
+    /// Accounts whose verification requests consume tokens, such as the router or FastAuth
+    consumers: Vec<AccountId>,
+}
+
+/// Summary of the replay protection state
+#[near(serializers = [json])]
+pub struct ReplayProtectionView {
+    pub enabled: bool,
+    pub consumed_tokens: u32,
+    pub storage_usage: u64,
+    pub storage_cost: NearToken,
+    pub consumers: Vec<AccountId>,
+}
+
+impl ReplayProtection {
+    /// Creates a new, disabled, registry
+    /// # Arguments
+    /// * `prefix` - The storage prefix of the consumed tokens map
+    pub fn new<S: IntoStorageKey>(prefix: S) -> Self {
+        Self {
+            enabled: false,
+            consumed: IterableMap::new(prefix),
+            prune_cursor: 0,
+            storage_usage: 0,
+            consumers: vec![],
+        }
+    }
+
+    /// Whether replay protection is enabled
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Enables or disables replay protection
+    /// # Arguments
+    /// * `enabled` - The new state
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    /// Sets the accounts whose verification requests consume tokens
+    /// # Arguments
+    /// * `consumers` - The accounts, replacing the current ones
+    pub fn set_consumers(&mut self, consumers: Vec<AccountId>) {
+        self.consumers = consumers;
+    }
+
+    /// Whether the verification requests of an account consume tokens
+    /// # Arguments
+    /// * `account_id` - The account requesting the verification
+    pub fn is_consumer(&self, account_id: &AccountId) -> bool {
+        self.consumers.contains(account_id)
+    }
+
+    /// Computes the registry key of a token
+    /// # Arguments
+    /// * `issuer` - The issuer the token was verified against
+    /// * `jti` - The `jti` claim of the token, if any
+    /// * `jwt` - The complete token
+    /// # Returns
+    /// * SHA-256 of `issuer#jti`, or of the signing input `header.payload` when it has no `jti`.
+    ///   The signature is left out since signatures can be malleable, such as `(r, n - s)` for ES256
+    pub fn token_key(issuer: &str, jti: Option<&str>, jwt: &str) -> Vec<u8> {
+        match jti {
+            Some(jti) => env::sha256(format!("{issuer}#{jti}").as_bytes()),
+            None => {
+                let signing_input = jwt.rsplit_once('.').map_or(jwt, |(signing_input, _)| signing_input);
+                env::sha256(signing_input.as_bytes())
+            }
+        }
+    }
+
+    /// Checks whether a token key has already been consumed
+    /// # Arguments
+    /// * `key` - The token key
+    pub fn is_consumed(&self, key: &[u8]) -> bool {
+        self.consumed.contains_key(key)
+    }
+
+    /// Records a token as consumed
+    /// # Arguments
+    /// * `key` - The token key
+    /// * `exp` - The token expiration (seconds)
+    /// # Returns
+    /// * `Err` with the rejection reason if the token was already consumed or the contract
+    ///   balance cannot cover the new entry
+    pub fn consume(&mut self, key: Vec<u8>, exp: u64) -> Result<(), String> {
+        self.prune(AUTO_PRUNE_LIMIT);
+
+        if self.consumed.contains_key(&key) {
+            return Err("Token already used".to_string());
+        }
+
+        let initial_storage = env::storage_usage();
+        self.consumed.insert(key.clone(), exp);
+        self.consumed.flush();
+        let used = env::storage_usage().saturating_sub(initial_storage);
+
+        let required_balance = env::storage_byte_cost().saturating_mul(env::storage_usage() as u128);
+        if env::account_balance() < required_balance {
+            self.consumed.remove(&key);
+            self.consumed.flush();
+            return Err("Insufficient balance to store consumed token".to_string());
+        }
+
+        self.storage_usage = self.storage_usage.saturating_add(used);
+        Ok(())
+    }
+
+    /// Removes expired entries
+    /// # Arguments
+    /// * `limit` - The maximum number of entries to inspect
+    /// # Returns
+    /// * The number of removed entries
+    pub fn prune(&mut self, limit: u32) -> u32 {
+        let len = self.consumed.len();
+        if len == 0 {
+            self.prune_cursor = 0;
+            return 0;
+        }
+
+        let now = env::block_timestamp_ms() / 1000;
+        let start = if self.prune_cursor < len { self.prune_cursor } else { 0 };
+        // The pass wraps around to the first entries, so every entry is inspected when the limit allows it
+        let inspected = limit.min(len);
+        let expired: Vec<Vec<u8>> = self.consumed
+            .iter()
+            .skip(start as usize)
+            .chain(self.consumed.iter().take(start as usize))
+            .take(inspected as usize)
+            .filter(|(_, exp)| **exp <= now)
+            .map(|(key, _)| key.clone())
+            .collect();
+
+        let initial_storage = env::storage_usage();
+        for key in expired.iter() {
+            self.consumed.remove(key);
+        }
+        self.consumed.flush();
+        let freed = initial_storage.saturating_sub(env::storage_usage());
+        self.storage_usage = self.storage_usage.saturating_sub(freed);
+
+        // Removed entries are replaced by the last ones, so only the kept entries are skipped
+        let remaining = self.consumed.len();
+        self.prune_cursor = if remaining == 0 { 0 } else { (start + inspected - expired.len() as u32) % remaining };
+
+        expired.len() as u32
+    }
+
+    /// Gets a summary of the replay protection state
+    pub fn view(&self) -> ReplayProtectionView {
+        ReplayProtectionView {
+            enabled: self.enabled,
+            consumed_tokens: self.consumed.len(),
+            storage_usage: self.storage_usage,
+            storage_cost: env::storage_byte_cost().saturating_mul(self.storage_usage as u128),
+            consumers: self.consumers.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::testing_env;
+
+    fn set_time(seconds: u64) {
+        let mut context = VMContextBuilder::new();
+        context.block_timestamp(seconds * 1_000_000_000);
+        testing_env!(context.build());
+    }
+
+    #[test]
+    fn token_key_uses_jti_when_present() {
+        let with_jti = ReplayProtection::token_key("issuer", Some("id"), "a.b.c");
+        let other_token_same_jti = ReplayProtection::token_key("issuer", Some("id"), "d.e.f");
+        let without_jti = ReplayProtection::token_key("issuer", None, "a.b.c");
+
+        assert_eq!(with_jti, other_token_same_jti);
+        assert_ne!(with_jti, without_jti);
+        assert_ne!(with_jti, ReplayProtection::token_key("other", Some("id"), "a.b.c"));
+    }
+
+    #[test]
+    fn token_key_ignores_signature() {
+        let key = ReplayProtection::token_key("issuer", None, "a.b.c");
+        assert_eq!(key, ReplayProtection::token_key("issuer", None, "a.b.d"));
+        assert_ne!(key, ReplayProtection::token_key("issuer", None, "a.e.c"));
+    }
+
+    #[test]
+    fn consume_rejects_replay() {
+        set_time(100);
+        let mut registry = ReplayProtection::new(b"r".to_vec());
+        let key = ReplayProtection::token_key("issuer", Some("id"), "a.b.c");
+
+        assert!(registry.consume(key.clone(), 200).is_ok());
+        assert!(registry.is_consumed(&key));
+        assert_eq!(registry.consume(key, 200), Err("Token already used".to_string()));
+        assert_eq!(registry.view().consumed_tokens, 1);
+        assert!(registry.view().storage_usage > 0);
+    }
+
+    #[test]
+    fn prune_removes_only_expired_entries() {
+        set_time(100);
+        let mut registry = ReplayProtection::new(b"r".to_vec());
+        registry.consume(vec![1], 150).unwrap();
+        registry.consume(vec![2], 300).unwrap();
+        registry.consume(vec![3], 150).unwrap();
+
+        set_time(200);
+        assert_eq!(registry.prune(10), 2);
+        assert!(!registry.is_consumed(&[1]));
+        assert!(registry.is_consumed(&[2]));
+        assert!(!registry.is_consumed(&[3]));
+        assert_eq!(registry.view().consumed_tokens, 1);
+    }
+
+    #[test]
+    fn prune_resumes_from_cursor() {
+        set_time(100);
+        let mut registry = ReplayProtection::new(b"r".to_vec());
+        registry.consume(vec![1], 300).unwrap();
+        registry.consume(vec![2], 300).unwrap();
+        registry.consume(vec![3], 150).unwrap();
+
+        set_time(200);
+        assert_eq!(registry.prune(2), 0);
+        assert_eq!(registry.prune(2), 1);
+        assert!(!registry.is_consumed(&[3]));
+    }
+
+    #[test]
+    fn expired_entries_are_collected_on_consume() {
+        set_time(100);
+        let mut registry = ReplayProtection::new(b"r".to_vec());
+        registry.consume(vec![1], 150).unwrap();
+
+        set_time(200);
+        registry.consume(vec![2], 300).unwrap();
+        assert!(!registry.is_consumed(&[1]));
+        assert_eq!(registry.view().consumed_tokens, 1);
+    }
+}
@@ -0,0 +1,99 @@
+use sha2::{Digest, Sha256, Sha384, Sha512};
+
+/// ASN.1 DER encoded prefix for SHA-256 algorithm identifier
+/// This prefix is prepended to the message hash during RSA signature verification
+/// as specified in PKCS#1 v1.5
+const SHA256_PREFIX: &[u8] = &[
+    0x30, 0x31, 0x30, 0x0d, 0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x01, 0x05, 0x00, 0x04, 0x20
+];
+
+/// ASN.1 DER encoded prefix for SHA-384 algorithm identifier
+const SHA384_PREFIX: &[u8] = &[
+    0x30, 0x41, 0x30, 0x0d, 0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x02, 0x05, 0x00, 0x04, 0x30
+];
+
+/// ASN.1 DER encoded prefix for SHA-512 algorithm identifier
+const SHA512_PREFIX: &[u8] = &[
+    0x30, 0x51, 0x30, 0x0d, 0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x03, 0x05, 0x00, 0x04, 0x40
+];
+
+/// Hash functions RSA signatures are computed over
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RsaHash {
+    Sha256,
+    Sha384,
+    Sha512,
+}
+
+impl RsaHash {
+    /// Hashes the data
+    /// # Arguments
+    /// * `data` - The data to hash
+    /// # Returns
+    /// * The digest, of `output_len` bytes
+    pub fn digest(&self, data: &[u8]) -> Vec<u8> {
+        match self {
+            Self::Sha256 => Sha256::digest(data).to_vec(),
+            Self::Sha384 => Sha384::digest(data).to_vec(),
+            Self::Sha512 => Sha512::digest(data).to_vec(),
+        }
+    }
+
+    /// Gets the byte length of the digests
+    pub fn output_len(&self) -> usize {
+        match self {
+            Self::Sha256 => 32,
+            Self::Sha384 => 48,
+            Self::Sha512 => 64,
+        }
+    }
+
+    /// Gets the DER encoded DigestInfo prefix of the PKCS#1 v1.5 encoding
+    pub fn digest_info_prefix(&self) -> &'static [u8] {
+        match self {
+            Self::Sha256 => SHA256_PREFIX,
+            Self::Sha384 => SHA384_PREFIX,
+            Self::Sha512 => SHA512_PREFIX,
+        }
+    }
+
+    /// Generates a mask with MGF1 (RFC 8017 appendix B.2.1)
+    /// # Arguments
+    /// * `seed` - The seed the mask is generated from
+    /// * `mask_len` - The byte length of the mask
+    /// # Returns
+    /// * The mask
+    pub fn mgf1(&self, seed: &[u8], mask_len: usize) -> Vec<u8> {
+        let mut mask = Vec::with_capacity(mask_len + self.output_len());
+        let mut counter: u32 = 0;
+        while mask.len() < mask_len {
+            mask.extend_from_slice(&self.digest(&[seed, &counter.to_be_bytes()[..]].concat()));
+            counter += 1;
+        }
+        mask.truncate(mask_len);
+        mask
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn digest_lengths_match_prefixes() {
+        for hash in [RsaHash::Sha256, RsaHash::Sha384, RsaHash::Sha512] {
+            assert_eq!(hash.digest(b"payload").len(), hash.output_len());
+            // The DigestInfo prefix ends with the OCTET STRING length of the digest
+            assert_eq!(*hash.digest_info_prefix().last().unwrap() as usize, hash.output_len());
+            assert_eq!(hash.digest_info_prefix()[1] as usize, hash.digest_info_prefix().len() - 2 + hash.output_len());
+        }
+    }
+
+    #[test]
+    fn mgf1_concatenates_counter_digests() {
+        let mask = RsaHash::Sha256.mgf1(b"seed", 40);
+        assert_eq!(mask.len(), 40);
+        assert_eq!(mask[..32], RsaHash::Sha256.digest(b"seed\x00\x00\x00\x00")[..]);
+        assert_eq!(mask[32..], RsaHash::Sha256.digest(b"seed\x00\x00\x00\x01")[..8]);
+    }
+}
@@ -1,2 +1,32 @@
+use crate::jwt::header::JwtAlgorithm;
+use crate::rsa::hash::RsaHash;
+use crate::rsa::pss::verify_pss_signature;
+use crate::rsa::rs256::verify_pkcs1v15_signature;
+
+pub mod hash;
 pub mod key;
-pub mod rs256;
\ No newline at end of file
+pub mod pss;
+pub mod rs256;
+
+/// Verifies an RSA signature with the scheme and hash function of a JWS algorithm
+///
+/// # Arguments
+/// * `algorithm` - The algorithm named in the token header
+/// * `payload` - The data that was signed
+/// * `signature_bytes` - The RSA signature to verify, as a byte vector
+/// * `n` - The RSA public key modulus component, as a byte vector
+/// * `e` - The RSA public key exponent component, as a byte vector
+///
+/// # Returns
+/// * `bool` - True if the signature is valid, false otherwise or if the algorithm is not an RSA one
+pub fn verify_rsa_signature(algorithm: JwtAlgorithm, payload: String, signature_bytes: Vec<u8>, n: Vec<u8>, e: Vec<u8>) -> bool {
+    match algorithm {
+        JwtAlgorithm::RS256 => verify_pkcs1v15_signature(RsaHash::Sha256, payload, signature_bytes, n, e),
+        JwtAlgorithm::RS384 => verify_pkcs1v15_signature(RsaHash::Sha384, payload, signature_bytes, n, e),
+        JwtAlgorithm::RS512 => verify_pkcs1v15_signature(RsaHash::Sha512, payload, signature_bytes, n, e),
+        JwtAlgorithm::PS256 => verify_pss_signature(RsaHash::Sha256, payload, signature_bytes, n, e),
+        JwtAlgorithm::PS384 => verify_pss_signature(RsaHash::Sha384, payload, signature_bytes, n, e),
+        JwtAlgorithm::PS512 => verify_pss_signature(RsaHash::Sha512, payload, signature_bytes, n, e),
+        JwtAlgorithm::ES256 | JwtAlgorithm::EdDSA => false,
+    }
+}
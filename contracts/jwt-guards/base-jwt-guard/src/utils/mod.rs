@@ -1,4 +1,8 @@
-use crate::core::JwtPublicKey;
+use crate::core::{JwtEcPublicKey, JwtOkpPublicKey, JwtPublicKey};
+use crate::ec::es256::{sec1_from_coordinates, COORDINATE_LENGTH};
+use crate::okp::eddsa::ED25519_PUBLIC_KEY_LENGTH;
+use crate::rsa::rs256::SUPPORTED_KEY_BITS;
+use p256::ecdsa::VerifyingKey;
 use crypto_bigint::{BoxedUint, Odd};
 use near_sdk::require as require_err;
 
@@ -9,10 +13,10 @@ use near_sdk::require as require_err;
 /// * If the public key is invalid
 pub fn assert_valid_public_key(public_key: JwtPublicKey)  {
     require_err!(
-        public_key.n.len() == 256,
+        SUPPORTED_KEY_BITS.iter().any(|bits| public_key.n.len() * 8 == *bits as usize),
         "invalid n component length"
     );
-    let n_int = BoxedUint::from_be_slice(&public_key.n, 2048).unwrap();
+    let n_int = BoxedUint::from_be_slice(&public_key.n, public_key.n.len() as u32 * 8).unwrap();
 
     require_err!(
         Odd::new(n_int).is_some().unwrap_u8() == 1,
@@ -23,4 +27,44 @@ pub fn assert_valid_public_key(public_key: JwtPublicKey)  {
         allowed_e.contains(&public_key.e.as_slice()),
         "invalid e component"
     );
+    require_err!(
+        public_key.kid.as_ref().is_none_or(|kid| !kid.is_empty()),
+        "invalid kid"
+    );
+}
+
+/// Asserts that the ECDSA P-256 public key is valid
+/// # Arguments
+/// * `public_key` - The JwtEcPublicKey to assert
+/// # Panics
+/// * If the public key is invalid
+pub fn assert_valid_ec_public_key(public_key: JwtEcPublicKey) {
+    require_err!(
+        public_key.x.len() == COORDINATE_LENGTH && public_key.y.len() == COORDINATE_LENGTH,
+        "invalid coordinates length"
+    );
+    require_err!(
+        VerifyingKey::from_sec1_bytes(&sec1_from_coordinates(&public_key.x, &public_key.y)).is_ok(),
+        "point is not on the P-256 curve"
+    );
+    require_err!(
+        public_key.kid.as_ref().is_none_or(|kid| !kid.is_empty()),
+        "invalid kid"
+    );
+}
+
+/// Asserts that the Ed25519 public key is valid
+/// # Arguments
+/// * `public_key` - The JwtOkpPublicKey to assert
+/// # Panics
+/// * If the public key is invalid
+pub fn assert_valid_okp_public_key(public_key: JwtOkpPublicKey) {
+    require_err!(
+        public_key.x.len() == ED25519_PUBLIC_KEY_LENGTH,
+        "invalid x component length"
+    );
+    require_err!(
+        public_key.kid.as_ref().is_none_or(|kid| !kid.is_empty()),
+        "invalid kid"
+    );
 }
@@ -1,6 +1,6 @@
 use near_sdk::{near, AccountId};
 use std::collections::{HashMap, HashSet};
-use base_jwt_guard::{JwtPublicKey, assert_valid_public_key};
+use base_jwt_guard::{JwtOkpPublicKey, JwtPublicKey, assert_valid_okp_public_key, assert_valid_public_key};
 use crate::{
     error::CustomIssuerGuardError,
     require_err,
@@ -12,6 +12,8 @@ use super::Role;
 #[derive(Clone)]
 pub struct CustomIssuerGuardConfig {
     pub public_keys: Vec<JwtPublicKey>,
+    /// The Ed25519 public keys verifying EdDSA tokens, none if the issuer only signs RS256 tokens
+    pub okp_public_keys: Option<Vec<JwtOkpPublicKey>>,
     pub roles: RolesConfig,
 }
 
@@ -26,6 +28,9 @@ impl CustomIssuerGuardConfig {
         for public_key in self.public_keys.iter() {
             assert_valid_public_key(public_key.clone());
         }
+        for public_key in self.okp_public_keys.iter().flatten() {
+            assert_valid_okp_public_key(public_key.clone());
+        }
         // Roles validation
         self.roles.assert_valid();
     }
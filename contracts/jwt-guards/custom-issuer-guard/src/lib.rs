@@ -2,8 +2,8 @@ use std::slice::Iter;
 use borsh::{BorshDeserialize};
 use near_sdk::{near, AccountId, env, PanicOnDefault, Promise, Gas, ext_contract};
 use near_sdk::serde_json;
-use base_jwt_guard::{JwtGuard, JwtPublicKey};
-use base_jwt_guard::assert_valid_public_key;
+use base_jwt_guard::{JwtAlgorithm, JwtGuard, JwtGuardEvent, JwtOkpPublicKey, JwtPublicKey, JwtPublicKeyV1, ReplayProtectedJwtGuard, ReplayProtection, ReplayProtectionView};
+use base_jwt_guard::{assert_valid_okp_public_key, assert_valid_public_key};
 use near_plugins::{access_control, access_control_any, AccessControlRole, AccessControllable, Upgradable};
 use serde::{Deserialize, Serialize};
 use crate::config::{CustomIssuerGuardConfig, RolesConfig};
@@ -13,10 +13,13 @@ mod config;
 mod error;
 mod utils;
 
+const STATE_KEY: &[u8] = b"STATE";
+
 // External contract interface for AttestationContract
 #[ext_contract(attestation_contract)]
 pub trait AttestationContract {
     fn get_public_keys(&self) -> Vec<JwtPublicKey>;
+    fn get_okp_public_keys(&self) -> Vec<JwtOkpPublicKey>;
 }
 
 #[near(serializers = [json])]
@@ -64,6 +67,15 @@ pub struct CustomClaims {
 pub struct CustomIssuerGuard {
     public_keys: Vec<JwtPublicKey>,
     attestation_contract: AccountId,
+    replay_protection: ReplayProtection,
+    okp_public_keys: Vec<JwtOkpPublicKey>,
+}
+
+/// CustomIssuerGuard state layout prior to replay protection, used for state migration
+#[near(serializers = [borsh])]
+pub struct CustomIssuerGuardV1 {
+    public_keys: Vec<JwtPublicKeyV1>,
+    attestation_contract: AccountId,
 }
 
 #[derive(near_sdk::BorshStorageKey)]
@@ -71,6 +83,7 @@ pub struct CustomIssuerGuard {
 pub enum Prefix {
     JwtClaims,
     JwtHashClaims,
+    ConsumedTokens,
 }
 
 #[near(serializers = [json, borsh])]
@@ -93,11 +106,32 @@ impl CustomIssuerGuard {
         let mut this = Self {
             public_keys: config.public_keys,
             attestation_contract,
+            replay_protection: ReplayProtection::new(Prefix::ConsumedTokens),
+            okp_public_keys: config.okp_public_keys.unwrap_or_default(),
         };
         this.init_acl(config.roles);
         this
     }
 
+    /// Migrates the contract state
+    /// # Returns
+    /// * The migrated contract state
+    #[private]
+    #[init(ignore_state)]
+    pub fn migrate() -> Self {
+        let state = env::storage_read(STATE_KEY).expect("Error: No previous state");
+        if let Ok(prev_state) = Self::try_from_slice(&state) {
+            return prev_state;
+        }
+        let prev_state = CustomIssuerGuardV1::try_from_slice(&state).expect("Error: Cannot deserialize previous state");
+        Self {
+            public_keys: prev_state.public_keys.into_iter().map(JwtPublicKey::from).collect(),
+            attestation_contract: prev_state.attestation_contract,
+            replay_protection: ReplayProtection::new(Prefix::ConsumedTokens),
+            okp_public_keys: vec![],
+        }
+    }
+
     /// Initializes the ACL
     /// # Arguments
     /// * `roles` - The RolesConfig
@@ -160,6 +194,7 @@ impl CustomIssuerGuard {
                     .map(|key| JwtPublicKey {
                         n: key.n,
                         e: key.e,
+                        kid: key.kid,
                     })
                     .collect();
 
@@ -169,8 +204,8 @@ impl CustomIssuerGuard {
                 }
 
                 // Set the public keys
+                JwtGuardEvent::PublicKeysRotated { public_keys: jwt_public_keys.len() as u32 }.emit();
                 self.public_keys = jwt_public_keys;
-                env::log_str("Public keys successfully updated from AttestationContract");
             }
             Err(e) => {
                 env::panic_str(&format!("Failed to fetch public keys from AttestationContract: {:?}", e));
@@ -178,6 +213,41 @@ impl CustomIssuerGuard {
         }
     }
 
+    /// Fetches the Ed25519 public keys verifying the EdDSA tokens of the issuer from the AttestationContract
+    /// and sets them in a callback
+    /// # Returns
+    /// * `Promise` - A promise that resolves when the public keys are fetched and set
+    pub fn set_okp_public_keys(&mut self) -> Promise {
+        attestation_contract::ext(self.attestation_contract.clone())
+            .with_static_gas(Gas::from_tgas(5))
+            .get_okp_public_keys()
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(Gas::from_tgas(5))
+                    .on_set_okp_public_keys_callback()
+            )
+    }
+
+    /// Callback to handle the Ed25519 public keys fetched from AttestationContract
+    /// # Arguments
+    /// * `public_keys` - The Ed25519 public keys fetched from the AttestationContract
+    /// # Panics
+    /// Panics if the callback result is an error or if public key validation fails
+    #[private]
+    pub fn on_set_okp_public_keys_callback(
+        &mut self,
+        #[callback_result] public_keys_result: Result<Vec<JwtOkpPublicKey>, near_sdk::PromiseError>,
+    ) {
+        let public_keys = public_keys_result.unwrap_or_else(|e| {
+            env::panic_str(&format!("Failed to fetch Ed25519 public keys from AttestationContract: {:?}", e))
+        });
+        for public_key in public_keys.iter() {
+            assert_valid_okp_public_key(public_key.clone());
+        }
+        JwtGuardEvent::OkpPublicKeysRotated { public_keys: public_keys.len() as u32 }.emit();
+        self.okp_public_keys = public_keys;
+    }
+
     /// Sets the attestation contract address
     /// # Arguments
     /// * `attestation_contract` - The new attestation contract account ID
@@ -198,6 +268,8 @@ impl CustomIssuerGuard {
 
     /// Verifies a JWT token and its custom claims
     ///
+    /// With replay protection enabled, only the verifications requested by the token consumers consume the token
+    ///
     /// # Arguments
     /// * `jwt` - The JWT token to verify as a string
     /// * `sign_payload` - The payload to verify against the JWT
@@ -206,10 +278,72 @@ impl CustomIssuerGuard {
     /// * Tuple containing:
     ///   * Boolean indicating if verification succeeded
     ///   * String containing either the subject claim or error message
-    pub fn verify(&self, issuer: String, jwt: String, sign_payload: Vec<u8>, predecessor: AccountId) -> (bool, String) {
-        self.internal_verify(issuer, jwt, sign_payload, predecessor)
+    pub fn verify(&mut self, issuer: String, jwt: String, sign_payload: Vec<u8>, predecessor: AccountId) -> (bool, String) {
+        self.internal_verify_once(issuer, jwt, sign_payload, predecessor)
     }
 
+    /// Verifies a JWT token and its custom claims like `verify`, but never consumes the token, as for
+    /// the shadow guard requests of the router
+    ///
+    /// # Arguments
+    /// * `jwt` - The JWT token to verify as a string
+    /// * `sign_payload` - The payload to verify against the JWT
+    ///
+    /// # Returns
+    /// * Tuple containing:
+    ///   * Boolean indicating if verification succeeded
+    ///   * String containing either the subject claim or error message
+    pub fn verify_without_consuming(&self, issuer: String, jwt: String, sign_payload: Vec<u8>, predecessor: AccountId) -> (bool, String) {
+        self.internal_verify_unconsumed(issuer, jwt, sign_payload, predecessor)
+    }
+
+    /// Enables or disables JWT replay protection
+    /// # Arguments
+    /// * `enabled` - Whether consumed tokens must be rejected
+    /// # Panics
+    /// Panics if the caller is not authorized (DAO role)
+    #[access_control_any(roles(Role::DAO))]
+    pub fn set_replay_protection(&mut self, enabled: bool) {
+        self.replay_protection.set_enabled(enabled);
+        JwtGuardEvent::ReplayProtectionUpdated { enabled }.emit();
+    }
+
+    /// Sets the accounts whose verification requests consume tokens, the router or FastAuth account
+    /// # Arguments
+    /// * `consumers` - The accounts, replacing the current ones
+    /// # Panics
+    /// Panics if the caller is not authorized (DAO role)
+    #[access_control_any(roles(Role::DAO))]
+    pub fn set_token_consumers(&mut self, consumers: Vec<AccountId>) {
+        JwtGuardEvent::TokenConsumersUpdated { consumers: &consumers }.emit();
+        self.replay_protection.set_consumers(consumers);
+    }
+
+    /// Gets the replay protection state and its storage usage
+    /// # Returns
+    /// * `ReplayProtectionView` - The replay protection summary
+    pub fn get_replay_protection(&self) -> ReplayProtectionView {
+        self.replay_protection.view()
+    }
+
+    /// Removes expired consumed tokens, freeing their storage
+    /// # Arguments
+    /// * `limit` - The maximum number of consumed tokens to inspect
+    /// # Returns
+    /// * `u32` - The number of removed consumed tokens
+    pub fn prune_consumed_tokens(&mut self, limit: u32) -> u32 {
+        self.replay_protection.prune(limit)
+    }
+}
+
+impl ReplayProtectedJwtGuard for CustomIssuerGuard {
+    fn replay_protection(&self) -> &ReplayProtection {
+        &self.replay_protection
+    }
+
+    fn replay_protection_mut(&mut self) -> &mut ReplayProtection {
+        &mut self.replay_protection
+    }
 }
 
 #[near]
@@ -224,6 +358,26 @@ impl JwtGuard for CustomIssuerGuard {
         self.public_keys.clone()
     }
 
+    /// Gets the current Ed25519 public keys
+    ///
+    /// # Returns
+    /// * `Vec<JwtOkpPublicKey>` - The keys verifying EdDSA tokens
+    fn get_okp_public_keys(&self) -> Vec<JwtOkpPublicKey> {
+        self.okp_public_keys.clone()
+    }
+
+    /// Gets the signature algorithms the guard accepts
+    ///
+    /// # Returns
+    /// * `Vec<JwtAlgorithm>` - RS256, and EdDSA once Ed25519 public keys are set
+    fn allowed_algorithms(&self) -> Vec<JwtAlgorithm> {
+        if self.okp_public_keys.is_empty() {
+            vec![JwtAlgorithm::RS256]
+        } else {
+            vec![JwtAlgorithm::RS256, JwtAlgorithm::EdDSA]
+        }
+    }
+
     /// Verifies custom claims in the JWT payload
     /// # Arguments
     /// * `jwt` - JWT token
@@ -249,3 +403,70 @@ impl JwtGuard for CustomIssuerGuard {
         (true, "".parse().unwrap())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::test_utils::accounts;
+
+    #[test]
+    fn test_migrate_v1_layout() {
+        env::state_write(&CustomIssuerGuardV1 {
+            public_keys: vec![JwtPublicKeyV1 { n: vec![1; 256], e: vec![1, 0, 1] }],
+            attestation_contract: accounts(1),
+        });
+
+        let migrated = CustomIssuerGuard::migrate();
+        assert_eq!(migrated.attestation_contract, accounts(1));
+        assert_eq!(migrated.public_keys.len(), 1);
+        assert_eq!(migrated.public_keys[0].n, vec![1; 256]);
+        assert_eq!(migrated.public_keys[0].kid, None);
+        assert!(migrated.okp_public_keys.is_empty());
+        assert!(!migrated.replay_protection.is_enabled());
+    }
+
+    #[test]
+    fn test_on_set_okp_public_keys_callback() {
+        let mut contract = CustomIssuerGuard {
+            public_keys: vec![],
+            attestation_contract: accounts(1),
+            replay_protection: ReplayProtection::new(Prefix::ConsumedTokens),
+            okp_public_keys: vec![],
+        };
+
+        contract.on_set_okp_public_keys_callback(Ok(vec![JwtOkpPublicKey { x: vec![2; 32], kid: None }]));
+        assert_eq!(contract.okp_public_keys.len(), 1);
+        assert_eq!(contract.allowed_algorithms(), vec![JwtAlgorithm::RS256, JwtAlgorithm::EdDSA]);
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid x component length")]
+    fn test_on_set_okp_public_keys_callback_invalid_key() {
+        let mut contract = CustomIssuerGuard {
+            public_keys: vec![],
+            attestation_contract: accounts(1),
+            replay_protection: ReplayProtection::new(Prefix::ConsumedTokens),
+            okp_public_keys: vec![],
+        };
+
+        contract.on_set_okp_public_keys_callback(Ok(vec![JwtOkpPublicKey { x: vec![2; 31], kid: None }]));
+    }
+
+    #[test]
+    fn test_migrate_current_layout() {
+        let contract = CustomIssuerGuard {
+            public_keys: vec![JwtPublicKey { n: vec![1; 256], e: vec![1, 0, 1], kid: Some("key-1".to_string()) }],
+            attestation_contract: accounts(1),
+            replay_protection: ReplayProtection::new(Prefix::ConsumedTokens),
+            okp_public_keys: vec![JwtOkpPublicKey { x: vec![2; 32], kid: None }],
+        };
+        env::state_write(&contract);
+
+        let migrated = CustomIssuerGuard::migrate();
+        assert_eq!(migrated.attestation_contract, accounts(1));
+        assert_eq!(migrated.public_keys.len(), 1);
+        assert_eq!(migrated.public_keys[0].kid, Some("key-1".to_string()));
+        assert_eq!(migrated.okp_public_keys.len(), 1);
+        assert_eq!(migrated.okp_public_keys[0].x, vec![2; 32]);
+    }
+}
@@ -1,4 +1,4 @@
-use near_sdk::{near};
+use near_sdk::{near, AccountId, PublicKey};
 use near_sdk::borsh::{BorshDeserialize, BorshSerialize};
 use near_sdk::serde::{Deserialize, Serialize};
 use schemars::JsonSchema;
@@ -56,4 +56,9 @@ impl MockMPC {
             recovery_id: 0,
         }
     }
-}
+
+    pub fn derived_public_key(&self, _path: String, _predecessor: Option<AccountId>, _domain_id: Option<u64>) -> PublicKey {
+        // For testing, we'll always return the secp256k1 generator point
+        "secp256k1:3SB8tA9Kbn7FBtT6GWR6AJk73QceudisHaGThPoLCDgC9tan7d3cwZFiDZtrmhSAf8aTynEdQ3N7KXhMm3nWhekP".parse().unwrap()
+    }
+}
\ No newline at end of file
@@ -1,5 +1,5 @@
 import { ec as EC } from "elliptic";
-import { MPCSignaturePayload, Algorithm } from "./types";
+import { MPCSignaturePayload, Algorithm, SignOutcome } from "./types";
 import { FastAuthSignatureError } from "./signature.errors";
 import { ED25519ErrorCodes, SECP256K1ErrorCodes, SignatureErrorCodes } from "./signature.error-codes";
 
@@ -15,11 +15,18 @@ export class FastAuthSignature {
 
     /**
      * Create a FastAuthSignature from a base64 payload.
-     * @param base64Payload The base64 payload to create the signature from.
+     * @param base64Payload The base64 payload to create the signature from, either a sign outcome or a bare MPC signature.
      * @returns A new FastAuthSignature instance.
+     * @throws FastAuthSignatureError if the sign outcome is a failure.
      */
     static fromBase64(base64Payload: string): FastAuthSignature {
-        const payload = JSON.parse(Buffer.from(base64Payload, "base64").toString());
+        const payload = JSON.parse(Buffer.from(base64Payload, "base64").toString()) as SignOutcome | MPCSignaturePayload;
+        if ("status" in payload) {
+            if (payload.status === "failed") {
+                throw new FastAuthSignatureError(SignatureErrorCodes.SIGN_FAILED, `${payload.code}: ${payload.reason}`);
+            }
+            return new FastAuthSignature(payload.signature);
+        }
         return new FastAuthSignature(payload);
     }
 
@@ -10,6 +10,20 @@ describe("FastAuthSignature", () => {
             const sig = FastAuthSignature.fromBase64(base64);
             expect(() => sig.recover("ed25519")).not.toThrow();
         });
+
+        it("should unwrap a signed sign outcome", () => {
+            const payload = { status: "signed", signature: { signature: new Array(64).fill(1) } };
+            const base64 = Buffer.from(JSON.stringify(payload)).toString("base64");
+            const sig = FastAuthSignature.fromBase64(base64);
+            expect(sig.recover("ed25519").length).toBe(64);
+        });
+
+        it("should throw for a failed sign outcome", () => {
+            const payload = { status: "failed", code: "verification_rejected", reason: "Token expired" };
+            const base64 = Buffer.from(JSON.stringify(payload)).toString("base64");
+            expect(() => FastAuthSignature.fromBase64(base64)).toThrow(FastAuthSignatureError);
+            expect(() => FastAuthSignature.fromBase64(base64)).toThrow(SignatureErrorCodes.SIGN_FAILED);
+        });
     });
 
     describe("recover - ed25519", () => {
@@ -1,8 +1,11 @@
 import { FastAuthSignatureErrorCodes } from "./signature.error-codes";
 
 export class FastAuthSignatureError extends Error {
-    constructor(code: FastAuthSignatureErrorCodes) {
-        super(code.toString());
+    readonly reason?: string;
+
+    constructor(code: FastAuthSignatureErrorCodes, reason?: string) {
+        super(reason ? `${code}: ${reason}` : code.toString());
         this.name = "FastAuthSignatureError";
+        this.reason = reason;
     }
 }
@@ -16,4 +16,17 @@ export type EDDSASignaturePayload = {
 
 export type MPCSignaturePayload = ECDSASignaturePayload | EDDSASignaturePayload;
 
+export type SignErrorCode = "guard_call_failed" | "verification_rejected" | "invalid_sub" | "guard_frozen" | "mpc_failed" | "rate_limited" | "spending_limit_exceeded" | "approval_required" | "approval_pending" | "approval_rejected";
+
+export type SignOutcome =
+    | {
+          status: "signed";
+          signature: MPCSignaturePayload;
+      }
+    | {
+          status: "failed";
+          code: SignErrorCode;
+          reason: string;
+      };
+
 export type Algorithm = "secp256k1" | "ed25519";